@@ -0,0 +1,5 @@
+// blue over green and yellow to red
+vec3 heatmap(const float x) {
+    float t = clamp(x, 0, 1);
+    return clamp(vec3(t * 4 - 2, t < 0.5 ? t * 4 : 4 - t * 4, 2 - t * 4), 0, 1);
+}
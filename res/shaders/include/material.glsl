@@ -0,0 +1,50 @@
+// must match GpuMaterial and GpuTextureMap in material.rs
+
+#define MAP_AMBIENT 0
+#define MAP_DIFFUSE 1
+#define MAP_SPECULAR 2
+#define MAP_SPECULAR_EXP 3
+#define MAP_EMISSIVE 4
+#define MAP_ALPHA 5
+#define MAP_BUMP 6
+#define MAP_NORMAL 7
+#define MAP_DISPLACEMENT 8
+#define MAP_DECAL 9
+#define MAP_METALLIC_ROUGHNESS 10
+#define MAP_OCCLUSION 11
+#define TEXTURE_MAP_COUNT 12
+
+#define CHANNEL_RED 0
+#define CHANNEL_GREEN 1
+#define CHANNEL_BLUE 2
+#define CHANNEL_MATTE 3
+#define CHANNEL_LUMINANCE 4
+
+// the texture arrays of the maps, must match MaterialTextureArray
+#define ARRAY_SRGB 0
+#define ARRAY_LINEAR 1
+#define ARRAY_LINEAR16 2
+#define ARRAY_FLOAT 3
+
+struct TextureMap {
+    vec4 transform; // xy: scale, zw: offset
+    vec2 range; // x: base, y: gain
+    int layer; // layer in the texture array, -1 if unused
+    int channel;
+    float bumpMultiplier;
+    bool clampCoords;
+    int array; // color maps are in the srgb array, data maps in the linear array of their precision
+};
+
+struct Material {
+    vec4 ambient; // rgb: ambient color
+    vec4 diffuse; // rgb: diffuse color, a: dissolve
+    vec4 specular; // rgb: specular color, a: specular exponent
+    vec4 emissive; // rgb: emissive color, a: optical density
+    vec4 transmission; // rgb: transmission filter color, a: transmission
+    int illum;
+    float metallic;
+    float roughness;
+    float alphaCutoff; // alpha maps cut out texels below it
+    TextureMap maps[TEXTURE_MAP_COUNT];
+};
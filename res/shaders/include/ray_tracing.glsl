@@ -0,0 +1,37 @@
+#define MISS 1e30
+#define EPSILON 0.000001
+#define NO_RAY vec3(0, 0, 0)
+
+#ifndef NODE_STACK_SIZE
+#define NODE_STACK_SIZE 100
+#endif
+
+struct Ray {
+    vec3 org, dir, rDir;
+};
+
+struct AABB {
+    float minx, miny, minz;
+    float maxx, maxy, maxz;
+};
+
+struct Node {
+    AABB aabb;
+    bool is_leaf;
+    uint a, b;
+};
+
+struct Triangle {
+    uint p0, p1, p2, matIdx;
+};
+
+struct Intersection {
+    float t;
+    float u, v;
+    uint tringleIdx;
+};
+
+struct NodeStack {
+    uint nodes[NODE_STACK_SIZE];
+    uint idx;
+};
@@ -0,0 +1,8 @@
+// the vertex buffers hold tightly packed floats, as vec3 arrays would be padded to 16 bytes. these
+// are macros, so only the buffers that are used need to be declared, as positions, texCoords and normals
+#define fetchVec2(data, index) vec2(data[(index) * 2 + 0], data[(index) * 2 + 1])
+#define fetchVec3(data, index) vec3(data[(index) * 3 + 0], data[(index) * 3 + 1], data[(index) * 3 + 2])
+
+#define fetchPosition(index) fetchVec3(positions, index)
+#define fetchTexCoord(index) fetchVec2(texCoords, index)
+#define fetchNormal(index) fetchVec3(normals, index)
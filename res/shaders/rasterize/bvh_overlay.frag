@@ -0,0 +1,10 @@
+#version 460 core
+
+#include "include/heatmap.glsl"
+
+in float vertCost;
+out vec4 fragCol;
+
+void main() {
+    fragCol = vec4(heatmap(vertCost), 1);
+}
@@ -0,0 +1,13 @@
+#version 460 core
+
+layout (location = 0) in vec3 position;
+layout (location = 1) in float cost;
+
+out float vertCost;
+
+layout (location = 0) uniform mat4 mvp;
+
+void main() {
+    vertCost = cost;
+    gl_Position = mvp * vec4(position, 1);
+}
@@ -1,17 +1,114 @@
 #version 460 core
 
+#include "include/material.glsl"
+
 in vec3 vertPosition;
 in vec2 vertTexCoords;
 in vec3 vertNormal;
+in vec4 vertTangent; // xyz: tangent, w: handedness, zero if the model has no tangents
 
 layout (location = 0) out vec3 position;
 layout (location = 1) out vec4 normalMat;
 layout (location = 2) out vec2 texCoords;
 
-layout (location = 1) uniform int materialIdx;
+layout (location = 2) uniform sampler2DArray srgbTextures;
+layout (location = 3) uniform sampler2DArray linearTextures;
+layout (location = 4) uniform sampler2DArray linear16Textures;
+layout (location = 5) uniform sampler2DArray floatTextures;
+
+layout (std430, binding = 0) buffer materialBuffer { Material materials[]; };
+layout (std430, binding = 1) buffer triangleMaterialBuffer { uint triangleMaterials[]; };
+
+bool hasMap(const int mat, const int map) {
+    return materials[mat].maps[map].layer >= 0;
+}
+
+vec4 sampleMap(const int mat, const int map, const vec2 uv, const vec2 dx, const vec2 dy) {
+    TextureMap m = materials[mat].maps[map];
+    vec2 coord = uv * m.transform.xy + m.transform.zw;
+    if (m.clampCoords) coord = clamp(coord, 0, 1);
+    vec3 layerCoord = vec3(coord, m.layer);
+    vec2 mapDx = dx * m.transform.xy, mapDy = dy * m.transform.xy;
+    switch (m.array) {
+        case ARRAY_SRGB: return textureGrad(srgbTextures, layerCoord, mapDx, mapDy);
+        case ARRAY_LINEAR16: return textureGrad(linear16Textures, layerCoord, mapDx, mapDy);
+        case ARRAY_FLOAT: return textureGrad(floatTextures, layerCoord, mapDx, mapDy);
+        default: return textureGrad(linearTextures, layerCoord, mapDx, mapDy);
+    }
+}
+
+// all layers of a texture array have the same size
+vec2 mapSize(const TextureMap m) {
+    switch (m.array) {
+        case ARRAY_SRGB: return vec2(textureSize(srgbTextures, 0).xy);
+        case ARRAY_LINEAR16: return vec2(textureSize(linear16Textures, 0).xy);
+        case ARRAY_FLOAT: return vec2(textureSize(floatTextures, 0).xy);
+        default: return vec2(textureSize(linearTextures, 0).xy);
+    }
+}
+
+float sampleScalar(const int mat, const int map, const vec2 uv, const vec2 dx, const vec2 dy) {
+    TextureMap m = materials[mat].maps[map];
+    vec4 texel = sampleMap(mat, map, uv, dx, dy);
+    float value;
+    switch (m.channel) {
+        case CHANNEL_RED: value = texel.r; break;
+        case CHANNEL_GREEN: value = texel.g; break;
+        case CHANNEL_BLUE: value = texel.b; break;
+        case CHANNEL_MATTE: value = texel.a; break;
+        default: value = dot(texel.rgb, vec3(0.2126, 0.7152, 0.0722)); break;
+    }
+    return m.range.x + value * m.range.y;
+}
+
+// tangent frame from screen space derivatives, so no vertex tangents are needed
+mat3 cotangentFrame(const vec3 normal, const vec3 p, const vec2 uv) {
+    vec3 dp1 = dFdx(p), dp2 = dFdy(p);
+    vec2 duv1 = dFdx(uv), duv2 = dFdy(uv);
+    vec3 dp2perp = cross(dp2, normal), dp1perp = cross(normal, dp1);
+    vec3 t = dp2perp * duv1.x + dp1perp * duv2.x;
+    vec3 b = dp2perp * duv1.y + dp1perp * duv2.y;
+    float invMax = inversesqrt(max(max(dot(t, t), dot(b, b)), 1e-20));
+    return mat3(t * invMax, b * invMax, normal);
+}
+
+vec3 perturbNormal(const int mat, vec3 normal, const mat3 tbn, const vec2 uv, const vec2 dx, const vec2 dy) {
+    if (hasMap(mat, MAP_NORMAL)) {
+        vec3 n = sampleMap(mat, MAP_NORMAL, uv, dx, dy).xyz * 2 - 1;
+        n.xy *= materials[mat].maps[MAP_NORMAL].bumpMultiplier;
+        normal = normalize(tbn * n);
+    }
+    // the geometry is not tessellated, so displacement maps are applied like bump maps
+    int heightMap = hasMap(mat, MAP_BUMP) ? MAP_BUMP : MAP_DISPLACEMENT;
+    if (hasMap(mat, heightMap)) {
+        TextureMap m = materials[mat].maps[heightMap];
+        // height differences to the neighbouring texels
+        vec2 texel = 1.0 / (mapSize(m) * m.transform.xy);
+        float h = sampleScalar(mat, heightMap, uv, dx, dy);
+        float hu = sampleScalar(mat, heightMap, uv + vec2(texel.x, 0), dx, dy);
+        float hv = sampleScalar(mat, heightMap, uv + vec2(0, texel.y), dx, dy);
+        vec2 gradient = vec2(hu - h, hv - h) * m.bumpMultiplier;
+        normal = normalize(normal - gradient.x * tbn[0] - gradient.y * tbn[1]);
+    }
+    return normal;
+}
 
 void main() {
+    int materialIdx = int(triangleMaterials[gl_PrimitiveID]);
+    vec2 dx = dFdx(vertTexCoords), dy = dFdy(vertTexCoords);
+    vec3 geometricNormal = normalize(cross(dFdx(vertPosition), dFdy(vertPosition)));
+    vec3 normal = dot(vertNormal, vertNormal) > 0 ? normalize(vertNormal) : geometricNormal;
+    mat3 tbn;
+    if (dot(vertTangent.xyz, vertTangent.xyz) > 0) {
+        vec3 tangent = normalize(vertTangent.xyz - normal * dot(normal, vertTangent.xyz));
+        tbn = mat3(tangent, cross(normal, tangent) * vertTangent.w, normal);
+    } else {
+        tbn = cotangentFrame(normal, vertPosition, vertTexCoords);
+    }
+
+    if (hasMap(materialIdx, MAP_ALPHA) && sampleScalar(materialIdx, MAP_ALPHA, vertTexCoords, dx, dy) < materials[materialIdx].alphaCutoff) discard;
+
     position = vertPosition;
-    normalMat = vec4(vertNormal, intBitsToFloat(materialIdx));
+    normalMat = vec4(perturbNormal(materialIdx, normal, tbn, vertTexCoords, dx, dy), intBitsToFloat(materialIdx));
     texCoords = vertTexCoords;
-}
\ No newline at end of file
+}
@@ -3,16 +3,19 @@
 layout (location = 0) in vec3 position;
 layout (location = 1) in vec2 texCoords;
 layout (location = 2) in vec3 normal;
+layout (location = 3) in vec4 tangent;
 
 out vec3 vertPosition;
 out vec2 vertTexCoords;
 out vec3 vertNormal;
+out vec4 vertTangent;
 
 layout (location = 0) uniform mat4 mvp;
 void main() {
     vertPosition = position;
     vertTexCoords = texCoords;
     vertNormal = normal;
+    vertTangent = tangent;
 
     gl_Position = mvp * vec4(position, 1);
 }
\ No newline at end of file
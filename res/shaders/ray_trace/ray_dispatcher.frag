@@ -1,6 +1,8 @@
 #version 460 core
 
-#define NO_RAY vec3(0, 0, 0)
+#include "include/ray_tracing.glsl"
+#include "include/material.glsl"
+
 #define RAY_ORG_OFFSET 0.0001
 
 in vec2 fragPos;
@@ -10,16 +12,14 @@ layout (location = 1) out vec3 shadowDir;
 layout (location = 2) out vec3 reflectDir;
 layout (location = 3) out vec3 ambientDir;
 
-struct Material {
-    bool reflect;
-};
-
 layout (location = 0) uniform sampler2D positionData; // xyz: position
 layout (location = 1) uniform sampler2D normalMatData; // xyz: normal, w: material idx
 layout (location = 2) uniform sampler2D blueNoise; // xyz: noise, is unit length vector
 layout (location = 3) uniform vec3 lightPos;
 layout (location = 4) uniform vec3 cameraPos;
 layout (location = 5) uniform vec4 noiseOffsetScale; // xy: offset, zw: scale
+// replaces the illumination model of all materials if not negative
+layout (location = 6) uniform int illumOverride;
 
 layout (std430, binding = 0) buffer matBuffer { Material materials[]; };
 
@@ -32,11 +32,12 @@ void main() {
     vec3 position = texture(positionData, fragPos).xyz;
     //vec3 random = texelFetch(blueNoise, ivec2((fragPos * noiseOffsetScale.zw + noiseOffsetScale.xy) * 512) % 512, 0).xyz;
     vec3 random = texture(blueNoise, fragPos * noiseOffsetScale.zw + noiseOffsetScale.xy).xyz;
-    Material material = materials[materialIdx];
+    int illum = illumOverride >= 0 ? illumOverride : materials[materialIdx].illum;
 
     org = position + normal * RAY_ORG_OFFSET;
-    shadowDir = normalize(lightPos - position);
-    //reflectDir = material.reflect ? reflect(position - cameraPos, normal) : NO_RAY;
-    reflectDir = normalize(reflect(position - cameraPos, normal));
-    ambientDir = normalize(normal + random * 2 - 1);
+    // illumination model 0 is unlit, so it needs no shadow or ambient rays
+    shadowDir = illum == 0 ? NO_RAY : normalize(lightPos - position);
+    // only illumination models 3 to 7 use ray traced reflections
+    reflectDir = illum >= 3 && illum <= 7 ? normalize(reflect(position - cameraPos, normal)) : NO_RAY;
+    ambientDir = illum == 0 ? NO_RAY : normalize(normal + random * 2 - 1);
 }
\ No newline at end of file
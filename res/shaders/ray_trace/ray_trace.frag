@@ -1,56 +1,68 @@
 #version 460 core
 
-#define MISS 1e30
-#define EPSILON 0.000001
-#define NODE_STACK_SIZE 100
-#define NO_RAY vec3(0, 0, 0)
+#include "include/ray_tracing.glsl"
+#include "include/material.glsl"
+#include "include/vertex_data.glsl"
 
 in vec2 fragPos;
 layout (location = 0) out vec4 intersection;
-
-struct Ray {
-    vec3 org, dir, rDir;
-};
-
-struct AABB {
-    float minx, miny, minz;
-    float maxx, maxy, maxz;
-};
-
-struct Node {
-    AABB aabb;
-    bool is_leaf;
-    uint a, b;
-};
-
-struct Triangle {
-    uint p0, p1, p2, matIdx;
-};
-
-struct Intersection {
-    float t;
-    float u, v;
-    uint tringleIdx;
-};
-
-struct NodeStack {
-    uint nodes[NODE_STACK_SIZE];
-    uint idx;
-};
+// DEBUG_TRAVERSAL is defined by the variant tracing camera rays for the debug views, it writes the
+// number of nodes and triangles tested per ray
+#ifdef DEBUG_TRAVERSAL
+layout (location = 1) out vec2 traversalCost;
+#endif
 
 layout (location = 0) uniform sampler2D dir;
+#ifdef DEBUG_TRAVERSAL
+layout (location = 1) uniform vec3 cameraPos;
+#else
 layout (location = 1) uniform sampler2D org;
+#endif
+// HAS_TEX_COORDS is defined by variants for scenes with tex coords
+layout (location = 3) uniform sampler2DArray srgbTextures;
+layout (location = 4) uniform sampler2DArray linearTextures;
+layout (location = 5) uniform sampler2DArray linear16Textures;
+layout (location = 6) uniform sampler2DArray floatTextures;
 
 layout (std430, binding = 0) buffer nodeBuffer { Node nodes[]; };
 layout (std430, binding = 1) buffer triangleBuffer { Triangle triangles[]; };
 layout (std430, binding = 2) buffer positionBuffer { float positions[]; };
-
-vec3 fetchPosition(uint index) {
-    return vec3(
-        positions[index * 3 + 0],
-        positions[index * 3 + 1],
-        positions[index * 3 + 2]
-    );
+layout (std430, binding = 3) buffer texCoordBuffer { float texCoords[]; };
+layout (std430, binding = 4) buffer materialBuffer { Material materials[]; };
+
+#ifdef DEBUG_TRAVERSAL
+uint nodesTested = 0, trianglesTested = 0;
+#endif
+
+// returns false if the hit lies in a cut out part of a triangle with an alpha map
+bool alphaTest(const uint triangleIdx, const float u, const float v) {
+    Triangle triangle = triangles[triangleIdx];
+    TextureMap m = materials[triangle.matIdx].maps[MAP_ALPHA];
+    if (m.layer < 0) return true;
+
+    vec2 uv = vec2(0, 0);
+#ifdef HAS_TEX_COORDS
+    uv = fetchTexCoord(triangle.p1) * u + fetchTexCoord(triangle.p2) * v + fetchTexCoord(triangle.p0) * (1 - u - v);
+#endif
+    uv = uv * m.transform.xy + m.transform.zw;
+    if (m.clampCoords) uv = clamp(uv, 0, 1);
+    vec3 layerCoord = vec3(uv, m.layer);
+    vec4 texel;
+    switch (m.array) {
+        case ARRAY_SRGB: texel = textureLod(srgbTextures, layerCoord, 0); break;
+        case ARRAY_LINEAR16: texel = textureLod(linear16Textures, layerCoord, 0); break;
+        case ARRAY_FLOAT: texel = textureLod(floatTextures, layerCoord, 0); break;
+        default: texel = textureLod(linearTextures, layerCoord, 0); break;
+    }
+    float alpha;
+    switch (m.channel) {
+        case CHANNEL_RED: alpha = texel.r; break;
+        case CHANNEL_GREEN: alpha = texel.g; break;
+        case CHANNEL_BLUE: alpha = texel.b; break;
+        case CHANNEL_MATTE: alpha = texel.a; break;
+        default: alpha = dot(texel.rgb, vec3(0.2126, 0.7152, 0.0722)); break;
+    }
+    return m.range.x + alpha * m.range.y >= materials[triangle.matIdx].alphaCutoff;
 }
 
 float intersectAABB(const Ray ray, const AABB aabb, const float t) {
@@ -66,6 +78,9 @@ float intersectAABB(const Ray ray, const AABB aabb, const float t) {
 void intersectTriangle(const Ray ray, const uint triangleIdx, inout Intersection i) {
     vec3 edge1, edge2, h, s, q, p0, p1, p2;
     float a, f, t, u, v;
+#ifdef DEBUG_TRAVERSAL
+    trianglesTested++;
+#endif
     {
         Triangle triangle = triangles[triangleIdx];
         p0 = fetchPosition(triangle.p0);
@@ -93,7 +108,7 @@ void intersectTriangle(const Ray ray, const uint triangleIdx, inout Intersection
 
     t = f * dot(edge2, q);
 
-    if (t > EPSILON && t < i.t) {
+    if (t > EPSILON && t < i.t && alphaTest(triangleIdx, u, v)) {
         i.t = t;
         i.u = u;
         i.v = v;
@@ -109,6 +124,9 @@ void traverseBVH(const Ray ray, inout Intersection i) {
 
     while (stack.idx > 0) {
         Node node = nodes[stack.nodes[--stack.idx]];
+#ifdef DEBUG_TRAVERSAL
+        nodesTested++;
+#endif
         if (node.is_leaf) {
             for (uint idx = node.a; idx < node.a + node.b; idx++) {
                 intersectTriangle(ray, idx, i);
@@ -132,7 +150,11 @@ void main() {
     vec3 dir = texture(dir, fragPos).xyz;
     if (dir == NO_RAY) discard;
 
+#ifdef DEBUG_TRAVERSAL
+    vec3 org = cameraPos;
+#else
     vec3 org = texture(org, fragPos).xyz;
+#endif
     Intersection i = Intersection(MISS, 0, 0, 0);
     Ray ray = Ray(org, dir, 1 / dir);
     traverseBVH(ray, i);
@@ -140,4 +162,7 @@ void main() {
         i.t, i.u, i.v,
         uintBitsToFloat(i.tringleIdx)
     );
+#ifdef DEBUG_TRAVERSAL
+    traversalCost = vec2(nodesTested, trianglesTested);
+#endif
 }
\ No newline at end of file
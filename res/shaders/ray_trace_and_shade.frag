@@ -1,15 +1,7 @@
 #version 460 core
 
-#extension GL_EXT_nonuniform_qualifier : enable
-#ifdef GL_EXT_nonuniform_qualifier
-    #define NON_UNIFORM nonuniformEXT
-#else
-    #define NON_UNIFORM
-#endif
-
-#define MISS 1e30
-#define EPSILON 0.000001
-#define NODE_STACK_SIZE 100
+#include "include/ray_tracing.glsl"
+#include "include/vertex_data.glsl"
 
 in vec2 fragPos;
 out vec4 fragCol;
@@ -18,31 +10,8 @@ layout (location = 0) uniform sampler2D dir;
 layout (location = 1) uniform sampler2D org;
 layout (location = 2) uniform bool hasTexCoords;
 layout (location = 3) uniform bool hasNormals;
-layout (location = 4) uniform sampler2D modelTextures[2];
-
-struct Ray {
-    vec3 org, dir, rDir;
-};
-
-struct AABB {
-    float minx, miny, minz;
-    float maxx, maxy, maxz;
-};
-
-struct Node {
-    AABB aabb;
-    bool is_leaf;
-    uint a, b;
-};
-
-struct Triangle {
-    uint p0, p1, p2, matIdx;
-};
-
-struct NodeStack {
-    uint nodes[NODE_STACK_SIZE];
-    uint idx;
-};
+// one layer per material
+layout (location = 4) uniform sampler2DArray modelTextures;
 
 layout (std430, binding = 0) buffer nodeBuffer { Node nodes[]; };
 layout (std430, binding = 1) buffer triangleBuffer { Triangle triangles[]; };
@@ -86,29 +55,6 @@ float intersectTriangle(const Ray ray, const vec3 p0, const vec3 p1, const vec3
     return f * dot(edge2, q);
 }
 
-vec3 fetchPosition(uint index) {
-    return vec3(
-    positions[index * 3 + 0],
-    positions[index * 3 + 1],
-    positions[index * 3 + 2]
-    );
-}
-
-vec2 fetchTexCoord(uint index) {
-    return vec2(
-    texCoords[index * 2 + 0],
-    texCoords[index * 2 + 1]
-    );
-}
-
-vec3 fetchNormal(uint index) {
-    return vec3(
-    normals[index * 3 + 0],
-    normals[index * 3 + 1],
-    normals[index * 3 + 2]
-    );
-}
-
 void traverseBVH(const Ray ray, inout float t, inout uint triangleIdx, inout vec2 triangleUV, inout uint intersections) {
     NodeStack stack;
 
@@ -218,7 +164,7 @@ void main() {
         traverseBVH(shadow_ray, shadow_t, shadow_triangleIdx, uv, intersections);
         bool shadow = shadow_t < 1000;
 
-        vec3 albedo = texture(modelTextures[NON_UNIFORM(triangles[triangleIdx].matIdx)], vec2(texCoord.x, -texCoord.y)).xyz;
+        vec3 albedo = texture(modelTextures, vec3(texCoord.x, -texCoord.y, triangles[triangleIdx].matIdx)).xyz;
         vec3 ambient = albedo * skybox(normal) * AMBIENT;
         vec3 diffuse = albedo * SUN_COL * clamp(dot(normal, SUN_DIR), 0, 1) * DIFFUSE;
         vec3 specular = SUN_COL * clamp(pow(dot(reflected, SUN_DIR), SPEC_POW), 0, 1) * SPECULAR;
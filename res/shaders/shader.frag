@@ -1,13 +1,9 @@
 #version 460 core
 
-#extension GL_EXT_nonuniform_qualifier : enable
-#ifdef GL_EXT_nonuniform_qualifier
-#define NON_UNIFORM nonuniformEXT
-#else
-#define NON_UNIFORM
-#endif
+#include "include/ray_tracing.glsl"
+#include "include/material.glsl"
+#include "include/vertex_data.glsl"
 
-#define MISS 1e30
 #define NO_MATERIAL 1e30
 
 const vec3 SUN_DIR = normalize(vec3(1, 2, 1));
@@ -15,22 +11,16 @@ const vec3 SUN_COL = vec3(1, 0.97, 0.86);
 #define TOP_SKY vec3(0.5, 0.7, 0.9)
 #define BOTTOM_SKY vec3(0.2, 0.5, 0.8)
 
-#define DEFAULT_AMBIENT vec3(0.3)
-#define DEFAULT_DIFFUSE vec3(1.0)
-#define DEFAULT_SPECULAR vec3(1.0)
-#define DEFAULT_SPEC_EXP 30.00
-
 in vec2 fragPos;
 layout (location = 0) out vec4 color;
 
-struct Intersection {
-    float t;
-    float u, v;
-    uint tringleIdx;
-};
-
-struct Triangle {
-    uint p0, p1, p2, matIdx;
+// a shaded point with the gradients of its tex coords, approximated with ray cones for ray hits
+struct Surface {
+    vec3 position;
+    vec3 normal;
+    vec2 texCoord;
+    vec2 dx, dy;
+    uint materialIdx;
 };
 
 layout (location = 0) uniform sampler2D position;
@@ -49,62 +39,147 @@ layout (location = 9) uniform sampler2D ambientHits;
 layout (location = 10) uniform vec3 lightPos;
 layout (location = 11) uniform vec3 cameraPos;
 
-layout (location = 12) uniform bool hasNormalBuffer;
-layout (location = 13) uniform bool hasTexCoordBuffer;
+// HAS_NORMALS and HAS_TEX_COORDS are defined by variants for scenes with these vertex buffers
+// the material textures are a texture array per format, so that the texture of a map can be picked
+// per fragment without indexing an array of samplers
+layout (location = 14) uniform sampler2DArray srgbTextures;
+layout (location = 15) uniform sampler2DArray linearTextures;
+layout (location = 16) uniform sampler2DArray linear16Textures;
+layout (location = 17) uniform sampler2DArray floatTextures;
+layout (location = 30) uniform float pixelSpreadAngle;
+layout (location = 31) uniform vec3 lightColor;
+layout (location = 32) uniform float ambientStrength;
+layout (location = 33) uniform float reflectionStrength;
+// replaces the illumination model of all materials if not negative
+layout (location = 34) uniform int illumOverride;
+layout (location = 35) uniform bool whiteDiffuse;
 
 layout (std430, binding = 0) buffer triangleBuffer { Triangle triangles[]; };
-layout (std430, binding = 1) buffer positionBuffer { float triPositions[]; };
-layout (std430, binding = 2) buffer texCoordBuffer { float triTexCoords[]; };
-layout (std430, binding = 3) buffer normalBuffer { float triNormals[]; };
-
-vec3 fetchPosition(uint index) {
-    return vec3(
-        triPositions[index * 3 + 0],
-        triPositions[index * 3 + 1],
-        triPositions[index * 3 + 2]
-    );
+layout (std430, binding = 1) buffer positionBuffer { float positions[]; };
+layout (std430, binding = 2) buffer texCoordBuffer { float texCoords[]; };
+layout (std430, binding = 3) buffer normalBuffer { float normals[]; };
+layout (std430, binding = 4) buffer materialBuffer { Material materials[]; };
+
+vec2 triangleTexCoord(const uint idx, const vec2 uv) {
+#ifdef HAS_TEX_COORDS
+    vec2 t0 = fetchTexCoord(triangles[idx].p0);
+    vec2 t1 = fetchTexCoord(triangles[idx].p1);
+    vec2 t2 = fetchTexCoord(triangles[idx].p2);
+    float w = 1 - uv.x - uv.y;
+    return t1 * uv.x + t2 * uv.y + t0 * w;
+#else
+    return vec2(0, 0);
+#endif
 }
 
-vec2 fetchTexCoord(uint index) {
-    return vec2(
-        triTexCoords[index * 2 + 0],
-        triTexCoords[index * 2 + 1]
-    );
+vec3 triangleNormal(const uint idx, const vec2 uv) {
+#ifdef HAS_NORMALS
+    vec3 n0 = fetchNormal(triangles[idx].p0);
+    vec3 n1 = fetchNormal(triangles[idx].p1);
+    vec3 n2 = fetchNormal(triangles[idx].p2);
+    float w = 1 - uv.x - uv.y;
+    vec3 normal = n1 * uv.x + n2 * uv.y + n0 * w;
+    // models without normals have zero normals in a merged scene
+    if (dot(normal, normal) > 0) return normalize(normal);
+#endif
+    vec3 v0 = fetchPosition(triangles[idx].p0);
+    vec3 v1 = fetchPosition(triangles[idx].p1);
+    vec3 v2 = fetchPosition(triangles[idx].p2);
+    return normalize(cross(v1 - v0, v2 - v0));
 }
 
-vec3 fetchNormal(uint index) {
-    return vec3(
-        triNormals[index * 3 + 0],
-        triNormals[index * 3 + 1],
-        triNormals[index * 3 + 2]
-    );
+// tangent frame of a triangle from its positions and tex coords
+mat3 triangleTangentFrame(const uint idx, const vec3 normal) {
+#ifndef HAS_TEX_COORDS
+    return mat3(vec3(0), vec3(0), normal);
+#endif
+    Triangle triangle = triangles[idx];
+    vec3 e1 = fetchPosition(triangle.p1) - fetchPosition(triangle.p0);
+    vec3 e2 = fetchPosition(triangle.p2) - fetchPosition(triangle.p0);
+    vec2 d1 = fetchTexCoord(triangle.p1) - fetchTexCoord(triangle.p0);
+    vec2 d2 = fetchTexCoord(triangle.p2) - fetchTexCoord(triangle.p0);
+    float handedness = sign(d1.x * d2.y - d2.x * d1.y);
+    vec3 t = (e1 * d2.y - e2 * d1.y) * handedness;
+    vec3 b = (e2 * d1.x - e1 * d2.x) * handedness;
+    t -= normal * dot(normal, t);
+    b -= normal * dot(normal, b);
+    if (dot(t, t) < 1e-20 || dot(b, b) < 1e-20) return mat3(vec3(0), vec3(0), normal);
+    return mat3(normalize(t), normalize(b), normal);
 }
 
-vec2 triangleTexCoord(const uint idx, const vec2 uv) {
-    if (hasTexCoordBuffer) {
-        vec2 t0 = fetchTexCoord(triangles[idx].p0);
-        vec2 t1 = fetchTexCoord(triangles[idx].p1);
-        vec2 t2 = fetchTexCoord(triangles[idx].p2);
-        float w = 1 - uv.x - uv.y;
-        return t1 * uv.x + t2 * uv.y + t0 * w;
-    } else {
-        return vec2(0, 0);
+int illumination(const uint mat) {
+    return illumOverride >= 0 ? illumOverride : materials[mat].illum;
+}
+
+bool hasMap(const uint mat, const int map) {
+    return materials[mat].maps[map].layer >= 0;
+}
+
+vec4 sampleMap(const Surface s, const int map) {
+    TextureMap m = materials[s.materialIdx].maps[map];
+    vec2 coord = s.texCoord * m.transform.xy + m.transform.zw;
+    if (m.clampCoords) coord = clamp(coord, 0, 1);
+    vec3 layerCoord = vec3(coord, m.layer);
+    vec2 dx = s.dx * m.transform.xy, dy = s.dy * m.transform.xy;
+    switch (m.array) {
+        case ARRAY_SRGB: return textureGrad(srgbTextures, layerCoord, dx, dy);
+        case ARRAY_LINEAR16: return textureGrad(linear16Textures, layerCoord, dx, dy);
+        case ARRAY_FLOAT: return textureGrad(floatTextures, layerCoord, dx, dy);
+        default: return textureGrad(linearTextures, layerCoord, dx, dy);
     }
 }
 
-vec3 triangleNormal(const uint idx, const vec2 uv) {
-    if (hasNormalBuffer) {
-        vec3 n0 = fetchNormal(triangles[idx].p0);
-        vec3 n1 = fetchNormal(triangles[idx].p1);
-        vec3 n2 = fetchNormal(triangles[idx].p2);
-        float w = 1 - uv.x - uv.y;
-        return normalize(n1 * uv.x + n2 * uv.y + n0 * w);
-    } else {
-        vec3 v0 = fetchPosition(triangles[idx].p0);
-        vec3 v1 = fetchPosition(triangles[idx].p1);
-        vec3 v2 = fetchPosition(triangles[idx].p2);
-        return normalize(cross(v1 - v0, v2 - v0));
+// all layers of a texture array have the same size
+vec2 mapSize(const TextureMap m) {
+    switch (m.array) {
+        case ARRAY_SRGB: return vec2(textureSize(srgbTextures, 0).xy);
+        case ARRAY_LINEAR16: return vec2(textureSize(linear16Textures, 0).xy);
+        case ARRAY_FLOAT: return vec2(textureSize(floatTextures, 0).xy);
+        default: return vec2(textureSize(linearTextures, 0).xy);
+    }
+}
+
+float sampleScalar(const Surface s, const int map) {
+    TextureMap m = materials[s.materialIdx].maps[map];
+    vec4 texel = sampleMap(s, map);
+    float value;
+    switch (m.channel) {
+        case CHANNEL_RED: value = texel.r; break;
+        case CHANNEL_GREEN: value = texel.g; break;
+        case CHANNEL_BLUE: value = texel.b; break;
+        case CHANNEL_MATTE: value = texel.a; break;
+        default: value = dot(texel.rgb, vec3(0.2126, 0.7152, 0.0722)); break;
     }
+    return m.range.x + value * m.range.y;
+}
+
+vec3 mapColor(const Surface s, const int map, const vec3 color) {
+    return hasMap(s.materialIdx, map) ? color * sampleMap(s, map).rgb : color;
+}
+
+vec3 perturbNormal(Surface s, const mat3 tbn) {
+    vec3 normal = s.normal;
+    if (tbn[0] == vec3(0)) return normal;
+    if (hasMap(s.materialIdx, MAP_NORMAL)) {
+        vec3 n = sampleMap(s, MAP_NORMAL).xyz * 2 - 1;
+        n.xy *= materials[s.materialIdx].maps[MAP_NORMAL].bumpMultiplier;
+        normal = normalize(tbn * n);
+    }
+    // the geometry is not tessellated, so displacement maps are applied like bump maps
+    int heightMap = hasMap(s.materialIdx, MAP_BUMP) ? MAP_BUMP : MAP_DISPLACEMENT;
+    if (hasMap(s.materialIdx, heightMap)) {
+        TextureMap m = materials[s.materialIdx].maps[heightMap];
+        // height differences to the neighbouring texels
+        vec2 texel = 1.0 / (mapSize(m) * m.transform.xy);
+        float h = sampleScalar(s, heightMap);
+        s.texCoord.x += texel.x;
+        float hu = sampleScalar(s, heightMap);
+        s.texCoord += vec2(-texel.x, texel.y);
+        float hv = sampleScalar(s, heightMap);
+        vec2 gradient = vec2(hu - h, hv - h) * m.bumpMultiplier;
+        normal = normalize(normal - gradient.x * tbn[0] - gradient.y * tbn[1]);
+    }
+    return normal;
 }
 
 vec3 skybox(const vec3 dir) {
@@ -117,16 +192,104 @@ Intersection toIntersection(const vec4 data) {
     return Intersection(data.x, data.y, data.z, floatBitsToUint(data.w));
 }
 
-vec3 getColor(const vec3 dir, const Intersection i) {
+float fresnel(const vec3 viewDir, const vec3 normal, const float opticalDensity) {
+    float r0 = pow((opticalDensity - 1) / (opticalDensity + 1), 2.0);
+    return r0 + (1 - r0) * pow(1 - clamp(dot(-viewDir, normal), 0, 1), 5.0);
+}
+
+// shades a surface according to the illumination model of its material:
+// 0: color, 1: + ambient and diffuse, 2: + specular highlight, 3: + ray traced reflection,
+// 4: + glass, 5: + fresnel reflection, 6: + refraction, 7: + refraction and fresnel reflection,
+// 8: + environment reflection, 9: + glass and environment reflection, 10: like 2
+// refraction rays are not traced, transparent surfaces let the environment through instead
+vec3 shade(const Surface s, const vec3 viewDir, const float light, const float ambientLight, const vec3 reflection) {
+    Material material = materials[s.materialIdx];
+    int illum = illumination(s.materialIdx);
+
+    vec3 diffuseColor = whiteDiffuse ? vec3(1) : mapColor(s, MAP_DIFFUSE, material.diffuse.rgb);
+    vec3 emissiveColor = mapColor(s, MAP_EMISSIVE, material.emissive.rgb);
+    if (illum == 0) return diffuseColor + emissiveColor;
+
+    vec3 ambientColor = mapColor(s, MAP_AMBIENT, material.ambient.rgb);
+    if (hasMap(s.materialIdx, MAP_OCCLUSION)) ambientColor *= sampleScalar(s, MAP_OCCLUSION);
+
+    vec3 dirToLight = normalize(lightPos - s.position);
+    vec3 color = ambientColor * ambientLight * ambientStrength
+        + diffuseColor * clamp(dot(s.normal, dirToLight), 0, 1) * light * lightColor
+        + emissiveColor;
+
+    if (illum >= 2) {
+        vec3 specularColor = mapColor(s, MAP_SPECULAR, material.specular.rgb);
+        float specularExp = material.specular.a;
+        if (hasMap(s.materialIdx, MAP_SPECULAR_EXP)) specularExp *= sampleScalar(s, MAP_SPECULAR_EXP);
+        // metallic-roughness maps scale the factors by their blue and green channel, the specular
+        // color and exponent are derived from them like the loader does for the factors alone
+        if (hasMap(s.materialIdx, MAP_METALLIC_ROUGHNESS)) {
+            vec4 texel = sampleMap(s, MAP_METALLIC_ROUGHNESS);
+            float metallic = material.metallic * texel.b;
+            float roughness = max(material.roughness * texel.g, 0.01);
+            specularColor = mix(vec3(0.04), mapColor(s, MAP_DIFFUSE, material.diffuse.rgb), metallic);
+            specularExp = clamp(2 / pow(roughness, 4) - 2, 1, 1000);
+        }
+        vec3 halfway = normalize(dirToLight - viewDir);
+        color += specularColor * pow(clamp(dot(s.normal, halfway), 0, 1), max(specularExp, 1.0)) * light * lightColor;
+
+        if (illum >= 3 && illum <= 9) {
+            float reflectance = illum == 5 || illum == 7 ? fresnel(viewDir, s.normal, material.emissive.a) : 1;
+            color += specularColor * reflection * reflectance * reflectionStrength;
+        }
+    }
+
+    if (illum == 4 || illum == 6 || illum == 7 || illum == 9) {
+        vec3 transmitted = material.transmission.rgb * skybox(viewDir);
+        color = mix(transmitted, color, material.diffuse.a);
+    }
+    return color;
+}
+
+// tex coord footprint of a ray cone with the given width at a hit, the ratio of tex coord to world
+// space area of the triangle converts the width into tex coord units
+float rayConeFootprint(const uint idx, const float coneWidth, const vec3 dir, const vec3 normal) {
+#ifndef HAS_TEX_COORDS
+    return 0;
+#endif
+    Triangle triangle = triangles[idx];
+    vec3 p0 = fetchPosition(triangle.p0);
+    float worldArea = length(cross(fetchPosition(triangle.p1) - p0, fetchPosition(triangle.p2) - p0));
+    vec2 t0 = fetchTexCoord(triangle.p0);
+    vec2 d1 = fetchTexCoord(triangle.p1) - t0, d2 = fetchTexCoord(triangle.p2) - t0;
+    float texArea = abs(d1.x * d2.y - d2.x * d1.y);
+    if (worldArea <= 0) return 0;
+    return coneWidth / max(abs(dot(dir, normal)), 1e-3) * sqrt(texArea / worldArea);
+}
+
+// shades the hit of a traced ray, lighting is not traced any further from there
+vec3 getColor(const vec3 dir, const Intersection i, const float coneWidth) {
     if (i.t == MISS) return skybox(dir);
-    // todo: big TODO!!!
-    return vec3(1, 0, 1);
+
+    vec2 uv = vec2(i.u, i.v);
+    Surface s = Surface(
+        vec3(0), triangleNormal(i.tringleIdx, uv), triangleTexCoord(i.tringleIdx, uv),
+        vec2(0), vec2(0), triangles[i.tringleIdx].matIdx
+    );
+    {
+        Triangle triangle = triangles[i.tringleIdx];
+        float w = 1 - uv.x - uv.y;
+        s.position = fetchPosition(triangle.p1) * uv.x + fetchPosition(triangle.p2) * uv.y + fetchPosition(triangle.p0) * w;
+    }
+    float footprint = rayConeFootprint(i.tringleIdx, coneWidth, dir, s.normal);
+    s.dx = vec2(footprint, 0);
+    s.dy = vec2(0, footprint);
+    s.normal = perturbNormal(s, triangleTangentFrame(i.tringleIdx, s.normal));
+    return shade(s, dir, 1, 1, skybox(reflect(dir, s.normal)));
 }
 
 void main() {
     // fetch parameters
     vec3 position = texture(position, fragPos).xyz;
     vec2 texCoord = texture(texCoord, fragPos).xy;
+    // tex coord gradients are taken before any non-uniform control flow
+    vec2 dx = dFdx(texCoord), dy = dFdy(texCoord);
     vec3 normal;
     int materialIdx;
     {
@@ -142,24 +305,27 @@ void main() {
 
     vec3 viewDir = texture(viewDir, fragPos).xyz;
 
-    vec3 shadowDir = texture(shadowDir, fragPos).xyz;
     vec3 reflectDir = texture(reflectDir, fragPos).xyz;
-    vec3 ambientDir = texture(ambientDir, fragPos).xyz;
 
     Intersection shadowHit = toIntersection(texture(shadowHits, fragPos));
     Intersection reflectHit = toIntersection(texture(reflectHits, fragPos));
     Intersection ambientHit = toIntersection(texture(ambientHits, fragPos));
 
     // calculate necessary values
-    vec3 vecToLight = lightPos - position;
-    vec3 dirToLight = normalize(vecToLight);
-    float distToLight = length(vecToLight);
-    bool shadow = shadowHit.t < distToLight;
-
-    float diffuse = clamp(shadow ? 0.0 : dot(normal, dirToLight), 0.0, 1.0) * 0.8;
-    float specular = clamp(shadow ? 0.0 : pow(dot(reflectDir, dirToLight), 30.0), 0.0, 1.0) * 0.5;
-    float ambient = ambientHit.t == MISS ? 0.2 : 0.0;
-
-    //color = vec4((reflectDir * 0.5 + ambientDir * 0.5) * (shadow ? 0.2 : 1), 1);
-    color = vec4((diffuse + specular + ambient).xxx, 1);
-}
\ No newline at end of file
+    float distToLight = length(lightPos - position);
+    float light = shadowHit.t < distToLight ? 0.0 : 1.0;
+    float ambientLight = ambientHit.t == MISS ? 1.0 : 0.0;
+
+    // the normal in the g-buffer already has normal and bump maps applied
+    Surface s = Surface(position, normal, texCoord, dx, dy, uint(materialIdx));
+    int illum = illumination(uint(materialIdx));
+    vec3 reflection = vec3(0);
+    if (illum >= 3 && illum <= 7) {
+        // the cone keeps spreading at the pixel angle, the curvature at the reflection is ignored
+        float coneWidth = pixelSpreadAngle * (length(position - cameraPos) + reflectHit.t);
+        reflection = getColor(reflectDir, reflectHit, coneWidth);
+    }
+    else if (illum == 8 || illum == 9) reflection = skybox(reflect(viewDir, normal));
+
+    color = vec4(shade(s, viewDir, light, ambientLight, reflection), 1);
+}
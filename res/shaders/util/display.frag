@@ -1,10 +1,88 @@
 #version 460 core
 
+#include "include/ray_tracing.glsl"
+#include "include/heatmap.glsl"
+
+// how the displayed texture is mapped to colors, must match DebugView::remap
+#define REMAP_COLOR 0
+#define REMAP_POSITION 1
+#define REMAP_DIRECTION 2
+#define REMAP_TEX_COORD 3
+#define REMAP_MATERIAL_ID 4
+#define REMAP_DISTANCE 5
+#define REMAP_SHADOW_MASK 6
+#define REMAP_TRIANGLE_ID 7
+#define REMAP_HEATMAP 8
+
+#define NO_MATERIAL 1e30
+
 in vec2 fragPos;
 out vec4 fragCol;
 
 layout (location = 0) uniform sampler2D display;
+layout (location = 1) uniform int remap;
+layout (location = 2) uniform vec3 sceneMin;
+layout (location = 3) uniform vec3 sceneExtent;
+// traversal cost shown as the hottest color
+layout (location = 4) uniform float heatmapMax;
+
+// shading happens in linear space, srgb textures are decoded when sampled
+vec3 linearToSrgb(const vec3 color) {
+    vec3 c = clamp(color, 0, 1);
+    return mix(c * 12.92, 1.055 * pow(c, vec3(1 / 2.4)) - 0.055, step(0.0031308, c));
+}
+
+// distinct colors for neighbouring ids
+vec3 falseColor(uint id) {
+    id = (id ^ 61u) ^ (id >> 16u);
+    id *= 9u;
+    id ^= id >> 4u;
+    id *= 0x27d4eb2du;
+    id ^= id >> 15u;
+    return vec3(id & 255u, (id >> 8u) & 255u, (id >> 16u) & 255u) / 255.0;
+}
+
+// debug views are already in display space and not encoded as srgb
+vec3 remapTexel(const vec4 texel) {
+    switch (remap) {
+        case REMAP_POSITION:
+            return (texel.xyz - sceneMin) / max(sceneExtent, vec3(EPSILON));
+        case REMAP_DIRECTION:
+            return texel.xyz == NO_RAY ? vec3(0) : normalize(texel.xyz) * 0.5 + 0.5;
+        case REMAP_TEX_COORD:
+            return vec3(fract(texel.xy), 0);
+        case REMAP_MATERIAL_ID:
+            return texel.w == NO_MATERIAL ? vec3(0) : falseColor(floatBitsToUint(texel.w));
+        case REMAP_DISTANCE:
+            return texel.x == MISS ? vec3(0, 0, 0.2) : vec3(1 - texel.x / (texel.x + length(sceneExtent) * 0.25));
+        case REMAP_SHADOW_MASK:
+            return vec3(texel.x == MISS ? 1 : 0);
+        case REMAP_TRIANGLE_ID:
+            return texel.x == MISS ? vec3(0) : falseColor(floatBitsToUint(texel.w));
+        case REMAP_HEATMAP:
+            return heatmap((texel.x + texel.y) / heatmapMax);
+        default:
+            return linearToSrgb(texel.rgb);
+    }
+}
+
+// a texture larger than the window is box filtered, averaging the texels covered by the pixel
+vec4 boxFilter() {
+    ivec2 size = textureSize(display, 0);
+    vec2 pos = fragPos * vec2(size);
+    vec2 radius = fwidth(pos) * 0.5;
+    if (all(lessThanEqual(radius, vec2(0.5)))) return texture(display, fragPos);
+    ivec2 first = clamp(ivec2(floor(pos - radius + 0.5)), ivec2(0), size - 1);
+    ivec2 last = clamp(ivec2(floor(pos + radius - 0.5)), first, size - 1);
+    vec4 sum = vec4(0);
+    for (int y = first.y; y <= last.y; y++) {
+        for (int x = first.x; x <= last.x; x++) sum += texelFetch(display, ivec2(x, y), 0);
+    }
+    return sum / float((last.x - first.x + 1) * (last.y - first.y + 1));
+}
 
 void main() {
-    fragCol = texture(display, fragPos);
-}
\ No newline at end of file
+    // debug views hold ids and distances that must not be averaged
+    vec4 texel = remap == REMAP_COLOR ? boxFilter() : texture(display, fragPos);
+    fragCol = vec4(remapTexel(texel), remap == REMAP_COLOR ? texel.a : 1);
+}
@@ -0,0 +1,12 @@
+#version 460 core
+
+in vec2 vertTexCoord;
+in vec4 vertColor;
+out vec4 fragCol;
+
+layout (location = 1) uniform sampler2D tex;
+
+// colors and textures are premultiplied srgb, they are blended without conversion
+void main() {
+    fragCol = vertColor * texture(tex, vertTexCoord);
+}
@@ -0,0 +1,16 @@
+#version 460 core
+
+layout (location = 0) in vec2 position; // in points, origin in the upper left corner
+layout (location = 1) in vec2 texCoord;
+layout (location = 2) in vec4 color;
+
+out vec2 vertTexCoord;
+out vec4 vertColor;
+
+layout (location = 0) uniform vec2 screenSize;
+
+void main() {
+    vertTexCoord = texCoord;
+    vertColor = color;
+    gl_Position = vec4(position / screenSize * vec2(2, -2) + vec2(-1, 1), 0, 1);
+}
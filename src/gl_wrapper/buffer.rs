@@ -12,7 +12,7 @@ fn buffer_data<T>(id: u32, target: u32, data: &[T], usage: u32) {
         gl::BufferData(
             target,
             (data.len() * std::mem::size_of::<T>()) as isize,
-            &data[0] as *const T as *const c_void,
+            data.as_ptr() as *const c_void,
             usage,
         )
     }
@@ -43,6 +43,12 @@ impl IndexBuffer {
         self.size = data.len() as i32;
     }
 
+    // for data that is replaced every frame
+    pub fn buffer_stream_data<T>(&mut self, data: &[T]) {
+        buffer_data(self.ibo, gl::ELEMENT_ARRAY_BUFFER, data, gl::STREAM_DRAW);
+        self.size = data.len() as i32;
+    }
+
     pub fn size(&self) -> i32 {
         self.size
     }
@@ -73,6 +79,10 @@ impl VertexBuffer {
     pub fn buffer_data<T>(&mut self, data: &[T]) {
         buffer_data(self.vbo, gl::ARRAY_BUFFER, data, gl::STATIC_DRAW);
     }
+
+    pub fn buffer_stream_data<T>(&mut self, data: &[T]) {
+        buffer_data(self.vbo, gl::ARRAY_BUFFER, data, gl::STREAM_DRAW);
+    }
 }
 
 impl Drop for VertexBuffer {
@@ -34,6 +34,10 @@ impl Framebuffer {
     pub fn unbind(&self) { Self::bind_default() }
     pub fn bind_default() { unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, 0) } }
 
+    pub fn set_viewport(width: u32, height: u32) {
+        unsafe { gl::Viewport(0, 0, width as i32, height as i32) }
+    }
+
     pub fn set_clear_color(r: f32, g: f32, b: f32, a: f32) {
         unsafe { gl::ClearColor(r, g, b, a) }
     }
@@ -43,8 +47,42 @@ impl Framebuffer {
     pub fn clear_color_depth() {
         unsafe { gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT) }
     }
+    // clears a single draw buffer of the bound framebuffer, so that every attachment can have its own value
+    pub fn clear_color_attachment(draw_buffer: u32, color: [f32; 4]) {
+        unsafe { gl::ClearBufferfv(gl::COLOR, draw_buffer as i32, color.as_ptr()) }
+    }
+    pub fn clear_depth_attachment(depth: f32) {
+        unsafe { gl::ClearBufferfv(gl::DEPTH, 0, &depth) }
+    }
     pub fn enable_depth_test() { unsafe { gl::Enable(gl::DEPTH_TEST) } }
     pub fn disable_depth_test() { unsafe { gl::Disable(gl::DEPTH_TEST) } }
+    // blending for colors with premultiplied alpha
+    pub fn enable_blending() {
+        unsafe {
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+        }
+    }
+    pub fn disable_blending() { unsafe { gl::Disable(gl::BLEND) } }
+    // the rectangle is in window pixels with the origin in the lower left corner
+    pub fn set_scissor(x: i32, y: i32, width: i32, height: i32) {
+        unsafe {
+            gl::Enable(gl::SCISSOR_TEST);
+            gl::Scissor(x, y, width, height);
+        }
+    }
+    pub fn disable_scissor() { unsafe { gl::Disable(gl::SCISSOR_TEST) } }
+
+    // the back buffer of the window as rgba floats, rows from the bottom up
+    pub fn read_default_pixels(width: u32, height: u32) -> Vec<f32> {
+        let mut pixels = vec![0.0f32; (width * height * 4) as usize];
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+            gl::ReadBuffer(gl::BACK);
+            gl::ReadPixels(0, 0, width as i32, height as i32, gl::RGBA, gl::FLOAT, pixels.as_mut_ptr() as *mut _);
+        }
+        pixels
+    }
 
     pub fn attach_texture(&mut self, texture: &Texture, attachment: TextureAttachment, attach: bool) {
         self.bind();
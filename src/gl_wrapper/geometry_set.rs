@@ -42,6 +42,19 @@ impl GeometrySet {
             )
         }
     }
+
+    // draws a part of the index buffer, for buffers that are refilled after the set was built
+    pub fn draw_range(&self, first_index: u32, count: u32) {
+        self.bind();
+        unsafe {
+            gl::DrawElements(
+                self.primitives,
+                count as i32,
+                gl::UNSIGNED_INT,
+                (first_index as usize * std::mem::size_of::<u32>()) as *const _,
+            )
+        }
+    }
 }
 
 impl Drop for GeometrySet {
@@ -51,6 +64,7 @@ impl Drop for GeometrySet {
 }
 
 struct Attribute {
+    location: u32,
     dimension: i32,
     r#type: AttributeType,
 }
@@ -68,11 +82,12 @@ struct Buffer<'a> {
 
 pub struct GeometrySetBuilder<'a> {
     buffers: Vec<Buffer<'a>>,
+    next_location: u32,
 }
 
 impl<'a> GeometrySetBuilder<'a> {
     pub fn new() -> Self {
-        Self { buffers: vec![] }
+        Self { buffers: vec![], next_location: 0 }
     }
 
     pub fn add_buffer(mut self, buffer: &'a VertexBuffer) -> Self {
@@ -83,19 +98,25 @@ impl<'a> GeometrySetBuilder<'a> {
         self
     }
 
+    // attributes get consecutive locations, unless one is given explicitly
     pub fn add_attribute(mut self, dimension: i32, r#type: AttributeType) -> Self {
         if let Some(buffer) = self.buffers.last_mut() {
-            buffer.attributes.push(Attribute { dimension, r#type })
+            buffer.attributes.push(Attribute { location: self.next_location, dimension, r#type });
+            self.next_location += 1;
         }
         self
     }
 
+    pub fn add_attribute_at(mut self, location: u32, dimension: i32, r#type: AttributeType) -> Self {
+        self.next_location = location;
+        self.add_attribute(dimension, r#type)
+    }
+
     pub fn build(self, indices: &IndexBuffer, primitives: Primitive) -> GeometrySet {
         let gs = GeometrySet::new(indices.size(), primitives.to_gl_internal());
         gs.bind();
         indices.bind();
 
-        let mut idx = 0;
         self.buffers.iter().for_each(|buffer| {
             if let Some(stride) = buffer
                 .attributes
@@ -108,17 +129,16 @@ impl<'a> GeometrySetBuilder<'a> {
                 buffer.attributes.iter().for_each(|attrib| {
                     unsafe {
                         gl::VertexAttribPointer(
-                            idx,
+                            attrib.location,
                             attrib.dimension,
                             attrib.r#type.to_gl_internal(),
                             gl::FALSE,
                             stride,
                             offset as *const _,
                         );
-                        gl::EnableVertexAttribArray(idx); // fuck this line
+                        gl::EnableVertexAttribArray(attrib.location); // fuck this line
                     }
                     offset += attrib.size();
-                    idx += 1;
                 });
             }
         });
@@ -148,6 +168,7 @@ impl<'a> GeometrySetBuilder<'a> {
         let mut pos_vbo = VertexBuffer::new();
         let mut tex_vbo = None;
         let mut nor_vbo = None;
+        let mut tan_vbo = None;
 
         let model_guard = model.lock().unwrap();
 
@@ -161,12 +182,17 @@ impl<'a> GeometrySetBuilder<'a> {
         if let Some(tex_coords) = model_guard.tex_coords() {
             tex_vbo = Some(VertexBuffer::new());
             tex_vbo.as_mut().unwrap().buffer_data(tex_coords);
-            gsb = gsb.add_buffer(tex_vbo.as_ref().unwrap()).add_attribute(2, AttributeType::Float);
+            gsb = gsb.add_buffer(tex_vbo.as_ref().unwrap()).add_attribute_at(1, 2, AttributeType::Float);
         }
         if let Some(normals) = model_guard.normals() {
             nor_vbo = Some(VertexBuffer::new());
             nor_vbo.as_mut().unwrap().buffer_data(normals);
-            gsb = gsb.add_buffer(nor_vbo.as_ref().unwrap()).add_attribute(3, AttributeType::Float);
+            gsb = gsb.add_buffer(nor_vbo.as_ref().unwrap()).add_attribute_at(2, 3, AttributeType::Float);
+        }
+        if let Some(tangents) = model_guard.tangents() {
+            tan_vbo = Some(VertexBuffer::new());
+            tan_vbo.as_mut().unwrap().buffer_data(tangents);
+            gsb = gsb.add_buffer(tan_vbo.as_ref().unwrap()).add_attribute_at(3, 4, AttributeType::Float);
         }
 
         drop(model_guard);
@@ -175,6 +201,7 @@ impl<'a> GeometrySetBuilder<'a> {
         let mut vbos = vec![pos_vbo];
         if let Some(tex_vbo) = tex_vbo { vbos.push(tex_vbo) }
         if let Some(nor_vbo) = nor_vbo { vbos.push(nor_vbo) }
+        if let Some(tan_vbo) = tan_vbo { vbos.push(tan_vbo) }
 
         (gs, ibo, vbos)
     }
@@ -5,3 +5,4 @@ pub mod shader;
 pub mod texture;
 pub mod types;
 pub mod renderbuffer;
+pub mod query;
@@ -0,0 +1,42 @@
+fn gen_query() -> u32 {
+    let mut id: u32 = 0;
+    unsafe { gl::GenQueries(1, &mut id) }
+    id
+}
+
+// measures the gpu time of the commands between begin and end, only one timer can be active at
+// a time. the result is ready a few frames later, reading it before it is available stalls
+pub struct TimerQuery {
+    query: u32,
+}
+
+impl TimerQuery {
+    pub fn new() -> Self {
+        Self { query: gen_query() }
+    }
+
+    pub fn begin(&self) { unsafe { gl::BeginQuery(gl::TIME_ELAPSED, self.query) } }
+    pub fn end(&self) { unsafe { gl::EndQuery(gl::TIME_ELAPSED) } }
+
+    pub fn available(&self) -> bool {
+        let mut available: i32 = 0;
+        unsafe { gl::GetQueryObjectiv(self.query, gl::QUERY_RESULT_AVAILABLE, &mut available) }
+        available != 0
+    }
+
+    pub fn elapsed_ns(&self) -> u64 {
+        let mut elapsed: u64 = 0;
+        unsafe { gl::GetQueryObjectui64v(self.query, gl::QUERY_RESULT, &mut elapsed) }
+        elapsed
+    }
+}
+
+impl Default for TimerQuery {
+    fn default() -> Self { Self::new() }
+}
+
+impl Drop for TimerQuery {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteQueries(1, &self.query) }
+    }
+}
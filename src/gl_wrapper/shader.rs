@@ -1,5 +1,6 @@
-use std::collections::HashMap;
-use crate::gl_wrapper::types::ShaderType;
+use std::collections::{HashMap, HashSet};
+use crate::gl_wrapper::buffer::ShaderStorageBuffer;
+use crate::gl_wrapper::types::{ShaderType, UniformType};
 use crate::util::error::ShaderError;
 use cgmath::{Matrix, Matrix4, Vector2, Vector3, Vector4};
 use gl::types::GLchar;
@@ -79,6 +80,40 @@ fn get_program_error(program: u32, r#type: u32) -> Option<String> {
     }
 }
 
+fn active_resource_count(program: u32, interface: u32) -> u32 {
+    let mut count = 0;
+    unsafe { gl::GetProgramInterfaceiv(program, interface, gl::ACTIVE_RESOURCES, &mut count) }
+    count as u32
+}
+
+fn resource_properties<const N: usize>(program: u32, interface: u32, idx: u32, properties: [u32; N]) -> [i32; N] {
+    let mut values = [0; N];
+    unsafe {
+        gl::GetProgramResourceiv(
+            program,
+            interface,
+            idx,
+            N as i32,
+            properties.as_ptr(),
+            N as i32,
+            std::ptr::null_mut(),
+            values.as_mut_ptr(),
+        )
+    }
+    values
+}
+
+// name_length includes the terminating nul, as reported by GL_NAME_LENGTH
+fn resource_name(program: u32, interface: u32, idx: u32, name_length: i32) -> String {
+    let mut name = vec![0u8; name_length.max(1) as usize];
+    let mut length = 0;
+    unsafe {
+        gl::GetProgramResourceName(program, interface, idx, name.len() as i32, &mut length, name.as_mut_ptr() as *mut GLchar)
+    }
+    name.truncate(length as usize);
+    String::from_utf8_lossy(&name).into_owned()
+}
+
 fn cstring_with_capacity(capacity: i32) -> CString {
     let mut buffer = Vec::with_capacity(capacity as usize + 1);
     buffer.extend([b' '].iter().cycle().take(capacity as usize));
@@ -112,17 +147,74 @@ impl Drop for Shader {
     }
 }
 
+struct Uniform {
+    location: i32,
+    r#type: UniformType,
+    array_size: i32,
+    set: bool,
+}
+
 pub struct ShaderProgram {
     id: u32,
-    uniform_locations: HashMap<String, i32>,
+    label: String,
+    // the active uniforms and binding points of the storage blocks, queried after linking
+    uniforms: HashMap<String, Uniform>,
+    storage_blocks: HashMap<String, u32>,
+    // every problem with a name is only reported once
+    warned: HashSet<String>,
+    checked_unset_uniforms: bool,
 }
 
 impl ShaderProgram {
     pub fn new() -> Self {
         Self {
             id: unsafe { gl::CreateProgram() },
-            uniform_locations: HashMap::new(),
+            label: String::new(),
+            uniforms: HashMap::new(),
+            storage_blocks: HashMap::new(),
+            warned: HashSet::new(),
+            checked_unset_uniforms: false,
+        }
+    }
+
+    fn introspect(&mut self) {
+        for idx in 0..active_resource_count(self.id, gl::UNIFORM) {
+            let [name_length, r#type, location, array_size, block_idx] = resource_properties(
+                self.id, gl::UNIFORM, idx, [gl::NAME_LENGTH, gl::TYPE, gl::LOCATION, gl::ARRAY_SIZE, gl::BLOCK_INDEX]
+            );
+            // members of uniform blocks are not set with glUniform*
+            if block_idx != -1 { continue }
+            let name = resource_name(self.id, gl::UNIFORM, idx, name_length);
+            // arrays are reported by their first element
+            let name = name.strip_suffix("[0]").map(str::to_owned).unwrap_or(name);
+            self.uniforms.insert(name, Uniform { location, r#type: UniformType::from_gl_internal(r#type as u32), array_size, set: false });
         }
+        for idx in 0..active_resource_count(self.id, gl::SHADER_STORAGE_BLOCK) {
+            let [name_length, binding] = resource_properties(
+                self.id, gl::SHADER_STORAGE_BLOCK, idx, [gl::NAME_LENGTH, gl::BUFFER_BINDING]
+            );
+            self.storage_blocks.insert(resource_name(self.id, gl::SHADER_STORAGE_BLOCK, idx, name_length), binding as u32);
+        }
+    }
+
+    fn warn(&mut self, name: &str, message: String) {
+        if self.warned.insert(name.to_owned()) {
+            println!("Shader program {}: {}", self.label, message);
+        }
+    }
+
+    // location and array size of the uniform, if it is active and has the given type
+    fn uniform(&mut self, name: &str, r#type: UniformType) -> Option<(i32, i32)> {
+        let message = match self.uniforms.get_mut(name) {
+            Some(uniform) if uniform.r#type == r#type => {
+                uniform.set = true;
+                return Some((uniform.location, uniform.array_size));
+            }
+            Some(uniform) => format!("uniform {} is a {:?}, but was set as a {:?}", name, uniform.r#type, r#type),
+            None => format!("uniform {} does not exist or is not used", name),
+        };
+        self.warn(name, message);
+        None
     }
 
     pub fn bind(&self) {
@@ -132,56 +224,93 @@ impl ShaderProgram {
         unsafe { gl::UseProgram(0) }
     }
 
-    pub fn set_uniform_texture(&mut self, loc: i32, texture: u32) {
-        unsafe { gl::Uniform1i(loc, texture as i32) }
+    pub fn set_uniform_texture(&mut self, name: &str, texture: u32) {
+        if let Some((loc, _)) = self.uniform(name, UniformType::Sampler) {
+            unsafe { gl::Uniform1i(loc, texture as i32) }
+        }
     }
 
-    pub fn set_uniform_texture_array(&mut self, loc: i32, v: Vec<u32>) {
-        let v = v.into_iter().map(|i| i as i32).collect::<Vec<i32>>();
-        unsafe { gl::Uniform1iv(loc, v.len() as i32, v.as_ptr()) }
+    // sets the first elements of a sampler array, extra slots are dropped with a warning
+    pub fn set_uniform_texture_array(&mut self, name: &str, v: Vec<u32>) {
+        if let Some((loc, array_size)) = self.uniform(name, UniformType::Sampler) {
+            if v.len() > array_size as usize {
+                self.warn(name, format!("uniform {} has {} elements, but {} were set", name, array_size, v.len()));
+            }
+            let v = v.into_iter().take(array_size as usize).map(|i| i as i32).collect::<Vec<i32>>();
+            unsafe { gl::Uniform1iv(loc, v.len() as i32, v.as_ptr()) }
+        }
     }
 
-    pub fn set_uniform_1f(&mut self, loc: i32, f: f32) {
-        unsafe { gl::Uniform1f(loc, f) }
+    pub fn set_uniform_1f(&mut self, name: &str, f: f32) {
+        if let Some((loc, _)) = self.uniform(name, UniformType::Float) {
+            unsafe { gl::Uniform1f(loc, f) }
+        }
     }
 
-    pub fn set_uniform_2f(&mut self, loc: i32, v: Vector2<f32>) {
-        unsafe { gl::Uniform2f(loc, v[0], v[1]) }
+    pub fn set_uniform_2f(&mut self, name: &str, v: Vector2<f32>) {
+        if let Some((loc, _)) = self.uniform(name, UniformType::Vec2) {
+            unsafe { gl::Uniform2f(loc, v[0], v[1]) }
+        }
+    }
+
+    pub fn set_uniform_3f(&mut self, name: &str, v: Vector3<f32>) {
+        if let Some((loc, _)) = self.uniform(name, UniformType::Vec3) {
+            unsafe { gl::Uniform3f(loc, v[0], v[1], v[2]) }
+        }
     }
 
-    pub fn set_uniform_3f(&mut self, loc: i32, v: Vector3<f32>) {
-        unsafe { gl::Uniform3f(loc, v[0], v[1], v[2]) }
+    pub fn set_uniform_4f(&mut self, name: &str, v: Vector4<f32>) {
+        if let Some((loc, _)) = self.uniform(name, UniformType::Vec4) {
+            unsafe { gl::Uniform4f(loc, v[0], v[1], v[2], v[3]) }
+        }
     }
 
-    pub fn set_uniform_4f(&mut self, loc: i32, v: Vector4<f32>) {
-        unsafe { gl::Uniform4f(loc, v[0], v[1], v[2], v[3]) }
+    pub fn set_uniform_mat_4f(&mut self, name: &str, m: Matrix4<f32>) {
+        if let Some((loc, _)) = self.uniform(name, UniformType::Mat4) {
+            unsafe { gl::UniformMatrix4fv(loc, 1, gl::FALSE, m.as_ptr()) }
+        }
     }
 
-    pub fn set_uniform_mat_4f(&mut self, loc: i32, m: Matrix4<f32>) {
-        unsafe { gl::UniformMatrix4fv(loc, 1, gl::FALSE, m.as_ptr()) }
+    pub fn set_uniform_1i(&mut self, name: &str, i: i32) {
+        if let Some((loc, _)) = self.uniform(name, UniformType::Int) {
+            unsafe { gl::Uniform1i(loc, i) }
+        }
     }
 
-    pub fn set_uniform_1i(&mut self, loc: i32, i: i32) {
-        unsafe { gl::Uniform1i(loc, i) }
+    pub fn set_uniform_2i(&mut self, name: &str, v: Vector2<i32>) {
+        if let Some((loc, _)) = self.uniform(name, UniformType::IVec2) {
+            unsafe { gl::Uniform2i(loc, v[0], v[1]) }
+        }
     }
 
-    pub fn set_uniform_2i(&mut self, loc: i32, v: Vector2<i32>) {
-        unsafe { gl::Uniform2i(loc, v[0], v[1]) }
+    pub fn set_uniform_1b(&mut self, name: &str, b: bool) {
+        if let Some((loc, _)) = self.uniform(name, UniformType::Bool) {
+            unsafe { gl::Uniform1i(loc, b as i32) }
+        }
     }
 
-    pub fn set_uniform_1b(&mut self, loc: i32, b: bool) {
-        unsafe { gl::Uniform1i(loc, b as i32) }
+    pub fn uniform_location(&self, name: &str) -> Option<i32> {
+        self.uniforms.get(name).map(|uniform| uniform.location)
     }
 
-    pub fn uniform_location(&mut self, name: &str) -> i32 {
-        if let Some(loc) = self.uniform_locations.get(name) { *loc }
-        else {
-            let name_cstring = CString::new(name).unwrap();
-            let loc = unsafe { gl::GetUniformLocation(self.id, name_cstring.as_ptr()) };
-            self.uniform_locations.insert(name.to_owned(), loc);
-            loc
+    pub fn bind_storage_block(&mut self, name: &str, buffer: &ShaderStorageBuffer) {
+        match self.storage_blocks.get(name) {
+            Some(binding) => buffer.bind_to_slot(*binding),
+            None => self.warn(name, format!("storage block {} does not exist or is not used", name)),
         }
     }
+
+    // warns once about the active uniforms that were never set, as soon as the program was used
+    pub fn warn_unset_uniforms(&mut self) {
+        if self.checked_unset_uniforms || !self.uniforms.values().any(|uniform| uniform.set) { return }
+        self.checked_unset_uniforms = true;
+        let mut unset: Vec<String> = self.uniforms.iter()
+            .filter(|(_, uniform)| !uniform.set)
+            .map(|(name, _)| name.clone())
+            .collect();
+        unset.sort();
+        unset.iter().for_each(|name| self.warn(name, format!("uniform {} is never set", name)));
+    }
 }
 
 impl Drop for ShaderProgram {
@@ -192,11 +321,18 @@ impl Drop for ShaderProgram {
 
 pub struct ShaderProgramBuilder {
     shaders: Vec<Arc<Shader>>,
+    label: String,
 }
 
 impl ShaderProgramBuilder {
     pub fn new() -> Self {
-        Self { shaders: vec![] }
+        Self { shaders: vec![], label: String::new() }
+    }
+
+    // names the program in warnings
+    pub fn set_label(mut self, label: &str) -> Self {
+        self.label = label.to_owned();
+        self
     }
 
     pub fn add_shader(mut self, shader: Arc<Shader>) -> Self {
@@ -205,11 +341,15 @@ impl ShaderProgramBuilder {
     }
 
     pub fn build(self) -> Result<ShaderProgram, ShaderError> {
-        let program = ShaderProgram::new();
+        let mut program = ShaderProgram::new();
+        program.label = self.label;
         self.shaders.iter()
             .for_each(|shader| unsafe { gl::AttachShader(program.id, shader.id) });
         match link_program(program.id) {
-            Ok(_) => Ok(program),
+            Ok(_) => {
+                program.introspect();
+                Ok(program)
+            }
             Err(err) => Err(ShaderError::LinkError(err)),
         }
     }
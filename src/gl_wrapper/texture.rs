@@ -1,6 +1,10 @@
 use std::os::raw::c_void;
-use image::{EncodableLayout, RgbImage};
-use crate::gl_wrapper::types::{TextureAttachment, TextureFilter, TextureFormat};
+use image::{DynamicImage, ImageBuffer, Pixel, Primitive};
+use crate::gl_wrapper::types::{ColorSpace, TextureAttachment, TextureFilter, TextureFormat, TextureWrap};
+
+// core since OpenGL 4.6, but missing from the generated bindings
+const TEXTURE_MAX_ANISOTROPY: u32 = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: u32 = 0x84FF;
 
 fn gen_texture() -> u32 {
     let mut id: u32 = 0;
@@ -9,8 +13,11 @@ fn gen_texture() -> u32 {
 }
 
 fn reformat(texture: u32, width: u32, height: u32, format: &TextureFormat, data: *const c_void) {
+    let (pixel_format, pixel_type) = format.to_gl_pixel_format();
     unsafe {
         gl::BindTexture(gl::TEXTURE_2D, texture);
+        // image rows are tightly packed, not aligned to 4 bytes
+        gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
         gl::TexImage2D(
             gl::TEXTURE_2D,
             0,
@@ -18,8 +25,8 @@ fn reformat(texture: u32, width: u32, height: u32, format: &TextureFormat, data:
             width as i32,
             height as i32,
             0,
-            gl::RGB,
-            gl::UNSIGNED_BYTE,
+            pixel_format,
+            pixel_type,
             data,
         );
     }
@@ -36,17 +43,44 @@ fn change_filter(texture: u32, filter: &TextureFilter) {
         gl::TexParameteri(
             gl::TEXTURE_2D,
             gl::TEXTURE_MAG_FILTER,
-            filter.to_gl_internal() as i32,
+            filter.to_gl_internal_mag() as i32,
         );
     }
 }
 
+fn change_wrap(texture: u32, wrap: &TextureWrap) {
+    unsafe {
+        gl::BindTexture(gl::TEXTURE_2D, texture);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, wrap.to_gl_internal() as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, wrap.to_gl_internal() as i32);
+    }
+}
+
+fn generate_mipmaps(texture: u32) {
+    unsafe {
+        gl::BindTexture(gl::TEXTURE_2D, texture);
+        gl::GenerateMipmap(gl::TEXTURE_2D);
+    }
+}
+
+// single and dual channel images are stored as red and red-green textures and read back as gray
+fn set_gray_swizzle(texture: u32, has_alpha: bool) {
+    let alpha = if has_alpha { gl::GREEN } else { gl::ONE };
+    let swizzle = [gl::RED, gl::RED, gl::RED, alpha].map(|c| c as i32);
+    unsafe {
+        gl::BindTexture(gl::TEXTURE_2D, texture);
+        gl::TexParameteriv(gl::TEXTURE_2D, gl::TEXTURE_SWIZZLE_RGBA, swizzle.as_ptr());
+    }
+}
+
 pub struct Texture {
     texture: u32,
     width: u32,
     height: u32,
     format: TextureFormat,
     filter: TextureFilter,
+    wrap: TextureWrap,
+    has_mipmaps: bool,
 }
 
 impl Texture {
@@ -60,22 +94,53 @@ impl Texture {
             height,
             format,
             filter,
+            wrap: TextureWrap::Repeat,
+            has_mipmaps: false,
         }
     }
-    pub fn from_data(format: TextureFormat, filter: TextureFilter, data: &RgbImage) -> Self {
+
+    // keeps the channels and bit depth of the image, 8 bit color can be tagged as srgb.
+    // float images are stored with half precision, other formats are converted to 8 bit rgba
+    pub fn from_image(image: &DynamicImage, color_space: ColorSpace, filter: TextureFilter) -> Self {
+        let srgb = color_space == ColorSpace::Srgb;
+        let converted;
+        let (format, image) = match image {
+            DynamicImage::ImageLuma8(_) => (TextureFormat::R8, image),
+            DynamicImage::ImageLumaA8(_) => (TextureFormat::RG8, image),
+            DynamicImage::ImageRgb8(_) => (if srgb { TextureFormat::SRGB8 } else { TextureFormat::RGB8 }, image),
+            DynamicImage::ImageRgba8(_) => (if srgb { TextureFormat::SRGB8Alpha8 } else { TextureFormat::RGBA8 }, image),
+            // there are no 16 bit srgb formats, so these are always linear
+            DynamicImage::ImageLuma16(_) => (TextureFormat::R16, image),
+            DynamicImage::ImageLumaA16(_) => (TextureFormat::RG16, image),
+            DynamicImage::ImageRgb16(_) => (TextureFormat::RGB16, image),
+            DynamicImage::ImageRgba16(_) => (TextureFormat::RGBA16, image),
+            DynamicImage::ImageRgb32F(_) => (TextureFormat::RGB16F, image),
+            DynamicImage::ImageRgba32F(_) => (TextureFormat::RGBA16F, image),
+            _ => {
+                converted = DynamicImage::ImageRgba8(image.to_rgba8());
+                (if srgb { TextureFormat::SRGB8Alpha8 } else { TextureFormat::RGBA8 }, &converted)
+            }
+        };
+
         let texture = gen_texture();
-        reformat(
-            texture, data.width(), data.height(), &format,
-            data.as_bytes().as_ptr() as *const _
-        );
-        change_filter(texture, &filter);
-        Self {
+        reformat(texture, image.width(), image.height(), &format, image.as_bytes().as_ptr() as *const _);
+        match format {
+            TextureFormat::R8 | TextureFormat::R16 => set_gray_swizzle(texture, false),
+            TextureFormat::RG8 | TextureFormat::RG16 => set_gray_swizzle(texture, true),
+            _ => {}
+        }
+        let mut texture = Self {
             texture,
-            width: data.width(),
-            height: data.height(),
+            width: image.width(),
+            height: image.height(),
             format,
-            filter,
-        }
+            filter: TextureFilter::Linear,
+            wrap: TextureWrap::Repeat,
+            has_mipmaps: false,
+        };
+        texture.change_filter(filter);
+        change_wrap(texture.texture, &texture.wrap);
+        texture
     }
 
     pub fn bind(&self) {
@@ -99,6 +164,8 @@ impl Texture {
             self.width = width;
             self.height = height;
             self.format = format;
+            self.has_mipmaps = false;
+            if self.filter.uses_mipmaps() { self.generate_mipmaps() }
         }
     }
 
@@ -106,11 +173,35 @@ impl Texture {
         self.reformat(width, height, self.format.clone());
     }
 
+    // mipmaps are generated when a mipmapped filter is set for the first time
     pub fn change_filter(&mut self, filter: TextureFilter) {
         if self.filter != filter {
             change_filter(self.texture, &filter);
             self.filter = filter;
         }
+        if filter.uses_mipmaps() && !self.has_mipmaps { self.generate_mipmaps() }
+    }
+
+    pub fn generate_mipmaps(&mut self) {
+        generate_mipmaps(self.texture);
+        self.has_mipmaps = true;
+    }
+
+    pub fn change_wrap(&mut self, wrap: TextureWrap) {
+        if self.wrap != wrap {
+            change_wrap(self.texture, &wrap);
+            self.wrap = wrap;
+        }
+    }
+
+    // the anisotropy is clamped to what the driver supports, 1 disables anisotropic filtering
+    pub fn set_anisotropy(&mut self, anisotropy: f32) {
+        let mut max_anisotropy = 1.0;
+        unsafe {
+            gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max_anisotropy);
+            gl::BindTexture(gl::TEXTURE_2D, self.texture);
+            gl::TexParameterf(gl::TEXTURE_2D, TEXTURE_MAX_ANISOTROPY, anisotropy.clamp(1.0, max_anisotropy.max(1.0)));
+        }
     }
 
     pub fn attach_to_framebuffer(&self, attachment: TextureAttachment) {
@@ -126,6 +217,53 @@ impl Texture {
         }
     }
 
+    // replaces a region of the base level, the data has to be in the pixel format of the texture
+    pub fn update<T>(&mut self, x: u32, y: u32, width: u32, height: u32, data: &[T]) {
+        let (pixel_format, pixel_type) = self.format.to_gl_pixel_format();
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.texture);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D, 0,
+                x as i32, y as i32,
+                width as i32, height as i32,
+                pixel_format, pixel_type,
+                data.as_ptr() as *const _,
+            );
+        }
+        if self.has_mipmaps { self.generate_mipmaps() }
+    }
+
+    // reads a single texel of the base level back to the cpu, missing channels are 0 and alpha is 1
+    pub fn read_pixel(&self, x: u32, y: u32) -> [f32; 4] {
+        let mut pixel = [0.0f32; 4];
+        unsafe {
+            gl::GetTextureSubImage(
+                self.texture, 0,
+                x as i32, y as i32, 0,
+                1, 1, 1,
+                gl::RGBA, gl::FLOAT,
+                std::mem::size_of_val(&pixel) as i32,
+                pixel.as_mut_ptr() as *mut _,
+            );
+        }
+        pixel
+    }
+
+    // the base level as rgba floats, rows from the bottom up
+    pub fn read_pixels(&self) -> Vec<f32> {
+        let mut pixels = vec![0.0f32; (self.width * self.height * 4) as usize];
+        unsafe {
+            gl::GetTextureImage(
+                self.texture, 0,
+                gl::RGBA, gl::FLOAT,
+                (pixels.len() * std::mem::size_of::<f32>()) as i32,
+                pixels.as_mut_ptr() as *mut _,
+            );
+        }
+        pixels
+    }
+
     pub fn width(&self) -> u32 { self.width }
     pub fn height(&self) -> u32 { self.height }
 }
@@ -135,3 +273,75 @@ impl Drop for Texture {
         unsafe { gl::DeleteTextures(1, &self.texture) }
     }
 }
+
+// equally sized rgba layers sampled through a single sampler2DArray, so that shaders can pick
+// a texture per fragment without indexing an array of samplers
+pub struct TextureArray {
+    texture: u32,
+    width: u32,
+    height: u32,
+    layers: u32,
+}
+
+impl TextureArray {
+    // all images must have the same size and components matching the pixel format of the rgba
+    // format, u8 for 8 bit, u16 for 16 bit and f32 for float formats. an empty slice creates a single
+    // white layer, so that the array can always be bound
+    pub fn from_images<P: Pixel>(images: &[&ImageBuffer<P, Vec<P::Subpixel>>], format: TextureFormat, filter: TextureFilter, anisotropy: f32) -> Self {
+        assert_eq!(P::CHANNEL_COUNT, 4, "texture array layers are rgba");
+        let white = ImageBuffer::from_pixel(1, 1, *P::from_slice(&[P::Subpixel::DEFAULT_MAX_VALUE; 4]));
+        let white = [&white];
+        let images = if images.is_empty() { &white[..] } else { images };
+        let (width, height) = images[0].dimensions();
+        assert!(images.iter().all(|image| image.dimensions() == (width, height)), "texture array layers differ in size");
+        let (pixel_format, pixel_type) = format.to_gl_pixel_format();
+
+        let texture = gen_texture();
+        let mut max_anisotropy = 1.0;
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, texture);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexImage3D(
+                gl::TEXTURE_2D_ARRAY, 0,
+                format.to_gl_internal() as i32,
+                width as i32, height as i32, images.len() as i32,
+                0, pixel_format, pixel_type, std::ptr::null(),
+            );
+            for (layer, image) in images.iter().enumerate() {
+                gl::TexSubImage3D(
+                    gl::TEXTURE_2D_ARRAY, 0,
+                    0, 0, layer as i32,
+                    width as i32, height as i32, 1,
+                    pixel_format, pixel_type,
+                    image.as_raw().as_ptr() as *const _,
+                );
+            }
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MIN_FILTER, filter.to_gl_internal() as i32);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAG_FILTER, filter.to_gl_internal_mag() as i32);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_S, TextureWrap::Repeat.to_gl_internal() as i32);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_T, TextureWrap::Repeat.to_gl_internal() as i32);
+            gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max_anisotropy);
+            gl::TexParameterf(gl::TEXTURE_2D_ARRAY, TEXTURE_MAX_ANISOTROPY, anisotropy.clamp(1.0, max_anisotropy.max(1.0)));
+            if filter.uses_mipmaps() { gl::GenerateMipmap(gl::TEXTURE_2D_ARRAY) }
+        }
+        Self { texture, width, height, layers: images.len() as u32 }
+    }
+
+    pub fn bind_to_slot(&self, slot: u32) -> u32 {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + slot);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.texture);
+        }
+        slot
+    }
+
+    pub fn width(&self) -> u32 { self.width }
+    pub fn height(&self) -> u32 { self.height }
+    pub fn layers(&self) -> u32 { self.layers }
+}
+
+impl Drop for TextureArray {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteTextures(1, &self.texture) }
+    }
+}
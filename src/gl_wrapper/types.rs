@@ -105,10 +105,56 @@ impl ShaderType {
     }
 }
 
-#[derive(PartialEq)]
+// glsl type of an active uniform, as reported by program introspection
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum UniformType {
+    Float,
+    Vec2,
+    Vec3,
+    Vec4,
+    Int,
+    IVec2,
+    IVec3,
+    IVec4,
+    UInt,
+    Bool,
+    Mat3,
+    Mat4,
+    Sampler,
+    Other(u32),
+}
+
+impl UniformType {
+    pub fn from_gl_internal(r#type: u32) -> Self {
+        match r#type {
+            gl::FLOAT => UniformType::Float,
+            gl::FLOAT_VEC2 => UniformType::Vec2,
+            gl::FLOAT_VEC3 => UniformType::Vec3,
+            gl::FLOAT_VEC4 => UniformType::Vec4,
+            gl::INT => UniformType::Int,
+            gl::INT_VEC2 => UniformType::IVec2,
+            gl::INT_VEC3 => UniformType::IVec3,
+            gl::INT_VEC4 => UniformType::IVec4,
+            gl::UNSIGNED_INT => UniformType::UInt,
+            gl::BOOL => UniformType::Bool,
+            gl::FLOAT_MAT3 => UniformType::Mat3,
+            gl::FLOAT_MAT4 => UniformType::Mat4,
+            gl::SAMPLER_1D | gl::SAMPLER_2D | gl::SAMPLER_3D | gl::SAMPLER_CUBE | gl::SAMPLER_2D_SHADOW
+            | gl::SAMPLER_1D_ARRAY | gl::SAMPLER_2D_ARRAY | gl::SAMPLER_CUBE_MAP_ARRAY | gl::SAMPLER_2D_MULTISAMPLE
+            | gl::SAMPLER_BUFFER | gl::INT_SAMPLER_2D | gl::UNSIGNED_INT_SAMPLER_2D => UniformType::Sampler,
+            other => UniformType::Other(other),
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
 pub enum TextureFilter {
     Nearest,
     Linear,
+    NearestMipmapNearest,
+    LinearMipmapNearest,
+    NearestMipmapLinear,
+    LinearMipmapLinear,
 }
 
 impl TextureFilter {
@@ -116,8 +162,50 @@ impl TextureFilter {
         match self {
             TextureFilter::Nearest => gl::NEAREST,
             TextureFilter::Linear => gl::LINEAR,
+            TextureFilter::NearestMipmapNearest => gl::NEAREST_MIPMAP_NEAREST,
+            TextureFilter::LinearMipmapNearest => gl::LINEAR_MIPMAP_NEAREST,
+            TextureFilter::NearestMipmapLinear => gl::NEAREST_MIPMAP_LINEAR,
+            TextureFilter::LinearMipmapLinear => gl::LINEAR_MIPMAP_LINEAR,
         }
     }
+
+    // magnification never uses mipmaps
+    pub fn to_gl_internal_mag(&self) -> u32 {
+        match self {
+            TextureFilter::Nearest | TextureFilter::NearestMipmapNearest | TextureFilter::NearestMipmapLinear => gl::NEAREST,
+            _ => gl::LINEAR,
+        }
+    }
+
+    pub fn uses_mipmaps(&self) -> bool {
+        !matches!(self, TextureFilter::Nearest | TextureFilter::Linear)
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum TextureWrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    ClampToBorder,
+}
+
+impl TextureWrap {
+    pub fn to_gl_internal(&self) -> u32 {
+        match self {
+            TextureWrap::Repeat => gl::REPEAT,
+            TextureWrap::MirroredRepeat => gl::MIRRORED_REPEAT,
+            TextureWrap::ClampToEdge => gl::CLAMP_TO_EDGE,
+            TextureWrap::ClampToBorder => gl::CLAMP_TO_BORDER,
+        }
+    }
+}
+
+// color textures are stored in srgb, data like normals, heights or masks is linear
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+pub enum ColorSpace {
+    Linear,
+    Srgb,
 }
 
 #[derive(PartialEq, Clone)]
@@ -168,6 +256,9 @@ pub enum TextureFormat {
     RGBA32I,
     RGBA32UI,
     RGBA32F,
+
+    SRGB8,
+    SRGB8Alpha8,
 }
 
 impl TextureFormat {
@@ -215,8 +306,37 @@ impl TextureFormat {
             TextureFormat::RGBA32I => gl::RGBA32I,
             TextureFormat::RGBA32UI => gl::RGBA32UI,
             TextureFormat::RGBA32F => gl::RGBA32F,
+            TextureFormat::SRGB8 => gl::SRGB8,
+            TextureFormat::SRGB8Alpha8 => gl::SRGB8_ALPHA8,
         }
     }
+
+    // the pixel format and type of data uploaded to a texture of this format
+    pub fn to_gl_pixel_format(&self) -> (u32, u32) {
+        use TextureFormat::*;
+        let format = match self {
+            Depth => return (gl::DEPTH_COMPONENT, gl::FLOAT),
+            DepthStencil => return (gl::DEPTH_STENCIL, gl::UNSIGNED_INT_24_8),
+            R8 | R16 | R16F | R32F => gl::RED,
+            R8I | R8UI | R16I | R16UI | R32I | R32UI => gl::RED_INTEGER,
+            RG8 | RG16 | RG16F | RG32F => gl::RG,
+            RG8I | RG8UI | RG16I | RG16UI | RG32I | RG32UI => gl::RG_INTEGER,
+            RGB8 | RGB16 | RGB16F | RGB32F | SRGB8 => gl::RGB,
+            RGB8I | RGB8UI | RGB16I | RGB16UI | RGB32I | RGB32UI => gl::RGB_INTEGER,
+            RGBA8 | RGBA16 | RGBA16F | RGBA32F | SRGB8Alpha8 => gl::RGBA,
+            RGBA8I | RGBA8UI | RGBA16I | RGBA16UI | RGBA32I | RGBA32UI => gl::RGBA_INTEGER,
+        };
+        let r#type = match self {
+            R8I | RG8I | RGB8I | RGBA8I => gl::BYTE,
+            R16 | RG16 | RGB16 | RGBA16 | R16UI | RG16UI | RGB16UI | RGBA16UI => gl::UNSIGNED_SHORT,
+            R16I | RG16I | RGB16I | RGBA16I => gl::SHORT,
+            R32I | RG32I | RGB32I | RGBA32I => gl::INT,
+            R32UI | RG32UI | RGB32UI | RGBA32UI => gl::UNSIGNED_INT,
+            R16F | RG16F | RGB16F | RGBA16F | R32F | RG32F | RGB32F | RGBA32F => gl::FLOAT,
+            _ => gl::UNSIGNED_BYTE,
+        };
+        (format, r#type)
+    }
 }
 
 pub enum TextureAttachment {
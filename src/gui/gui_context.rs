@@ -0,0 +1,156 @@
+use egui::{ClippedPrimitive, Event, Modifiers, MouseWheelUnit, PointerButton, Pos2, RawInput, Rect, TextureId, Vec2, ViewportId, ViewportInfo};
+use glfw::{Action, Key, MouseButton, WindowEvent};
+use crate::gl_wrapper::shader::ShaderProgram;
+use crate::gui::gui_renderer::GuiRenderer;
+use crate::window::window::Window;
+
+fn to_modifiers(modifiers: glfw::Modifiers) -> Modifiers {
+    Modifiers {
+        alt: modifiers.contains(glfw::Modifiers::Alt),
+        ctrl: modifiers.contains(glfw::Modifiers::Control),
+        shift: modifiers.contains(glfw::Modifiers::Shift),
+        mac_cmd: false,
+        command: modifiers.contains(glfw::Modifiers::Control),
+    }
+}
+
+// the keys used for navigating and editing text
+fn to_key(key: Key) -> Option<egui::Key> {
+    Some(match key {
+        Key::Left => egui::Key::ArrowLeft,
+        Key::Right => egui::Key::ArrowRight,
+        Key::Up => egui::Key::ArrowUp,
+        Key::Down => egui::Key::ArrowDown,
+        Key::Escape => egui::Key::Escape,
+        Key::Tab => egui::Key::Tab,
+        Key::Backspace => egui::Key::Backspace,
+        Key::Enter | Key::KpEnter => egui::Key::Enter,
+        Key::Space => egui::Key::Space,
+        Key::Insert => egui::Key::Insert,
+        Key::Delete => egui::Key::Delete,
+        Key::Home => egui::Key::Home,
+        Key::End => egui::Key::End,
+        Key::PageUp => egui::Key::PageUp,
+        Key::PageDown => egui::Key::PageDown,
+        Key::A => egui::Key::A,
+        Key::C => egui::Key::C,
+        Key::V => egui::Key::V,
+        Key::X => egui::Key::X,
+        Key::Y => egui::Key::Y,
+        Key::Z => egui::Key::Z,
+        _ => return None,
+    })
+}
+
+fn to_pointer_button(button: MouseButton) -> Option<PointerButton> {
+    match button {
+        MouseButton::Button1 => Some(PointerButton::Primary),
+        MouseButton::Button2 => Some(PointerButton::Secondary),
+        MouseButton::Button3 => Some(PointerButton::Middle),
+        _ => None,
+    }
+}
+
+// an egui context fed with the window events and drawn through the gl wrapper
+pub struct Gui {
+    context: egui::Context,
+    renderer: GuiRenderer,
+    primitives: Vec<ClippedPrimitive>,
+    pixels_per_point: f32,
+    // textures egui no longer needs, freed after the frame was painted
+    freed_textures: Vec<TextureId>,
+    cursor_pos: Pos2,
+    modifiers: Modifiers,
+}
+
+impl Default for Gui {
+    fn default() -> Self { Self::new() }
+}
+
+impl Gui {
+    pub fn new() -> Self {
+        Self {
+            context: egui::Context::default(),
+            renderer: GuiRenderer::new(),
+            primitives: vec![],
+            pixels_per_point: 1.0,
+            freed_textures: vec![],
+            cursor_pos: Pos2::ZERO,
+            modifiers: Modifiers::NONE,
+        }
+    }
+
+    // runs the ui for the events of the current frame, paint draws the result
+    pub fn run(&mut self, window: &Window, ui: impl FnMut(&egui::Context)) {
+        // egui lays out in points, a point is content scale framebuffer pixels large
+        let content_scale = window.content_scale();
+        self.pixels_per_point = content_scale * self.context.zoom_factor();
+        let viewport = ViewportInfo { native_pixels_per_point: Some(content_scale), ..Default::default() };
+        let screen_size = Vec2::new(window.width() as f32, window.height() as f32) / self.pixels_per_point;
+        let framebuffer_scale = window.framebuffer_scale();
+        let input = RawInput {
+            viewports: std::iter::once((ViewportId::ROOT, viewport)).collect(),
+            screen_rect: Some(Rect::from_min_size(Pos2::ZERO, screen_size)),
+            time: Some(window.time()),
+            predicted_dt: window.dt(),
+            modifiers: self.modifiers,
+            events: window.frame_events().iter().filter_map(|event| self.convert_event(event, framebuffer_scale)).collect(),
+            focused: true,
+            ..Default::default()
+        };
+        let output = self.context.run(input, ui);
+        self.renderer.set_textures(&output.textures_delta.set);
+        self.freed_textures = output.textures_delta.free;
+        self.pixels_per_point = output.pixels_per_point;
+        self.primitives = self.context.tessellate(output.shapes, output.pixels_per_point);
+    }
+
+    // the program has to be bound
+    pub fn paint(&mut self, program: &mut ShaderProgram, window_size: (u32, u32)) {
+        self.renderer.paint(program, &self.primitives, self.pixels_per_point, window_size);
+        self.renderer.free_textures(&self.freed_textures);
+        self.freed_textures.clear();
+    }
+
+    // whether the last frame used the mouse, so that it should not control the camera
+    pub fn wants_pointer(&self) -> bool {
+        self.context.is_pointer_over_area() || self.context.wants_pointer_input()
+    }
+
+    pub fn wants_keyboard(&self) -> bool {
+        self.context.wants_keyboard_input()
+    }
+
+    // cursor positions are in screen coordinates, which framebuffer_scale converts to pixels
+    fn convert_event(&mut self, event: &WindowEvent, framebuffer_scale: (f32, f32)) -> Option<Event> {
+        match *event {
+            WindowEvent::CursorPos(x, y) => {
+                self.cursor_pos = Pos2::new(x as f32 * framebuffer_scale.0, y as f32 * framebuffer_scale.1) / self.pixels_per_point;
+                Some(Event::PointerMoved(self.cursor_pos))
+            }
+            WindowEvent::MouseButton(button, action, modifiers) => Some(Event::PointerButton {
+                pos: self.cursor_pos,
+                button: to_pointer_button(button)?,
+                pressed: action != Action::Release,
+                modifiers: to_modifiers(modifiers),
+            }),
+            WindowEvent::Scroll(x, y) => Some(Event::MouseWheel {
+                unit: MouseWheelUnit::Line,
+                delta: Vec2::new(x as f32, y as f32),
+                modifiers: self.modifiers,
+            }),
+            WindowEvent::Char(c) => Some(Event::Text(c.to_string())),
+            WindowEvent::Key(key, _, action, modifiers) => {
+                self.modifiers = to_modifiers(modifiers);
+                Some(Event::Key {
+                    key: to_key(key)?,
+                    physical_key: None,
+                    pressed: action != Action::Release,
+                    repeat: false,
+                    modifiers: self.modifiers,
+                })
+            }
+            _ => None,
+        }
+    }
+}
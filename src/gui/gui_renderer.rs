@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use cgmath::Vector2;
+use egui::epaint::{ImageDelta, Primitive};
+use egui::{ClippedPrimitive, ImageData, TextureId};
+use crate::gl_wrapper::buffer::{IndexBuffer, VertexBuffer};
+use crate::gl_wrapper::framebuffer::Framebuffer;
+use crate::gl_wrapper::geometry_set::{GeometrySet, GeometrySetBuilder};
+use crate::gl_wrapper::shader::ShaderProgram;
+use crate::gl_wrapper::texture::Texture;
+use crate::gl_wrapper::types::{AttributeType, Primitive as GlPrimitive, TextureFilter, TextureFormat, TextureWrap};
+
+// position and tex coord in points, color as premultiplied srgb
+type GuiVertex = [f32; 8];
+
+fn to_texture_filter(filter: egui::TextureFilter) -> TextureFilter {
+    match filter {
+        egui::TextureFilter::Nearest => TextureFilter::Nearest,
+        egui::TextureFilter::Linear => TextureFilter::Linear,
+    }
+}
+
+// draws egui meshes with the textures egui manages. egui colors are blended as they are, in srgb,
+// as the window framebuffer is not srgb encoded
+pub struct GuiRenderer {
+    textures: HashMap<TextureId, Texture>,
+    geometry: GeometrySet,
+    ibo: IndexBuffer,
+    vbo: VertexBuffer,
+}
+
+impl Default for GuiRenderer {
+    fn default() -> Self { Self::new() }
+}
+
+impl GuiRenderer {
+    pub fn new() -> Self {
+        let ibo = IndexBuffer::new();
+        let vbo = VertexBuffer::new();
+        // the buffers are refilled every frame, the vertex array keeps referring to them
+        let geometry = GeometrySetBuilder::new()
+            .add_buffer(&vbo)
+            .add_attribute(2, AttributeType::Float)
+            .add_attribute(2, AttributeType::Float)
+            .add_attribute(4, AttributeType::Float)
+            .build(&ibo, GlPrimitive::Triangles);
+        Self { textures: HashMap::new(), geometry, ibo, vbo }
+    }
+
+    // creates new textures and updates patches of existing ones, like the font atlas
+    pub fn set_textures(&mut self, textures: &[(TextureId, ImageDelta)]) {
+        for (id, delta) in textures {
+            let ImageData::Color(image) = &delta.image;
+            let [width, height] = image.size.map(|size| size as u32);
+            match delta.pos {
+                Some([x, y]) => match self.textures.get_mut(id) {
+                    Some(texture) => texture.update(x as u32, y as u32, width, height, &image.pixels),
+                    None => println!("GUI texture {:?} is updated before it was created", id),
+                },
+                None => {
+                    let mut texture = Texture::new(width, height, TextureFormat::RGBA8, to_texture_filter(delta.options.minification));
+                    texture.change_wrap(TextureWrap::ClampToEdge);
+                    texture.update(0, 0, width, height, &image.pixels);
+                    self.textures.insert(*id, texture);
+                }
+            }
+        }
+    }
+
+    pub fn free_textures(&mut self, textures: &[TextureId]) {
+        textures.iter().for_each(|id| { self.textures.remove(id); });
+    }
+
+    // the program has to be bound, the window size is in pixels
+    pub fn paint(&mut self, program: &mut ShaderProgram, primitives: &[ClippedPrimitive], pixels_per_point: f32, window_size: (u32, u32)) {
+        let meshes: Vec<_> = primitives.iter().filter_map(|primitive| match &primitive.primitive {
+            Primitive::Mesh(mesh) => Some((primitive.clip_rect, mesh)),
+            Primitive::Callback(_) => None,
+        }).collect();
+        if meshes.is_empty() { return }
+
+        let mut vertices: Vec<GuiVertex> = vec![];
+        let mut indices: Vec<u32> = vec![];
+        let mut ranges = Vec::with_capacity(meshes.len());
+        for (_, mesh) in &meshes {
+            let first_vertex = vertices.len() as u32;
+            ranges.push((indices.len() as u32, mesh.indices.len() as u32));
+            vertices.extend(mesh.vertices.iter().map(|v| {
+                let [r, g, b, a] = v.color.to_array().map(|c| c as f32 / 255.0);
+                [v.pos.x, v.pos.y, v.uv.x, v.uv.y, r, g, b, a]
+            }));
+            indices.extend(mesh.indices.iter().map(|i| first_vertex + i));
+        }
+        self.vbo.buffer_stream_data(&vertices);
+        self.ibo.buffer_stream_data(&indices);
+
+        let (width, height) = window_size;
+        program.set_uniform_2f("screenSize", Vector2::new(width as f32, height as f32) / pixels_per_point);
+        Framebuffer::enable_blending();
+        for ((clip_rect, mesh), (first_index, count)) in meshes.iter().zip(ranges) {
+            let Some(texture) = self.textures.get(&mesh.texture_id) else { continue };
+            program.set_uniform_texture("tex", texture.bind_to_slot(0));
+
+            // the clip rect is in points with the origin in the upper left corner
+            let min_x = (clip_rect.min.x * pixels_per_point).round().clamp(0.0, width as f32) as i32;
+            let min_y = (clip_rect.min.y * pixels_per_point).round().clamp(0.0, height as f32) as i32;
+            let max_x = (clip_rect.max.x * pixels_per_point).round().clamp(0.0, width as f32) as i32;
+            let max_y = (clip_rect.max.y * pixels_per_point).round().clamp(0.0, height as f32) as i32;
+            if max_x <= min_x || max_y <= min_y { continue }
+            Framebuffer::set_scissor(min_x, height as i32 - max_y, max_x - min_x, max_y - min_y);
+
+            self.geometry.draw_range(first_index, count);
+        }
+        Framebuffer::disable_scissor();
+        Framebuffer::disable_blending();
+    }
+}
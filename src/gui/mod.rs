@@ -0,0 +1,3 @@
+pub mod gui_context;
+pub mod gui_renderer;
+pub mod settings_panel;
@@ -0,0 +1,228 @@
+use std::collections::VecDeque;
+use egui::{ComboBox, Grid, Slider, Ui};
+use crate::rendering::camera::Camera;
+use crate::rendering::camera_path::{CameraPath, CameraPathPlayer, PlaybackClock};
+use crate::rendering::capture::{CaptureFormat, CaptureSource};
+use crate::rendering::debug_view::DebugView;
+use crate::rendering::gpu_profiler::GpuProfiler;
+use crate::rendering::render_settings::RenderSettings;
+use crate::rendering::scene::Scene;
+use crate::resource::resource_manager::ResourceManager;
+use crate::window::window::DisplayMode;
+
+// file name of the gpu times written from the panel, without extension
+pub const PROFILE_FILE: &str = "gpu_profile";
+// the camera path saved and loaded from the panel
+pub const CAMERA_PATH_FILE: &str = "camera_path.json";
+
+// frames averaged for the frame time
+const FRAME_HISTORY: usize = 100;
+
+pub struct FrameStats {
+    frame_times: VecDeque<f32>,
+}
+
+impl Default for FrameStats {
+    fn default() -> Self { Self::new() }
+}
+
+impl FrameStats {
+    pub fn new() -> Self {
+        Self { frame_times: VecDeque::with_capacity(FRAME_HISTORY) }
+    }
+
+    pub fn push(&mut self, dt: f32) {
+        if self.frame_times.len() == FRAME_HISTORY { self.frame_times.pop_front(); }
+        self.frame_times.push_back(dt);
+    }
+
+    pub fn average(&self) -> f32 {
+        if self.frame_times.is_empty() { return 0.0 }
+        self.frame_times.iter().sum::<f32>() / self.frame_times.len() as f32
+    }
+
+    pub fn max(&self) -> f32 {
+        self.frame_times.iter().copied().fold(0.0, f32::max)
+    }
+}
+
+fn names_section(ui: &mut Ui, label: &str, names: &[String]) {
+    ui.collapsing(format!("{} ({})", label, names.len()), |ui| {
+        names.iter().for_each(|name| { ui.label(name); });
+    });
+}
+
+// the state the panel shows without changing it
+pub struct PanelInfo<'a> {
+    pub stats: &'a FrameStats,
+    // names of the camera controllers, settings.camera_controller indexes them
+    pub camera_controllers: &'a [&'a str],
+    pub profiler: &'a GpuProfiler,
+    pub camera: &'a Camera,
+    pub scene: &'a Scene,
+    pub resource_manager: &'a ResourceManager,
+    pub render_size: (u32, u32),
+}
+
+// a window with the render settings, frame and scene statistics and the loaded resources
+pub fn settings_panel(ctx: &egui::Context, settings: &mut RenderSettings, camera_path: &mut CameraPathPlayer, info: &PanelInfo) {
+    let PanelInfo { stats, camera_controllers, profiler, camera, scene, resource_manager, render_size } = info;
+    egui::Window::new("Settings").default_width(280.0).show(ctx, |ui| {
+        let average = stats.average();
+        ui.label(format!(
+            "{:.2} ms ({:.0} fps), max {:.2} ms",
+            average * 1000.0, if average > 0.0 { 1.0 / average } else { 0.0 }, stats.max() * 1000.0
+        ));
+        ui.label(format!("{} triangles, {} bvh nodes", scene.trace_model().triangles().len(), scene.bvh().data().len()));
+
+        ui.collapsing("Display", |ui| {
+            ComboBox::from_label("mode")
+                .selected_text(settings.display_mode.to_string())
+                .show_ui(ui, |ui| {
+                    for mode in DisplayMode::ALL {
+                        ui.selectable_value(&mut settings.display_mode, mode, mode.to_string());
+                    }
+                });
+            ui.checkbox(&mut settings.vsync, "vsync");
+            ui.add(Slider::new(&mut settings.resolution_scale, 0.25..=2.0).text("resolution scale"));
+            ui.label(format!("rendering at {}x{}", render_size.0, render_size.1));
+        });
+
+        ui.collapsing("Camera", |ui| {
+            ComboBox::from_label("controller")
+                .selected_text(camera_controllers[settings.camera_controller])
+                .show_ui(ui, |ui| {
+                    for (i, name) in camera_controllers.iter().enumerate() {
+                        ui.selectable_value(&mut settings.camera_controller, i, *name);
+                    }
+                });
+            ui.add(Slider::new(&mut settings.movement_speed, 0.1..=50.0).logarithmic(true).text("movement speed"));
+            ui.add(Slider::new(&mut settings.mouse_sensitivity, 1.0..=20.0).text("mouse sensitivity"));
+        });
+
+        ui.collapsing("Camera path", |ui| {
+            let path = camera_path.path();
+            ui.label(format!("{} keyframes, {:.1} s", path.keyframes().len(), path.duration()));
+            ui.horizontal(|ui| {
+                if ui.button("Add keyframe").clicked() { camera_path.add_keyframe(camera, ui.input(|input| input.time)) }
+                if ui.button("Clear").clicked() { camera_path.clear() }
+                let play_label = if camera_path.is_playing() { "Stop" } else { "Play" };
+                if ui.button(play_label).clicked() {
+                    if camera_path.is_playing() { camera_path.stop() } else { camera_path.play() }
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    if let Err(e) = camera_path.path().save(CAMERA_PATH_FILE) { println!("Failed to save camera path: {:?}", e) }
+                }
+                if ui.button("Load").clicked() {
+                    match CameraPath::load(CAMERA_PATH_FILE) {
+                        Ok(path) => camera_path.set_path(path),
+                        Err(e) => println!("Failed to load camera path: {:?}", e),
+                    }
+                }
+            });
+            let mut fixed = matches!(camera_path.clock, PlaybackClock::Fixed(_));
+            let mut fps = match camera_path.clock { PlaybackClock::Fixed(step) => (1.0 / step).round() as u32, PlaybackClock::RealTime => 30 };
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut fixed, "fixed clock");
+                ui.add_enabled(fixed, Slider::new(&mut fps, 1..=120).text("fps"));
+            });
+            camera_path.clock = if fixed { PlaybackClock::Fixed(1.0 / fps as f32) } else { PlaybackClock::RealTime };
+            ui.checkbox(&mut camera_path.looping, "loop");
+        });
+
+        ui.collapsing("Light", |ui| {
+            ui.add(Slider::new(&mut settings.light_radius, 0.0..=100.0).text("radius"));
+            ui.add(Slider::new(&mut settings.light_height, -50.0..=100.0).text("height"));
+            ui.add(Slider::new(&mut settings.light_speed, -5.0..=5.0).text("speed"));
+            ui.horizontal(|ui| {
+                ui.color_edit_button_rgb(&mut settings.light_color);
+                ui.add(Slider::new(&mut settings.light_intensity, 0.0..=10.0).text("intensity"));
+            });
+            ui.add(Slider::new(&mut settings.ambient_strength, 0.0..=2.0).text("ambient"));
+            ui.add(Slider::new(&mut settings.reflection_strength, 0.0..=2.0).text("reflections"));
+        });
+
+        ui.collapsing("Materials", |ui| {
+            let mut overridden = settings.illum_override.is_some();
+            let mut illum = settings.illum_override.unwrap_or(2);
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut overridden, "illumination model");
+                ui.add_enabled(overridden, Slider::new(&mut illum, 0..=10));
+            });
+            settings.illum_override = overridden.then_some(illum);
+            ui.checkbox(&mut settings.white_diffuse, "white diffuse color");
+        });
+
+        ui.collapsing("Passes", |ui| {
+            ui.checkbox(&mut settings.trace_shadows, "shadow rays");
+            ui.checkbox(&mut settings.trace_reflections, "reflection rays");
+            ui.checkbox(&mut settings.trace_ambient, "ambient rays");
+            ComboBox::from_label("debug view")
+                .selected_text(settings.debug_view.to_string())
+                .show_ui(ui, |ui| {
+                    for view in DebugView::ALL {
+                        ui.selectable_value(&mut settings.debug_view, view, view.to_string());
+                    }
+                });
+            ui.checkbox(&mut settings.bvh_overlay, "bvh overlay");
+        });
+
+        ui.collapsing("Capture", |ui| {
+            ComboBox::from_label("source")
+                .selected_text(settings.capture_source.to_string())
+                .show_ui(ui, |ui| {
+                    for source in CaptureSource::ALL {
+                        ui.selectable_value(&mut settings.capture_source, source, source.to_string());
+                    }
+                });
+            ComboBox::from_label("format")
+                .selected_text(settings.capture_format.to_string())
+                .show_ui(ui, |ui| {
+                    for format in CaptureFormat::ALL {
+                        ui.selectable_value(&mut settings.capture_format, format, format.to_string());
+                    }
+                });
+            ui.add_enabled(!settings.recording, Slider::new(&mut settings.record_fps, 1..=120).text("recording fps"));
+            ui.horizontal(|ui| {
+                if ui.button("Screenshot").clicked() { settings.screenshot_requested = true }
+                let record_label = if settings.recording { "Stop recording" } else { "Record" };
+                if ui.button(record_label).clicked() { settings.recording = !settings.recording }
+            });
+        });
+
+        ui.collapsing("GPU times", |ui| {
+            Grid::new("gpuTimes").num_columns(3).striped(true).show(ui, |ui| {
+                for timing in profiler.averages() {
+                    ui.label(&timing.name);
+                    ui.label(format!("{:.3} ms", timing.average_ms));
+                    ui.label(format!("max {:.3} ms", timing.max_ms));
+                    ui.end_row();
+                }
+                ui.label("total");
+                ui.label(format!("{:.3} ms", profiler.average_total()));
+                ui.end_row();
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Log").clicked() { profiler.log() }
+                if ui.button("Write CSV and trace").clicked() { profiler.dump(PROFILE_FILE) }
+            });
+        });
+
+        ui.collapsing("Resources", |ui| {
+            let resources = resource_manager.loaded_resources();
+            ui.collapsing(format!("Models ({})", resources.models.len()), |ui| {
+                resources.models.iter().for_each(|name| {
+                    match resource_manager.model_load_stats(name) {
+                        Some(stats) => ui.label(format!("{}: {}", name, stats)),
+                        None => ui.label(name),
+                    };
+                });
+            });
+            names_section(ui, "Materials", &resources.materials);
+            names_section(ui, "Textures", &resources.textures);
+            names_section(ui, "Shader programs", &resources.shader_programs);
+        });
+    });
+}
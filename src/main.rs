@@ -1,14 +1,30 @@
 use std::sync::{Arc, Mutex};
-use cgmath::{SquareMatrix, Vector3, Vector4};
-use rand::{Rng, thread_rng};
-use crate::gl_wrapper::buffer::{ShaderStorageBuffer};
-use crate::gl_wrapper::framebuffer::Framebuffer;
-use crate::gl_wrapper::geometry_set::GeometrySetBuilder;
-use crate::gl_wrapper::types::{TextureAttachment, TextureFormat};
+use cgmath::{Array, Deg, EuclideanSpace, Matrix4, Point3, SquareMatrix, Vector3, Vector4};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use crate::gl_wrapper::shader::ShaderProgram;
+use crate::gl_wrapper::types::TextureFormat;
+use crate::gui::gui_context::Gui;
+use crate::gui::settings_panel::{settings_panel, FrameStats, PanelInfo, PROFILE_FILE};
+use crate::raytracing::types::AABB;
+use crate::rendering::bvh_overlay::{BvhFilter, BvhOverlay};
 use crate::rendering::camera::Camera;
+use crate::rendering::benchmark::Benchmark;
+use crate::rendering::camera_path::{CameraPath, CameraPathPlayer};
+use crate::rendering::capture::{screenshot, Recorder};
+use crate::resource::resource::Resource;
 use crate::resource::resource_manager::ResourceManager;
-use rendering::camera_controller::CameraController;
-use crate::rendering::framebuffer_manager::FramebufferManager;
+use crate::rendering::camera_controller::{CameraController, FlyController, OrbitController};
+use crate::rendering::debug_view::DEBUG_TRAVERSAL_PASS;
+use crate::rendering::gpu_profiler::GpuProfiler;
+use crate::rendering::render_settings::RenderSettings;
+use crate::rendering::model::Model;
+use crate::rendering::render_graph::{RenderGraph, RenderGraphBuilder, RenderPass};
+use crate::rendering::scene::{Scene, SceneBuilder};
+use crate::resource::shader_preprocessor::ShaderDefines;
+use crate::util::args::{Args, USAGE};
+use crate::util::error::{ActionMapError, ResourceError};
+use crate::window::action_map::ActionMap;
 use crate::window::window::Window;
 
 pub mod gl_wrapper;
@@ -17,230 +33,504 @@ pub mod rendering;
 pub mod util;
 pub mod window;
 pub mod resource;
+pub mod gui;
+
+// clear values of the g-buffer and intersection textures, must match the shaders
+const NO_MATERIAL: f32 = 1e30;
+const MISS: f32 = 1e30;
+
+// scenes that can be loaded by name, each a model standing on the ground, which the camera frames
+const SCENES: [(&str, &str); 4] = [
+    ("f16", "f16.obj"),
+    ("bunny", "bunny.obj"),
+    ("teapot", "teapot.obj"),
+    ("armadillo", "armadillo_lowres.obj"),
+];
+
+// placed below the model of the scene, reaching this many times its horizontal size to each side
+const GROUND_MODEL: &str = "ground.obj";
+const GROUND_SIZE: f32 = 2.0;
+
+// length of the orbit flown by benchmarks without a camera path
+const BENCHMARK_ORBIT_SECONDS: f32 = 10.0;
+
+// rebinds actions of the action map, actions missing from it keep their default bindings
+const INPUT_CONFIG: &str = "input.json";
+
+// moves the top of the ground to the bottom of the model and scales it to the model's size. the
+// result only depends on the bounds, so the ground can be placed again after either was reloaded
+fn place_ground(ground: &mut Model, model_bounds: &AABB) {
+    let Some(ground_bounds) = ground.bounds() else { return };
+    let ground_side = ground_bounds.extent().x.max(ground_bounds.extent().z);
+    let model_side = model_bounds.extent().x.max(model_bounds.extent().z);
+    if ground_side <= 0.0 || model_side <= 0.0 { return }
+    let scale = model_side * (1.0 + 2.0 * GROUND_SIZE) / ground_side;
+    let ground_top = Vector3::new(ground_bounds.center().x, ground_bounds.max.y, ground_bounds.center().z);
+    let model_bottom = Vector3::new(model_bounds.center().x, model_bounds.min.y, model_bounds.center().z);
+    ground.transform(Matrix4::from_translation(model_bottom) * Matrix4::from_scale(scale) * Matrix4::from_translation(-ground_top));
+}
+
+// shader variants for the vertex buffers the scene has
+fn scene_defines(scene: &Scene) -> ShaderDefines {
+    let mut defines = ShaderDefines::new();
+    if scene.has_normals() { defines.set("HAS_NORMALS", "") }
+    if scene.has_tex_coords() { defines.set("HAS_TEX_COORDS", "") }
+    defines
+}
+
+// a variant that fails to build on hot reload leaves the passes with their current program
+fn set_reloaded_program(graph: &mut RenderGraph, passes: &[&str], program: Result<Arc<Mutex<ShaderProgram>>, ResourceError>) {
+    match program {
+        Ok(program) => passes.iter().for_each(|pass| graph.set_program(pass, program.clone())),
+        Err(e) => ResourceManager::print_reload_error(&passes.join(", "), &e),
+    }
+}
 
 fn main() {
+    let args = Args::from_env().unwrap_or_else(|e| {
+        println!("{}\n{}", e, USAGE);
+        std::process::exit(1)
+    });
+    let Some((_, scene_model)) = SCENES.iter().find(|(name, _)| *name == args.scene) else {
+        println!("Unknown scene {}, the scenes are {:?}", args.scene, SCENES.map(|(name, _)| name));
+        std::process::exit(1)
+    };
+
     // create window
-    let window = Arc::new(Mutex::new(Window::new(1000, 800, "Raytracing :)").expect("Failed to create window!")));
+    let window = Arc::new(Mutex::new(Window::new("Raytracing :)", &args.window).expect("Failed to create window!")));
+    // key, mouse and gamepad bindings of the actions, the defaults apply without a config
+    // the config is read from the resource directory build.rs copies next to the executable, not
+    // relative to the working directory
+    match Resource::new_rel_to_exe("res").map_err(ActionMapError::ResourceError).and_then(|res| ActionMap::load(&res, INPUT_CONFIG)) {
+        Ok(actions) => window.lock().unwrap().input_mut().set_action_map(actions),
+        Err(e) => println!("Failed to load {}, using the default bindings: {:?}", INPUT_CONFIG, e),
+    }
     let mut camera = Camera::new_default(window.clone());
-    let camera_controller = CameraController::new(window.clone(), 1.0, 8.0);
+    let mut settings = RenderSettings {
+        display_mode: window.lock().unwrap().display_mode(),
+        vsync: args.window.vsync,
+        resolution_scale: args.resolution_scale,
+        ..Default::default()
+    };
+    let mut controllers: Vec<Box<dyn CameraController>> = vec![Box::new(FlyController), Box::new(OrbitController::new())];
+    let controller_names: Vec<&str> = controllers.iter().map(|controller| controller.name()).collect();
+    let mut active_controller = settings.camera_controller;
 
     // load resources
-    let mut resource_manager = ResourceManager::new("res/models", "res/textures", "res/shaders").expect("Failed to create resource manager");
-
-    let model = resource_manager.get_model("f16.obj").expect("Failed to load model resources");
-    model.lock().unwrap().build_bvh();
+    // debug builds load from the source tree and reload resources edited while running, except
+    // when benchmarking
+    let mut resource_manager = if cfg!(debug_assertions) && args.benchmark.is_none() {
+        ResourceManager::new_hot_reload("res/models", "res/textures", "res/shaders")
+    } else {
+        ResourceManager::new("res/models", "res/textures", "res/shaders")
+    }.expect("Failed to create resource manager");
+    resource_manager.set_mesh_processing(args.mesh_processing);
+
+    let model = resource_manager.get_model(scene_model).expect("Failed to load model resources");
+    let ground = resource_manager.get_model(GROUND_MODEL).expect("Failed to load model resources");
+    // an empty model leaves the camera and the ground where they are
+    let model_bounds = model.lock().unwrap().bounds();
+    if let Some(bounds) = &model_bounds {
+        camera.frame(bounds);
+        place_ground(&mut ground.lock().unwrap(), bounds);
+    }
+    let mut scene = SceneBuilder::new()
+        .add_model(model.clone())
+        .add_model(ground.clone())
+        .build(&mut resource_manager).expect("Failed to build scene");
 
     let g_buffer_program = resource_manager.create_shader_program(
-        "gBuffer", "rasterize/default.vert", "rasterize/default.frag"
+        "gBuffer", "rasterize/default.vert", "rasterize/default.frag", &ShaderDefines::new()
     ).expect("Failed to load shader");
     let ray_dir_create_program = resource_manager.create_shader_program(
-        "rayDirCreate", "util/quad-11.vert", "ray_trace/ray_create.frag"
+        "rayDirCreate", "util/quad-11.vert", "ray_trace/ray_create.frag", &ShaderDefines::new()
     ).expect("Failed to load shader");
     let ray_dispatch_program = resource_manager.create_shader_program(
-        "rayDispatch", "util/quad01.vert", "ray_trace/ray_dispatcher.frag"
+        "rayDispatch", "util/quad01.vert", "ray_trace/ray_dispatcher.frag", &ShaderDefines::new()
     ).expect("Failed to load shader");
     let ray_trace_program = resource_manager.create_shader_program(
-        "rayTrace", "util/quad01.vert", "ray_trace/ray_trace.frag"
+        "rayTrace", "util/quad01.vert", "ray_trace/ray_trace.frag", &scene_defines(&scene)
+    ).expect("Failed to load shader");
+    let debug_traversal_program = resource_manager.create_shader_program(
+        "rayTrace", "util/quad01.vert", "ray_trace/ray_trace.frag", &scene_defines(&scene).with_flag("DEBUG_TRAVERSAL")
     ).expect("Failed to load shader");
     let shader_program = resource_manager.create_shader_program(
-        "shader", "util/quad01.vert", "shader.frag"
+        "shader", "util/quad01.vert", "shader.frag", &scene_defines(&scene)
     ).expect("Failed to load shader");
     let display_program = resource_manager.create_shader_program(
-        "display", "util/quad01.vert", "util/display.frag"
+        "display", "util/quad01.vert", "util/display.frag", &ShaderDefines::new()
+    ).expect("Failed to load shader");
+    let bvh_overlay_program = resource_manager.create_shader_program(
+        "bvhOverlay", "rasterize/bvh_overlay.vert", "rasterize/bvh_overlay.frag", &ShaderDefines::new()
+    ).expect("Failed to load shader");
+    let gui_program = resource_manager.create_shader_program(
+        "gui", "util/gui.vert", "util/gui.frag", &ShaderDefines::new()
     ).expect("Failed to load shader");
 
     // load blue noise texture
-    let blue_noise_tex = resource_manager.get_texture("blue_noise.png").expect("Failed to load blue noise texture");
-
-    // create frame buffers
-
-    let mut fbo_manager = FramebufferManager::new(window.clone());
-
-    let g_buffer = fbo_manager.new_framebuffer();
-    let position_tex = fbo_manager.attach_texture(TextureFormat::RGB32F, TextureAttachment::Color(0), true);
-    let normal_mat_tex = fbo_manager.attach_texture(TextureFormat::RGBA32F, TextureAttachment::Color(1), true);
-    let tex_coord_tex = fbo_manager.attach_texture(TextureFormat::RG32F, TextureAttachment::Color(2), true);
-    let _depth_rbo = fbo_manager.attach_renderbuffer(TextureFormat::Depth, TextureAttachment::Depth, false);
-
-    let ray_dir_buffer = fbo_manager.new_framebuffer();
-    let ray_dir_tex = fbo_manager.attach_texture(TextureFormat::RGB32F, TextureAttachment::Color(0), true);
-
-    let ray_buffer = fbo_manager.new_framebuffer();
-    let ray_org_tex = fbo_manager.attach_texture(TextureFormat::RGB32F, TextureAttachment::Color(0), true);
-    let shadow_ray_dir_tex = fbo_manager.attach_texture(TextureFormat::RGB32F, TextureAttachment::Color(1), true);
-    let reflect_ray_dir_tex = fbo_manager.attach_texture(TextureFormat::RGB32F, TextureAttachment::Color(2), true);
-    let ambient_ray_dir_tex = fbo_manager.attach_texture(TextureFormat::RGB32F, TextureAttachment::Color(3), true);
-
-    let shadow_intersection_buffer = fbo_manager.new_framebuffer();
-    let shadow_intersection_tex = fbo_manager.attach_texture(TextureFormat::RGBA32F, TextureAttachment::Color(0), true);
-
-    let reflect_intersection_buffer = fbo_manager.new_framebuffer();
-    let reflect_intersection_tex = fbo_manager.attach_texture(TextureFormat::RGBA32F, TextureAttachment::Color(0), true);
-
-    let ambient_intersection_buffer = fbo_manager.new_framebuffer();
-    let ambient_intersection_tex = fbo_manager.attach_texture(TextureFormat::RGBA32F, TextureAttachment::Color(0), true);
-
-    let color_buffer = fbo_manager.new_framebuffer();
-    let color_tex = fbo_manager.attach_texture(TextureFormat::RGBA32F, TextureAttachment::Color(0), true);
-
-    fbo_manager.build_framebuffers();
-
-    // create geometry
-    let (quad_geometry, _ibo, _vbo) = GeometrySetBuilder::create_square_geometry();
-    let (model_geometry, _m_ibo, _m_vbos) = GeometrySetBuilder::from_model(model.clone());
-
-    // create buffer with mesh
-    let node_ssbo = ShaderStorageBuffer::new();
-    let triangle_ssbo = ShaderStorageBuffer::new();
-    let position_ssbo = ShaderStorageBuffer::new();
-    let tex_coord_ssbo = ShaderStorageBuffer::new();
-    let normal_ssbo = ShaderStorageBuffer::new();
-    node_ssbo.buffer_data(model.lock().unwrap().get_bvh().unwrap().data());
-    triangle_ssbo.buffer_data(model.lock().unwrap().triangles());
-    position_ssbo.buffer_data(model.lock().unwrap().positions());
-    if let Some(model_uvs) = model.lock().unwrap().tex_coords() { tex_coord_ssbo.buffer_data(model_uvs) }
-    if let Some(model_normals) = model.lock().unwrap().normals() { normal_ssbo.buffer_data(model_normals) }
-
-    let mut time = 0.0;
+    let mut blue_noise_tex = resource_manager.get_texture("blue_noise.png").expect("Failed to load blue noise texture");
+
+    // create render graph
+    let trace_pass = |name: &str, dir: &str, hits: &str| RenderPass::new(name, ray_trace_program.clone())
+        .input("dir", dir)
+        .input("org", "rayOrigin")
+        .cleared_output(hits, TextureFormat::RGBA32F, [MISS, 0.0, 0.0, 0.0])
+        .fullscreen();
+    let mut graph = RenderGraphBuilder::new()
+        .add_pass(RenderPass::new("gBuffer", g_buffer_program)
+            .cleared_output("position", TextureFormat::RGB32F, [0.0; 4])
+            .cleared_output("normalMat", TextureFormat::RGBA32F, [0.0, 0.0, 0.0, NO_MATERIAL])
+            .cleared_output("texCoord", TextureFormat::RG32F, [0.0; 4])
+            .depth_buffer())
+        .add_pass(RenderPass::new("rayDirCreate", ray_dir_create_program)
+            .cleared_output("viewDir", TextureFormat::RGB32F, [0.0; 4])
+            .fullscreen())
+        .add_pass(RenderPass::new("rayDispatch", ray_dispatch_program)
+            .input("positionData", "position")
+            .input("normalMatData", "normalMat")
+            .cleared_output("rayOrigin", TextureFormat::RGB32F, [0.0; 4])
+            .cleared_output("shadowDir", TextureFormat::RGB32F, [0.0; 4])
+            .cleared_output("reflectDir", TextureFormat::RGB32F, [0.0; 4])
+            .cleared_output("ambientDir", TextureFormat::RGB32F, [0.0; 4])
+            .fullscreen())
+        .add_pass(trace_pass("traceShadows", "shadowDir", "shadowHits"))
+        .add_pass(trace_pass("traceReflections", "reflectDir", "reflectHits"))
+        .add_pass(trace_pass("traceAmbient", "ambientDir", "ambientHits"))
+        .add_pass(RenderPass::new("shade", shader_program)
+            .input("position", "position")
+            .input("normalMat", "normalMat")
+            .input("texCoord", "texCoord")
+            .input("viewDir", "viewDir")
+            .input("shadowDir", "shadowDir")
+            .input("shadowHits", "shadowHits")
+            .input("reflectDir", "reflectDir")
+            .input("reflectHits", "reflectHits")
+            .input("ambientDir", "ambientDir")
+            .input("ambientHits", "ambientHits")
+            .cleared_output("color", TextureFormat::RGBA32F, [0.0; 4])
+            .fullscreen())
+        .add_pass(RenderPass::new(DEBUG_TRAVERSAL_PASS, debug_traversal_program)
+            .input("dir", "viewDir")
+            .cleared_output("primaryHits", TextureFormat::RGBA32F, [MISS, 0.0, 0.0, 0.0])
+            .cleared_output("traversalCost", TextureFormat::RG32F, [0.0; 4])
+            .fullscreen())
+        // shows the texture of the debug view, scaled from the render size to the window
+        .add_pass(RenderPass::new("display", display_program)
+            .fullscreen())
+        // bvh node boxes drawn over the image
+        .add_pass(RenderPass::new("bvhOverlay", bvh_overlay_program))
+        // the settings panel on top of everything
+        .add_pass(RenderPass::new("gui", gui_program))
+        .build(window.clone()).expect("Failed to build render graph");
+    let mut bvh_filter = BvhFilter::Level(0);
+    let mut bvh_overlay: Option<BvhOverlay> = None;
+
+    let mut gui = Gui::new();
+    let mut frame_stats = FrameStats::new();
+    let mut profiler = GpuProfiler::new();
+    let mut recorder: Option<Recorder> = None;
+    // the fixed timestep from before the recording, a benchmark's or none, restored when it stops
+    let mut timestep_before_recording: Option<f32> = None;
+    let mut camera_path = CameraPathPlayer::new();
+    let mut light_angle = 0.0;
+    // benchmarks are deterministic, rendering uses the same random numbers and timestep every run
+    let mut rng = args.seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64);
+    let mut benchmark = args.benchmark.as_ref().map(|benchmark_args| {
+        let path = match &benchmark_args.camera_path {
+            Some(file) => CameraPath::load(file).expect("Failed to load camera path"),
+            None => {
+                // orbits the benchmarked model without the ground, an empty one like a unit box around
+                // the origin
+                let bounds = model_bounds.unwrap_or(AABB::new(Vector3::from_value(-1.0), Vector3::from_value(1.0)));
+                CameraPath::orbit(&bounds, Deg::from(camera.fov()), BENCHMARK_ORBIT_SECONDS, 8)
+            }
+        };
+        camera_path.set_path(path);
+        settings.show_gui = false;
+        window.lock().unwrap().set_fixed_timestep(Some(1.0 / benchmark_args.fps as f32));
+        Benchmark::new(benchmark_args, &mut camera_path, &mut camera)
+    });
     while !window.lock().unwrap().should_close() {
         // handle events
         window.lock().unwrap().handle_events();
-        camera_controller.control(&mut camera);
-
-        time += window.lock().unwrap().dt();
-
-        println!("FPS: {}", (1.0 / window.lock().unwrap().dt()) as u32);
+        if let Some(bench) = &mut benchmark {
+            if !bench.update(&mut camera_path, &profiler) { break }
+        }
+        if settings.camera_controller != active_controller {
+            controllers[active_controller].deactivate(&mut window.lock().unwrap());
+            active_controller = settings.camera_controller;
+        }
+        // the camera follows the path while it is played, otherwise the controller moves it
+        if camera_path.is_playing() {
+            let dt = window.lock().unwrap().dt();
+            camera_path.update(&mut camera, dt);
+        } else if benchmark.is_none() {
+            let pointer_free = !(settings.show_gui && gui.wants_pointer());
+            controllers[active_controller].control(&mut camera, &mut window.lock().unwrap(), &mut settings, pointer_free);
+        }
 
-        // update buffers
-        if window.lock().unwrap().resized() {
-            unsafe {
-                let window = window.lock().unwrap();
-                gl::Viewport(0, 0, window.width() as i32, window.height() as i32)
+        let reloaded = resource_manager.poll_changes();
+        if reloaded.scene_changed() {
+            // a reloaded ground is back at its place in the file, a reloaded model may have moved
+            if let Some(bounds) = model.lock().unwrap().bounds() { place_ground(&mut ground.lock().unwrap(), &bounds) }
+            match scene.rebuild(&mut resource_manager) {
+                Ok(rebuilt) => scene = rebuilt,
+                Err(e) => println!("Failed to rebuild scene: {:?}", e),
+            }
+            let defines = scene_defines(&scene);
+            set_reloaded_program(&mut graph, &["traceShadows", "traceReflections", "traceAmbient"], resource_manager.create_shader_program(
+                "rayTrace", "util/quad01.vert", "ray_trace/ray_trace.frag", &defines
+            ));
+            set_reloaded_program(&mut graph, &[DEBUG_TRAVERSAL_PASS], resource_manager.create_shader_program(
+                "rayTrace", "util/quad01.vert", "ray_trace/ray_trace.frag", &defines.clone().with_flag("DEBUG_TRAVERSAL")
+            ));
+            set_reloaded_program(&mut graph, &["shade"], resource_manager.create_shader_program(
+                "shader", "util/quad01.vert", "shader.frag", &defines
+            ));
+            blue_noise_tex = resource_manager.get_texture("blue_noise.png").expect("Failed to load blue noise texture");
+            // triangle indices change with the bvh
+            if bvh_overlay.is_some() {
+                bvh_filter = BvhFilter::Level(0);
+                bvh_overlay = Some(BvhOverlay::new(scene.bvh(), bvh_filter));
             }
-            fbo_manager.update_buffers();
         }
+        let material_buffer = scene.material_buffer();
 
-        let cvv = camera.generate_view_vectors();
-        let vp_mat = camera.view_proj_matrices();
-
-        let light_pos = Vector3::new(time.sin() * 20.0, 20.0, time.cos() * 20.0);
-
-        // clear g-buffer and render
-        fbo_manager.bind_fbo(g_buffer);
-        Framebuffer::set_clear_color(0.0, 0.0, 0.0, 1e30); // materialIdx is set to 1e30 (code for "no material")
-        Framebuffer::clear_color_depth();
-        Framebuffer::enable_depth_test();
-        {
-            let mut program = g_buffer_program.lock().unwrap();
-            program.bind();
-            program.set_uniform_mat_4f(0, vp_mat.proj * vp_mat.view);
-            program.set_uniform_1i(1, 0);
+        // shortcuts are actions of the action map, res/input.json lists them with their bindings.
+        // they are ignored while the gui takes text input
+        let mut pick = None;
+        if !(settings.show_gui && gui.wants_keyboard()) {
+            let window = window.lock().unwrap();
+            let input = window.input();
+            if input.action_down("toggle_gui") { settings.show_gui = !settings.show_gui }
+            if input.action_down("log_gpu_times") { profiler.log() }
+            if input.action_down("write_gpu_times") { profiler.dump(PROFILE_FILE) }
+            if input.action_down("screenshot") { settings.screenshot_requested = true }
+            if input.action_down("toggle_recording") { settings.recording = !settings.recording }
+            if input.action_down("add_keyframe") {
+                camera_path.add_keyframe(&camera, window.time());
+                println!("Camera path: {} keyframes, {:.1} s", camera_path.path().keyframes().len(), camera_path.path().duration());
+            }
+            if input.action_down("toggle_path_playback") {
+                if camera_path.is_playing() { camera_path.stop() } else { camera_path.play() }
+            }
+            if input.action_down("next_camera_controller") {
+                settings.camera_controller = (settings.camera_controller + 1) % controllers.len();
+                println!("Camera controller: {}", controller_names[settings.camera_controller]);
+            }
+            // the previous view is bound with a modifier on the key of the next one
+            let debug_view = if input.action_down("previous_debug_view") { Some(settings.debug_view.previous()) }
+                else if input.action_down("next_debug_view") { Some(settings.debug_view.next()) }
+                else { None };
+            if let Some(debug_view) = debug_view {
+                settings.debug_view = debug_view;
+                println!("Debug view: {}", settings.debug_view);
+            }
+            if input.action_down("toggle_bvh_overlay") { settings.bvh_overlay = !settings.bvh_overlay }
         }
-        model_geometry.draw();
-        Framebuffer::disable_depth_test();
-
-        // create rays
-        fbo_manager.bind_fbo(ray_dir_buffer);
-        Framebuffer::set_clear_color(0.0, 0.0, 0.0, 0.0); // ray dir is vec3(0, 0, 0) by default
-        Framebuffer::clear_color();
-        {
-            let mut program = ray_dir_create_program.lock().unwrap();
-            program.bind();
-            program.set_uniform_mat_4f(0, (vp_mat.proj * vp_mat.view).invert().unwrap());
-            program.set_uniform_1f(1, vp_mat.near);
-            program.set_uniform_1f(2, vp_mat.far);
+        // the overlay follows the setting, which the gui can change as well
+        if settings.bvh_overlay != bvh_overlay.is_some() {
+            bvh_overlay = settings.bvh_overlay.then(|| {
+                println!("BVH overlay: {}", bvh_filter);
+                BvhOverlay::new(scene.bvh(), bvh_filter)
+            });
         }
-        quad_geometry.draw();
-
-        // dispatch rays
-        fbo_manager.bind_fbo(ray_buffer);
-        Framebuffer::set_clear_color(0.0, 0.0, 0.0, 0.0); // ray org and dir is set to NO_RAY (=vec3(0, 0, 0))
-        Framebuffer::clear_color();
-        {
+        if !(settings.show_gui && gui.wants_keyboard()) {
             let window = window.lock().unwrap();
-            let noise_settings = Vector4::new(
-                thread_rng().gen_range(0..blue_noise_tex.width()) as f32/ blue_noise_tex.width() as f32,
-                thread_rng().gen_range(0..blue_noise_tex.height()) as f32 / blue_noise_tex.height() as f32,
-                window.width() as f32 / blue_noise_tex.width() as f32,
-                window.height() as f32 / blue_noise_tex.height() as f32,
-            );
-            let mut program = ray_dispatch_program.lock().unwrap();
-            program.bind();
-            program.set_uniform_texture(0, fbo_manager.bind_tex_to_slot(position_tex, 0));
-            program.set_uniform_texture(1, fbo_manager.bind_tex_to_slot(normal_mat_tex, 1));
-            program.set_uniform_texture(2, blue_noise_tex.bind_to_slot(2));
-            program.set_uniform_3f(3, light_pos);
-            program.set_uniform_3f(4, cvv.pos);
-            program.set_uniform_4f(5, noise_settings);
+            let input = window.input();
+            let mut filter = bvh_filter;
+            if let Some(overlay) = &bvh_overlay {
+                let level = match bvh_filter { BvhFilter::Level(level) => level, BvhFilter::Triangle(_) => 0 };
+                if input.action_down("bvh_level_up") { filter = BvhFilter::Level((level + 1).min(overlay.max_depth())) }
+                if input.action_down("bvh_level_down") { filter = BvhFilter::Level(level.saturating_sub(1)) }
+                if input.action_down("pick_triangle") {
+                    pick = Some(graph.texel_at(window.framebuffer_pos(input.cursor_pos())));
+                }
+            }
+            if filter != bvh_filter {
+                bvh_filter = filter;
+                bvh_overlay = Some(BvhOverlay::new(scene.bvh(), bvh_filter));
+                println!("BVH overlay: {}", bvh_filter);
+            }
+        }
+        graph.set_pass_enabled("traceShadows", settings.trace_shadows);
+        graph.set_pass_enabled("traceReflections", settings.trace_reflections);
+        graph.set_pass_enabled("traceAmbient", settings.trace_ambient);
+        // the orbit controller looks up the point under the cursor in the camera ray hits as well
+        let target_pick = controllers[active_controller].target_request()
+            .map(|pos| graph.texel_at(window.lock().unwrap().framebuffer_pos(pos)));
+        graph.set_pass_enabled(DEBUG_TRAVERSAL_PASS, settings.debug_view.traces_camera_rays() || pick.is_some() || target_pick.is_some());
+        graph.set_pass_enabled("bvhOverlay", bvh_overlay.is_some());
+        graph.set_pass_enabled("gui", settings.show_gui);
+
+        let dt = window.lock().unwrap().dt();
+        light_angle += dt * settings.light_speed;
+        frame_stats.push(dt);
+
+        // runs the gui before rendering, its changes apply to this frame
+        if settings.show_gui {
+            let info = PanelInfo { stats: &frame_stats, camera_controllers: &controller_names, profiler: &profiler, camera: &camera, scene: &scene, resource_manager: &resource_manager, render_size: graph.render_size() };
+            gui.run(&window.lock().unwrap(), |ctx| settings_panel(ctx, &mut settings, &mut camera_path, &info));
         }
-        quad_geometry.draw();
 
-        // trace rays
-        Framebuffer::set_clear_color(1e30, 0.0, 0.0, 0.0); // t-value of Intersection is set to MISS (=1e30)
-        node_ssbo.bind_to_slot(0);
-        triangle_ssbo.bind_to_slot(1);
-        position_ssbo.bind_to_slot(2);
+        // the window follows the settings, which the gui can change as well
         {
-            let mut program = ray_trace_program.lock().unwrap();
-            program.bind();
-            program.set_uniform_texture(1, fbo_manager.bind_tex_to_slot(ray_org_tex, 1));
-
-            fbo_manager.bind_fbo(shadow_intersection_buffer);
-            Framebuffer::clear_color();
-            program.set_uniform_texture(0, fbo_manager.bind_tex_to_slot(shadow_ray_dir_tex, 0));
-            quad_geometry.draw();
-
-            fbo_manager.bind_fbo(reflect_intersection_buffer);
-            Framebuffer::clear_color();
-            program.set_uniform_texture(0, fbo_manager.bind_tex_to_slot(reflect_ray_dir_tex, 0));
-            quad_geometry.draw();
-
-            fbo_manager.bind_fbo(ambient_intersection_buffer);
-            Framebuffer::clear_color();
-            program.set_uniform_texture(0, fbo_manager.bind_tex_to_slot(ambient_ray_dir_tex, 0));
-            quad_geometry.draw();
+            let mut window = window.lock().unwrap();
+            if settings.display_mode != window.display_mode() {
+                if let Err(e) = window.set_display_mode(settings.display_mode) {
+                    println!("Failed to change display mode: {:?}", e);
+                    settings.display_mode = window.display_mode();
+                }
+            }
+            if settings.vsync != window.vsync() { window.set_vsync(settings.vsync) }
         }
 
-        // shade
-        fbo_manager.bind_fbo(color_buffer);
-        Framebuffer::set_clear_color(0.0, 0.0, 0.0, 0.0);
-        Framebuffer::clear_color();
-        triangle_ssbo.bind_to_slot(0);
-        position_ssbo.bind_to_slot(1);
-        tex_coord_ssbo.bind_to_slot(2);
-        normal_ssbo.bind_to_slot(3);
-        {
-            let mut program = shader_program.lock().unwrap();
-            program.bind();
-            program.set_uniform_texture(0, fbo_manager.bind_tex_to_slot(position_tex, 0));
-            program.set_uniform_texture(1, fbo_manager.bind_tex_to_slot(normal_mat_tex, 1));
-            program.set_uniform_texture(2, fbo_manager.bind_tex_to_slot(tex_coord_tex, 2));
-            program.set_uniform_texture(3, fbo_manager.bind_tex_to_slot(ray_dir_tex, 3));
-            program.set_uniform_texture(4, fbo_manager.bind_tex_to_slot(shadow_ray_dir_tex, 4));
-            program.set_uniform_texture(5, fbo_manager.bind_tex_to_slot(shadow_intersection_tex, 5));
-            program.set_uniform_texture(6, fbo_manager.bind_tex_to_slot(reflect_ray_dir_tex, 6));
-            program.set_uniform_texture(7, fbo_manager.bind_tex_to_slot(reflect_intersection_tex, 7));
-            program.set_uniform_texture(8, fbo_manager.bind_tex_to_slot(ambient_ray_dir_tex, 8));
-            program.set_uniform_texture(9, fbo_manager.bind_tex_to_slot(ambient_intersection_tex, 9));
-            program.set_uniform_3f(10, light_pos);
-            program.set_uniform_3f(11, cvv.pos);
-            program.set_uniform_1b(12, model.lock().unwrap().has_normals());
-            program.set_uniform_1b(13, model.lock().unwrap().has_tex_coords());
+        // update buffers
+        if window.lock().unwrap().resized() { graph.resize() }
+        if settings.resolution_scale != graph.resolution_scale() { graph.set_resolution_scale(settings.resolution_scale) }
+        let render_size = graph.render_size();
+
+        let cvv = camera.generate_view_vectors();
+        let vp_mat = camera.view_proj_matrices();
+
+        let light_pos = settings.light_position(light_angle);
+        let illum_override = settings.illum_override.unwrap_or(-1);
+
+        graph.execute_profiled(&mut profiler, |pass| {
+            let program = pass.program;
+            match pass.name {
+                "gBuffer" => {
+                    program.set_uniform_mat_4f("mvp", vp_mat.proj * vp_mat.view);
+                    material_buffer.bind_textures(program, pass.free_texture_slot);
+                    program.bind_storage_block("materialBuffer", material_buffer.buffer());
+                    for model in scene.models() {
+                        program.bind_storage_block("triangleMaterialBuffer", model.triangle_material_buffer());
+                        model.draw();
+                    }
+                }
+                "rayDirCreate" => {
+                    program.set_uniform_mat_4f("invProjView", (vp_mat.proj * vp_mat.view).invert().unwrap());
+                    program.set_uniform_1f("near", vp_mat.near);
+                    program.set_uniform_1f("far", vp_mat.far);
+                }
+                "rayDispatch" => {
+                    let noise_settings = Vector4::new(
+                        rng.gen_range(0..blue_noise_tex.width()) as f32/ blue_noise_tex.width() as f32,
+                        rng.gen_range(0..blue_noise_tex.height()) as f32 / blue_noise_tex.height() as f32,
+                        render_size.0 as f32 / blue_noise_tex.width() as f32,
+                        render_size.1 as f32 / blue_noise_tex.height() as f32,
+                    );
+                    program.set_uniform_texture("blueNoise", blue_noise_tex.bind_to_slot(pass.free_texture_slot));
+                    program.set_uniform_3f("lightPos", light_pos);
+                    program.set_uniform_3f("cameraPos", cvv.pos);
+                    program.set_uniform_4f("noiseOffsetScale", noise_settings);
+                    program.set_uniform_1i("illumOverride", illum_override);
+                    program.bind_storage_block("matBuffer", material_buffer.buffer());
+                }
+                "traceShadows" | "traceReflections" | "traceAmbient" | DEBUG_TRAVERSAL_PASS => {
+                    // camera rays start at the camera instead of the g-buffer
+                    if pass.name == DEBUG_TRAVERSAL_PASS { program.set_uniform_3f("cameraPos", cvv.pos) }
+                    material_buffer.bind_textures(program, pass.free_texture_slot);
+                    program.bind_storage_block("nodeBuffer", scene.node_buffer());
+                    program.bind_storage_block("triangleBuffer", scene.triangle_buffer());
+                    program.bind_storage_block("positionBuffer", scene.position_buffer());
+                    if scene.has_tex_coords() { program.bind_storage_block("texCoordBuffer", scene.tex_coord_buffer()) }
+                    program.bind_storage_block("materialBuffer", material_buffer.buffer());
+                }
+                "shade" => {
+                    program.set_uniform_3f("lightPos", light_pos);
+                    program.set_uniform_3f("cameraPos", cvv.pos);
+                    material_buffer.bind_textures(program, pass.free_texture_slot);
+                    program.set_uniform_1f("pixelSpreadAngle", camera.pixel_spread_angle(render_size.1));
+                    program.set_uniform_3f("lightColor", settings.light_color());
+                    program.set_uniform_1f("ambientStrength", settings.ambient_strength);
+                    program.set_uniform_1f("reflectionStrength", settings.reflection_strength);
+                    program.set_uniform_1i("illumOverride", illum_override);
+                    program.set_uniform_1b("whiteDiffuse", settings.white_diffuse);
+                    program.bind_storage_block("triangleBuffer", scene.triangle_buffer());
+                    program.bind_storage_block("positionBuffer", scene.position_buffer());
+                    if scene.has_tex_coords() { program.bind_storage_block("texCoordBuffer", scene.tex_coord_buffer()) }
+                    if scene.has_normals() { program.bind_storage_block("normalBuffer", scene.normal_buffer()) }
+                    program.bind_storage_block("materialBuffer", material_buffer.buffer());
+                }
+                "display" => settings.debug_view.set_uniforms(program, &graph, &scene, pass.free_texture_slot),
+                "bvhOverlay" => if let Some(overlay) = &bvh_overlay {
+                    program.set_uniform_mat_4f("mvp", vp_mat.proj * vp_mat.view);
+                    overlay.draw();
+                }
+                "gui" => {
+                    let window = window.lock().unwrap();
+                    gui.paint(program, (window.width(), window.height()));
+                }
+                _ => {}
+            }
+        });
+
+        // the camera ray hits hold the index of the triangle under the cursor
+        if let Some((x, y)) = pick {
+            match graph.read_pixel("primaryHits", x, y) {
+                Some(hit) if hit[0] < MISS => {
+                    bvh_filter = BvhFilter::Triangle(hit[3].to_bits());
+                    bvh_overlay = Some(BvhOverlay::new(scene.bvh(), bvh_filter));
+                    println!("BVH overlay: {}, {} nodes", bvh_filter, bvh_overlay.as_ref().unwrap().node_count());
+                }
+                _ => println!("BVH overlay: no triangle under the cursor"),
+            }
+        }
+        if let Some((x, y)) = target_pick {
+            if let (Some(hit), Some(dir)) = (graph.read_pixel("primaryHits", x, y), graph.read_pixel("viewDir", x, y)) {
+                if hit[0] < MISS {
+                    let target = cvv.pos + Vector3::new(dir[0], dir[1], dir[2]) * hit[0];
+                    controllers[active_controller].set_target(&mut camera, Point3::from_vec(target));
+                }
+            }
         }
-        quad_geometry.draw();
 
-        // temp: draw any buffer to screen
-        Framebuffer::bind_default();
-        {
-            let mut program = display_program.lock().unwrap();
-            program.bind();
-            program.set_uniform_texture(0, fbo_manager.bind_tex_to_slot(color_tex, 0));
-            program.set_uniform_texture(0, 0);
+        // captures read the frame before the buffers are swapped
+        let window_size = { let window = window.lock().unwrap(); (window.width(), window.height()) };
+        if settings.screenshot_requested {
+            settings.screenshot_requested = false;
+            match screenshot(settings.capture_source, settings.capture_format, &graph, window_size) {
+                Ok(path) => println!("Saved screenshot to {}", path.display()),
+                Err(e) => println!("Failed to save screenshot: {:?}", e),
+            }
+        }
+        if let Some(rec) = &mut recorder {
+            if let Err(e) = rec.record(&graph, window_size) {
+                println!("Failed to record frame: {:?}", e);
+                settings.recording = false;
+            }
+        }
+        // recording starts with the next frame, which already runs at the fixed timestep
+        if settings.recording != recorder.is_some() {
+            if let Some(rec) = &recorder { println!("Recorded {} frames to {}", rec.frame_count(), rec.dir().display()) }
+            let was_recording = recorder.is_some();
+            recorder = if settings.recording {
+                Recorder::new(settings.capture_source, settings.capture_format)
+                    .inspect(|rec| println!("Recording to {}", rec.dir().display()))
+                    .inspect_err(|e| println!("Failed to start recording: {:?}", e))
+                    .ok()
+            } else { None };
+            settings.recording = recorder.is_some();
+            if settings.recording {
+                timestep_before_recording = window.lock().unwrap().fixed_timestep();
+                window.lock().unwrap().set_fixed_timestep(Some(1.0 / settings.record_fps as f32));
+            } else if was_recording {
+                window.lock().unwrap().set_fixed_timestep(timestep_before_recording);
+            }
         }
-        quad_geometry.draw();
 
+        resource_manager.warn_unset_uniforms();
         window.lock().unwrap().update();
     }
 
+    if let Some(bench) = &mut benchmark {
+        let report = bench.report(&mut profiler, &args.scene, args.seed.unwrap_or_default(), graph.render_size()).to_string();
+        match args.benchmark.as_ref().and_then(|benchmark_args| benchmark_args.output.as_ref()) {
+            Some(file) => std::fs::write(file, report).expect("Failed to write benchmark report"),
+            None => println!("{}", report),
+        }
+    }
+
     window.lock().unwrap().close();
 }
@@ -8,11 +8,12 @@ struct BVHTriangle {
     p1: usize,
     p2: usize,
     mat_idx: u32,
+    group: u32,
     centroid: [f32; 3],
 }
 
 impl BVHTriangle {
-    fn new(triangle: &Triangle, vertices: &Vec<Vector3<f32>>) -> Self {
+    fn new(triangle: &Triangle, group: u32, vertices: &Vec<Vector3<f32>>) -> Self {
         const THIRD: f32 = 1.0 / 3.0;
         let centroid = (
             vertices[triangle.p0 as usize] +
@@ -24,6 +25,7 @@ impl BVHTriangle {
             p1: triangle.p1 as usize,
             p2: triangle.p2 as usize,
             mat_idx: triangle.mat_idx,
+            group,
             centroid: [ centroid.x, centroid.y, centroid.z ]
         }
     }
@@ -60,7 +62,8 @@ pub struct BVHBuilder<'a> {
 impl<'a> BVHBuilder<'a> {
     pub fn new(model: &'a mut Model) -> Self {
         Self {
-            triangles: model.triangles().iter().map(|tri| BVHTriangle::new(tri, model.positions())).collect(),
+            triangles: model.triangles().iter().zip(model.triangle_groups())
+                .map(|(tri, group)| BVHTriangle::new(tri, *group, model.positions())).collect(),
             nodes: Vec::with_capacity(model.triangles().len() * 2),
             model,
         }
@@ -72,6 +75,7 @@ impl<'a> BVHBuilder<'a> {
         self.nodes.push(BVHNode::new_dummy());
         self.split_leaf_node_sah(0);
         self.model.set_triangles(self.triangles.iter().map(BVHTriangle::to_tri).collect());
+        self.model.set_triangle_groups(self.triangles.iter().map(|tri| tri.group).collect());
         self.model.set_indices(self.triangles.iter().flat_map(|tri| {
             [tri.p0 as u32, tri.p1 as u32, tri.p2 as u32].into_iter()
         }).collect());
@@ -39,6 +39,14 @@ impl AABB {
         let e = self.max - self.min;
         e.x * e.y + e.y * e.z + e.z * e.x
     }
+
+    pub fn center(&self) -> Vector3<f32> {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn extent(&self) -> Vector3<f32> {
+        self.max - self.min
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -135,6 +143,15 @@ impl BVHNode {
         &self.bounds
     }
 
+    pub fn is_leaf(&self) -> bool {
+        self.is_leaf != 0
+    }
+
+    // only valid for inner nodes
+    pub fn children(&self) -> (u32, u32) {
+        (self.a, self.b)
+    }
+
     pub fn first_triangle(&self) -> u32 {
         self.a
     }
@@ -152,30 +169,56 @@ pub struct IndexBundle {
 }
 
 impl IndexBundle {
-    pub fn new(input: &Vec<&str>) -> Result<Self, ResourceParseError> {
+    // the tex coord or normal index of a vertex that was given none
+    pub const MISSING: i32 = i32::MIN;
+
+    pub fn new(input: &str) -> Result<Self, ResourceParseError> {
+        let invalid = || ResourceParseError::InvalidLineArgCount { count: input.split('/').count(), line: input.to_owned() };
+        let mut parts = input.split('/');
         let mut ib = Self::new_default();
-        if input.len() > 0 && !input[0].is_empty() { ib.pos_idx = Self::parse(input[0])? }
-        if input.len() > 1 && !input[1].is_empty() { ib.tex_idx = Self::parse(input[1])? }
-        if input.len() > 2 && !input[2].is_empty() { ib.nor_idx = Self::parse(input[2])? }
+        ib.pos_idx = match parts.next() {
+            Some(pos) if !pos.is_empty() => Self::parse(pos)?,
+            _ => return Err(invalid()),
+        };
+        if let Some(tex) = parts.next().filter(|tex| !tex.is_empty()) { ib.tex_idx = Self::parse(tex)? }
+        if let Some(nor) = parts.next().filter(|nor| !nor.is_empty()) { ib.nor_idx = Self::parse(nor)? }
+        if parts.next().is_some() { return Err(invalid()) }
         Ok(ib)
     }
 
-    pub fn normalize(&mut self, pos_len: i32, tex_len: i32, nor_len: i32) {
-        if self.pos_idx < 0 { self.pos_idx += pos_len }
-        if self.tex_idx < 0 { self.tex_idx += tex_len }
-        if self.nor_idx < 0 { self.nor_idx += nor_len }
+    // resolves relative (negative) indices and checks the given indices against the element counts
+    // at that point in the file, so elements that are only defined later are out of range as well
+    pub fn normalize(&mut self, pos_len: i32, tex_len: i32, nor_len: i32) -> Result<(), ResourceParseError> {
+        self.pos_idx = Self::resolve(self.pos_idx, pos_len)?;
+        if self.tex_idx != Self::MISSING { self.tex_idx = Self::resolve(self.tex_idx, tex_len)? }
+        if self.nor_idx != Self::MISSING { self.nor_idx = Self::resolve(self.nor_idx, nor_len)? }
+        Ok(())
+    }
+
+    fn resolve(idx: i32, len: i32) -> Result<i32, ResourceParseError> {
+        let idx = if idx < 0 { idx + len } else { idx };
+        Self::check_range(idx, len)?;
+        Ok(idx)
+    }
+
+    pub fn check_range(idx: i32, len: i32) -> Result<(), ResourceParseError> {
+        if idx < 0 || idx >= len { Err(ResourceParseError::IndexOutOfRange { idx: idx as i64, len: len as usize }) }
+        else { Ok(()) }
     }
 
     pub fn new_default() -> Self {
         Self {
             pos_idx: 0,
-            tex_idx: 0,
-            nor_idx: 0,
+            tex_idx: Self::MISSING,
+            nor_idx: Self::MISSING,
         }
     }
 
     pub fn parse(str: &str) -> Result<i32, ResourceParseError> {
         let idx = str.trim().parse::<i32>().map_err(|err| ResourceParseError::ParseIntError { err, line: str.to_owned() })?;
+        if idx == 0 { return Err(ResourceParseError::ZeroIndex { line: str.to_owned() }) }
+        // the one relative index that would read as missing is out of range of any i32 count anyway
+        if idx == Self::MISSING { return Err(ResourceParseError::IndexOutOfRange { idx: idx as i64, len: 0 }) }
         Ok(if idx > 0 { idx - 1 } else { idx })
     }
 }
@@ -0,0 +1,103 @@
+use std::time::Instant;
+use crate::rendering::camera::Camera;
+use crate::rendering::camera_path::{CameraPathPlayer, PlaybackClock};
+use crate::rendering::gpu_profiler::GpuProfiler;
+use crate::util::args::BenchmarkArgs;
+use crate::util::json::Json;
+
+// min, average, 99th percentile and max of a series of times in milliseconds
+fn summary(times: &[f64]) -> Json {
+    if times.is_empty() { return Json::Null }
+    let mut sorted = times.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let p99 = sorted[((sorted.len() as f64 * 0.99).ceil() as usize).clamp(1, sorted.len()) - 1];
+    Json::object()
+        .with("min", sorted[0])
+        .with("avg", sorted.iter().sum::<f64>() / sorted.len() as f64)
+        .with("p99", p99)
+        .with("max", sorted[sorted.len() - 1])
+}
+
+// renders warm-up frames at the start of the camera path, then plays the path once at a fixed
+// clock and measures the frames until it ends
+pub struct Benchmark {
+    warmup_frames: u32,
+    frame: u32,
+    last_frame: Option<Instant>,
+    // set when the warm-up is over
+    measuring: bool,
+    frame_times: Vec<f64>,
+    // gpu times of the passes in the order they first ran
+    pass_times: Vec<(String, Vec<f64>)>,
+    gpu_totals: Vec<f64>,
+    next_gpu_frame: u64,
+}
+
+impl Benchmark {
+    // the warm-up frames are rendered from the start of the path
+    pub fn new(args: &BenchmarkArgs, camera_path: &mut CameraPathPlayer, camera: &mut Camera) -> Self {
+        camera_path.clock = PlaybackClock::Fixed(1.0 / args.fps as f32);
+        camera_path.looping = false;
+        if let Some(start) = camera_path.path().sample(0.0) { start.apply(camera) }
+        Self {
+            warmup_frames: args.warmup_frames,
+            frame: 0,
+            last_frame: None,
+            measuring: false,
+            frame_times: vec![],
+            pass_times: vec![],
+            gpu_totals: vec![],
+            next_gpu_frame: 0,
+        }
+    }
+
+    // called once per frame before it is rendered, returns false once the path has ended
+    pub fn update(&mut self, camera_path: &mut CameraPathPlayer, profiler: &GpuProfiler) -> bool {
+        let now = Instant::now();
+        if self.measuring {
+            if let Some(last) = self.last_frame { self.frame_times.push((now - last).as_secs_f64() * 1000.0) }
+            if !camera_path.is_playing() { return false }
+        } else if self.frame == self.warmup_frames {
+            camera_path.play();
+            self.measuring = true;
+            self.next_gpu_frame = profiler.next_frame();
+        }
+        self.last_frame = Some(now);
+        self.frame += 1;
+        self.collect_gpu_times(profiler);
+        true
+    }
+
+    // the gpu times arrive a few frames late, the history of the profiler holds them until then
+    fn collect_gpu_times(&mut self, profiler: &GpuProfiler) {
+        if !self.measuring { return }
+        let next_gpu_frame = self.next_gpu_frame;
+        for frame in profiler.history().filter(|frame| frame.frame >= next_gpu_frame) {
+            for (name, ms) in &frame.passes {
+                match self.pass_times.iter_mut().find(|(pass, _)| pass == name) {
+                    Some((_, times)) => times.push(*ms),
+                    None => self.pass_times.push((name.clone(), vec![*ms])),
+                }
+            }
+            self.gpu_totals.push(frame.total());
+            self.next_gpu_frame = frame.frame + 1;
+        }
+    }
+
+    // the resolution is the render size the rays are traced at
+    pub fn report(&mut self, profiler: &mut GpuProfiler, scene: &str, seed: u64, resolution: (u32, u32)) -> Json {
+        profiler.finish();
+        self.collect_gpu_times(profiler);
+        let passes = self.pass_times.iter()
+            .fold(Json::object(), |passes, (name, times)| passes.with(name, summary(times)));
+        Json::object()
+            .with("scene", scene)
+            .with("seed", seed as f64)
+            .with("resolution", vec![resolution.0, resolution.1])
+            .with("warmup_frames", self.warmup_frames)
+            .with("frames", self.frame_times.len())
+            .with("frame_ms", summary(&self.frame_times))
+            .with("gpu_ms", summary(&self.gpu_totals))
+            .with("passes", passes)
+    }
+}
@@ -0,0 +1,133 @@
+use std::fmt;
+use cgmath::Vector3;
+use crate::gl_wrapper::buffer::{IndexBuffer, VertexBuffer};
+use crate::gl_wrapper::geometry_set::{GeometrySet, GeometrySetBuilder};
+use crate::gl_wrapper::types::{AttributeType, Primitive};
+use crate::raytracing::bvh::BVH;
+use crate::raytracing::types::AABB;
+
+// cost of visiting an inner node relative to testing a triangle
+const TRAVERSAL_COST: f32 = 1.0;
+
+// corner pairs of the 12 edges of a box, corner i has the max coordinate on axis j if bit j is set
+const BOX_EDGES: [(u32, u32); 12] = [
+    (0, 1), (2, 3), (4, 5), (6, 7),
+    (0, 2), (1, 3), (4, 6), (5, 7),
+    (0, 4), (1, 5), (2, 6), (3, 7),
+];
+
+// the nodes drawn by the overlay
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BvhFilter {
+    // nodes at a depth of the tree, the root has depth 0
+    Level(u32),
+    // the nodes containing a triangle of the traced scene, from the root down to its leaf
+    Triangle(u32),
+}
+
+impl fmt::Display for BvhFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BvhFilter::Level(level) => write!(f, "level {}", level),
+            BvhFilter::Triangle(triangle) => write!(f, "nodes containing triangle {}", triangle),
+        }
+    }
+}
+
+struct OverlayNode {
+    depth: u32,
+    parent: Option<usize>,
+    cost: f32,
+}
+
+// the bounding boxes of bvh nodes as lines, colored by the nodes' share of the tree's sah cost, so
+// that boxes that are large for the number of triangles they hold stand out
+pub struct BvhOverlay {
+    geometry: GeometrySet,
+    _ibo: IndexBuffer,
+    _vbo: VertexBuffer,
+    node_count: usize,
+    max_depth: u32,
+}
+
+impl BvhOverlay {
+    pub fn new(bvh: &BVH, filter: BvhFilter) -> Self {
+        let nodes = Self::collect_nodes(bvh);
+        let max_depth = nodes.iter().flatten().map(|node| node.depth).max().unwrap_or(0);
+
+        let shown: Vec<usize> = match filter {
+            BvhFilter::Level(level) => (0..nodes.len())
+                .filter(|idx| nodes[*idx].as_ref().is_some_and(|node| node.depth == level))
+                .collect(),
+            BvhFilter::Triangle(triangle) => {
+                let leaf = (0..nodes.len()).find(|idx| {
+                    let node = &bvh.data()[*idx];
+                    nodes[*idx].is_some() && node.is_leaf()
+                        && (node.first_triangle()..node.first_triangle() + node.triangle_count()).contains(&triangle)
+                });
+                std::iter::successors(leaf, |idx| nodes[*idx].as_ref().unwrap().parent).collect()
+            }
+        };
+
+        // costs are normalized to the most expensive node shown
+        let max_cost = shown.iter().map(|idx| nodes[*idx].as_ref().unwrap().cost).fold(0.0, f32::max);
+        let mut vertices: Vec<[f32; 4]> = Vec::with_capacity(shown.len() * 8);
+        let mut indices: Vec<u32> = Vec::with_capacity(shown.len() * BOX_EDGES.len() * 2);
+        for idx in &shown {
+            let cost = if max_cost > 0.0 { nodes[*idx].as_ref().unwrap().cost / max_cost } else { 0.0 };
+            let first_vertex = vertices.len() as u32;
+            vertices.extend(Self::corners(bvh.data()[*idx].bounds()).map(|p| [p.x, p.y, p.z, cost]));
+            indices.extend(BOX_EDGES.iter().flat_map(|(a, b)| [first_vertex + a, first_vertex + b]));
+        }
+
+        let mut vbo = VertexBuffer::new();
+        let mut ibo = IndexBuffer::new();
+        vbo.buffer_data(&vertices);
+        ibo.buffer_data(&indices);
+        let geometry = GeometrySetBuilder::new()
+            .add_buffer(&vbo)
+            .add_attribute(3, AttributeType::Float)
+            .add_attribute(1, AttributeType::Float)
+            .build(&ibo, Primitive::Lines);
+
+        Self { geometry, _ibo: ibo, _vbo: vbo, node_count: shown.len(), max_depth }
+    }
+
+    pub fn draw(&self) {
+        if self.node_count > 0 { self.geometry.draw() }
+    }
+
+    pub fn node_count(&self) -> usize { self.node_count }
+    pub fn max_depth(&self) -> u32 { self.max_depth }
+
+    // depth, parent and sah cost of the nodes reachable from the root, the builder leaves unused
+    // nodes in the array to align child pairs
+    fn collect_nodes(bvh: &BVH) -> Vec<Option<OverlayNode>> {
+        let data = bvh.data();
+        let mut nodes: Vec<Option<OverlayNode>> = (0..data.len()).map(|_| None).collect();
+        if data.is_empty() { return nodes }
+
+        let root_area = data[0].bounds().area();
+        let mut stack = vec![(0usize, 0u32, None)];
+        while let Some((idx, depth, parent)) = stack.pop() {
+            let node = &data[idx];
+            let node_cost = if node.is_leaf() { node.triangle_count() as f32 } else { TRAVERSAL_COST };
+            let cost = if root_area > 0.0 { node.bounds().area() / root_area * node_cost } else { 0.0 };
+            nodes[idx] = Some(OverlayNode { depth, parent, cost });
+            if !node.is_leaf() {
+                let (left, right) = node.children();
+                stack.push((left as usize, depth + 1, Some(idx)));
+                stack.push((right as usize, depth + 1, Some(idx)));
+            }
+        }
+        nodes
+    }
+
+    fn corners(bounds: &AABB) -> [Vector3<f32>; 8] {
+        std::array::from_fn(|i| Vector3::new(
+            if i & 1 == 0 { bounds.min.x } else { bounds.max.x },
+            if i & 2 == 0 { bounds.min.y } else { bounds.max.y },
+            if i & 4 == 0 { bounds.min.z } else { bounds.max.z },
+        ))
+    }
+}
@@ -1,3 +1,4 @@
+use crate::raytracing::types::AABB;
 use crate::window::window::Window;
 use cgmath::{Angle, Deg, InnerSpace, Matrix4, perspective, Point3, Rad, Vector3};
 use std::ops::{Add, Div, Mul};
@@ -45,6 +46,24 @@ impl Camera {
         self.position = self.position.add(Vector3::new(x, y, z));
     }
 
+    // moves the camera back along its view direction until the bounding sphere of the box fits
+    // into the view frustum
+    pub fn frame(&mut self, bounds: &AABB) {
+        let aspect = self.window.lock().unwrap().aspect();
+        let half_fov_y = self.fov.0 / 2.0;
+        let half_fov_x = (half_fov_y.tan() * aspect).atan();
+        let radius = (bounds.extent().magnitude() / 2.0).max(NEAR);
+        let distance = radius / half_fov_x.min(half_fov_y).sin();
+        let center = bounds.center();
+        self.position = Point3::new(center.x, center.y, center.z) - self.direction.normalize() * distance;
+    }
+
+    // the angle a single pixel of an image of the height covers, the spread of the ray cones used
+    // for texture filtering
+    pub fn pixel_spread_angle(&self, height: u32) -> f32 {
+        (2.0 * (self.fov.0 / 2.0).tan() / height.max(1) as f32).atan()
+    }
+
     pub fn set_rotation(&mut self, yaw: f32, pitch: f32) {
         let pi = std::f32::consts::PI;
         let pitch = f32::max(f32::min(pitch, pi / 2.1), -pi / 2.1);
@@ -55,6 +74,19 @@ impl Camera {
         self.direction = Vector3::new(cy * cp, sp, -sy * cp).normalize();
     }
 
+    // yaw and pitch in radians as set_rotation takes them
+    pub fn rotation(&self) -> (f32, f32) {
+        let direction = self.direction.normalize();
+        ((-direction.z).atan2(direction.x), direction.y.clamp(-1.0, 1.0).asin())
+    }
+
+    pub fn position(&self) -> Point3<f32> { self.position }
+    pub fn set_position(&mut self, position: Point3<f32>) { self.position = position }
+
+    // vertical field of view
+    pub fn fov(&self) -> Rad<f32> { self.fov }
+    pub fn set_fov(&mut self, fov: Rad<f32>) { self.fov = fov }
+
     pub fn generate_view_vectors(&self) -> CameraViewVectors {
         let window = self.window.lock().unwrap();
         let sin_fov = self.fov.div(2.0).sin();
@@ -1,31 +1,73 @@
-use std::sync::{Arc, Mutex};
+use cgmath::{InnerSpace, Point3, Vector3};
 use crate::rendering::camera::Camera;
+use crate::rendering::render_settings::RenderSettings;
 use crate::window::window::Window;
 
-pub struct CameraController {
-    window: Arc<Mutex<Window>>,
-    movement_speed: f32,
-    mouse_sensitivity: f32,
+// radians per pixel of cursor movement at a mouse sensitivity of 1
+const LOOK_SCALE: f32 = 0.0003;
+// radians per second at full stick deflection and a mouse sensitivity of 1
+const GAMEPAD_LOOK_SCALE: f32 = 0.3;
+// change of the movement speed or orbit distance per scroll step
+const SCROLL_FACTOR: f32 = 1.1;
+// the pitch set_rotation clamps to
+const MAX_PITCH: f32 = std::f32::consts::PI / 2.1;
+
+// the view direction for a yaw and pitch as set_rotation computes it
+fn direction(yaw: f32, pitch: f32) -> Vector3<f32> {
+    Vector3::new(yaw.cos() * pitch.cos(), pitch.sin(), -yaw.sin() * pitch.cos())
 }
 
-impl CameraController {
-    pub fn new(window: Arc<Mutex<Window>>, movement_speed: f32, mouse_sensitivity: f32) -> Self {
-        Self {
-            window,
-            movement_speed,
-            mouse_sensitivity,
-        }
+// moves the camera from the window input every frame, the controllers read the camera state each
+// frame, so that they continue where another controller or a camera path left it
+pub trait CameraController {
+    fn name(&self) -> &'static str;
+
+    // pointer_free is false while the gui uses the mouse
+    fn control(&mut self, camera: &mut Camera, window: &mut Window, settings: &mut RenderSettings, pointer_free: bool);
+
+    // called when another controller takes over
+    fn deactivate(&mut self, window: &mut Window) {
+        window.set_cursor_captured(false);
     }
 
-    pub fn control(&self, camera: &mut Camera) {
-        let window = self.window.lock().unwrap();
-        let (cursor_x, cursor_y) = window.input().cursor_pos();
-        let (input_x, input_y, input_z) = window.input().movement();
+    // a window position to look up in the scene, the point there is passed to set_target
+    fn target_request(&mut self) -> Option<(f32, f32)> { None }
+    fn set_target(&mut self, _camera: &mut Camera, _target: Point3<f32>) {}
+}
+
+// first person controls, clicking into the window captures the cursor for mouse look and escape
+// releases it. the movement actions move, the look actions turn and scrolling changes the
+// movement speed
+#[derive(Default)]
+pub struct FlyController;
 
-        let move_factor = window.dt() * self.movement_speed;
-        let yaw = (1.0 - cursor_x / window.width() as f32 * 2.0) * self.mouse_sensitivity;
-        let pitch = (1.0 - cursor_y / window.height() as f32 * 2.0) * self.mouse_sensitivity;
+impl CameraController for FlyController {
+    fn name(&self) -> &'static str { "fly" }
+
+    fn control(&mut self, camera: &mut Camera, window: &mut Window, settings: &mut RenderSettings, pointer_free: bool) {
+        let captured = window.cursor_captured();
+        if !captured && pointer_free && window.input().action_down("capture_cursor") {
+            window.set_cursor_captured(true);
+        } else if captured && window.input().action_down("release_cursor") {
+            window.set_cursor_captured(false);
+        }
 
+        let input = window.input();
+        let (mut yaw, mut pitch) = camera.rotation();
+        if window.cursor_captured() {
+            let (dx, dy) = input.cursor_delta();
+            yaw -= dx * LOOK_SCALE * settings.mouse_sensitivity;
+            pitch = (pitch - dy * LOOK_SCALE * settings.mouse_sensitivity).clamp(-MAX_PITCH, MAX_PITCH);
+        }
+        let gamepad_look = window.dt() * GAMEPAD_LOOK_SCALE * settings.mouse_sensitivity;
+        yaw -= input.action_axis("look_right", "look_left") * gamepad_look;
+        pitch = (pitch + input.action_axis("look_up", "look_down") * gamepad_look).clamp(-MAX_PITCH, MAX_PITCH);
+        if pointer_free || window.cursor_captured() {
+            settings.movement_speed = (settings.movement_speed * SCROLL_FACTOR.powf(input.scroll().1)).clamp(0.1, 50.0);
+        }
+
+        let (input_x, input_y, input_z) = input.movement();
+        let move_factor = window.dt() * settings.movement_speed;
         camera.set_rotation(yaw, pitch);
         camera.add_position(
             (input_x * yaw.sin() + input_z * yaw.cos()) * move_factor,
@@ -34,3 +76,69 @@ impl CameraController {
         );
     }
 }
+
+// turntable controls around a target in front of the camera. dragging with the rotate action
+// rotates around it, with the pan action or the pan modifier held pans and scrolling zooms. the
+// pick action moves the target to the point under the cursor
+pub struct OrbitController {
+    distance: f32,
+    target_request: Option<(f32, f32)>,
+}
+
+impl Default for OrbitController {
+    fn default() -> Self { Self::new() }
+}
+
+impl OrbitController {
+    pub fn new() -> Self {
+        Self { distance: 10.0, target_request: None }
+    }
+}
+
+impl CameraController for OrbitController {
+    fn name(&self) -> &'static str { "orbit" }
+
+    fn control(&mut self, camera: &mut Camera, window: &mut Window, settings: &mut RenderSettings, pointer_free: bool) {
+        if !pointer_free { return }
+        let input = window.input();
+        let (mut yaw, mut pitch) = camera.rotation();
+        let mut target = camera.position() + direction(yaw, pitch) * self.distance;
+        let (dx, dy) = input.cursor_delta();
+        let look_scale = LOOK_SCALE * settings.mouse_sensitivity;
+
+        let pan = input.action_pressed("orbit_pan")
+            || (input.action_pressed("orbit_rotate") && input.action_pressed("orbit_pan_modifier"));
+        if pan {
+            let forward = direction(yaw, pitch);
+            let right = forward.cross(Vector3::unit_y()).normalize();
+            let up = right.cross(forward);
+            // the point under the cursor follows it at the distance of the target
+            let scale = look_scale * self.distance;
+            target += (up * dy - right * dx) * scale;
+        } else if input.action_pressed("orbit_rotate") {
+            yaw -= dx * look_scale;
+            pitch = (pitch - dy * look_scale).clamp(-MAX_PITCH, MAX_PITCH);
+        }
+        let gamepad_look = window.dt() * GAMEPAD_LOOK_SCALE * settings.mouse_sensitivity;
+        yaw -= input.action_axis("look_right", "look_left") * gamepad_look;
+        pitch = (pitch + input.action_axis("look_up", "look_down") * gamepad_look).clamp(-MAX_PITCH, MAX_PITCH);
+        self.distance = (self.distance * SCROLL_FACTOR.powf(-input.scroll().1)).max(0.01);
+        if input.action_down("pick_target") { self.target_request = Some(input.cursor_pos()) }
+
+        camera.set_rotation(yaw, pitch);
+        camera.set_position(target - direction(yaw, pitch) * self.distance);
+    }
+
+    fn target_request(&mut self) -> Option<(f32, f32)> {
+        self.target_request.take()
+    }
+
+    // turns the camera towards the target without moving it
+    fn set_target(&mut self, camera: &mut Camera, target: Point3<f32>) {
+        let offset = target - camera.position();
+        if offset.magnitude() < 1e-4 { return }
+        let forward = offset.normalize();
+        self.distance = offset.magnitude();
+        camera.set_rotation((-forward.z).atan2(forward.x), forward.y.clamp(-1.0, 1.0).asin());
+    }
+}
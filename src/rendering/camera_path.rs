@@ -0,0 +1,237 @@
+use std::f32::consts::PI;
+use std::fmt;
+use std::path::Path;
+use cgmath::{Deg, InnerSpace, Point3, Rad};
+use crate::raytracing::types::AABB;
+use crate::rendering::camera::Camera;
+use crate::util::error::CameraPathError;
+use crate::util::json::Json;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraKeyframe {
+    // seconds from the start of the path
+    pub time: f32,
+    pub position: Point3<f32>,
+    pub yaw: f32,
+    pub pitch: f32,
+    // vertical field of view in degrees
+    pub fov: f32,
+}
+
+impl CameraKeyframe {
+    pub fn from_camera(camera: &Camera, time: f32) -> Self {
+        let (yaw, pitch) = camera.rotation();
+        Self { time, position: camera.position(), yaw, pitch, fov: Deg::from(camera.fov()).0 }
+    }
+
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.set_position(self.position);
+        camera.set_rotation(self.yaw, self.pitch);
+        camera.set_fov(Rad::from(Deg(self.fov)));
+    }
+
+    fn to_array(self) -> [f32; 6] {
+        [self.position.x, self.position.y, self.position.z, self.yaw, self.pitch, self.fov]
+    }
+
+    fn from_array(time: f32, [x, y, z, yaw, pitch, fov]: [f32; 6]) -> Self {
+        Self { time, position: Point3::new(x, y, z), yaw, pitch, fov }
+    }
+
+    fn to_json(self) -> Json {
+        Json::object()
+            .with("time", self.time)
+            .with("position", vec![self.position.x, self.position.y, self.position.z])
+            .with("yaw", self.yaw)
+            .with("pitch", self.pitch)
+            .with("fov", self.fov)
+    }
+
+    fn from_json(json: &Json) -> Option<Self> {
+        let position = json.get("position")?.as_f32_array().filter(|p| p.len() == 3)?;
+        Some(Self {
+            time: json.get("time")?.as_f32()?,
+            position: Point3::new(position[0], position[1], position[2]),
+            yaw: json.get("yaw")?.as_f32()?,
+            pitch: json.get("pitch")?.as_f32()?,
+            fov: json.get("fov")?.as_f32()?,
+        })
+    }
+}
+
+// catmull-rom spline through p1 and p2 at t in [0, 1], with the tangents taken from the neighbours
+fn catmull_rom(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2.0 * p1 + (p2 - p0) * t + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2 + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+// keyframes of a camera flight, sorted by time
+#[derive(Debug, Clone, Default)]
+pub struct CameraPath {
+    keyframes: Vec<CameraKeyframe>,
+}
+
+impl CameraPath {
+    pub fn new() -> Self {
+        Self { keyframes: vec![] }
+    }
+
+    // keyframes are added at the end, the yaw is unwrapped so that the camera turns the short way
+    pub fn add_keyframe(&mut self, mut keyframe: CameraKeyframe) {
+        if let Some(last) = self.keyframes.last() {
+            keyframe.time = keyframe.time.max(last.time);
+            keyframe.yaw = last.yaw + (keyframe.yaw - last.yaw + PI).rem_euclid(2.0 * PI) - PI;
+        }
+        self.keyframes.push(keyframe);
+    }
+
+    // a circle around the box at the height of its top, looking at its center
+    pub fn orbit(bounds: &AABB, fov: Deg<f32>, duration: f32, keyframe_count: u32) -> Self {
+        let center = bounds.center();
+        let extent = bounds.extent();
+        let radius = extent.x.max(extent.z).max(extent.y);
+        let mut path = Self::new();
+        for i in 0..=keyframe_count {
+            let t = i as f32 / keyframe_count as f32;
+            let angle = t * 2.0 * PI;
+            let position = Point3::new(center.x + angle.cos() * radius, bounds.max.y, center.z + angle.sin() * radius);
+            let direction = (Point3::new(center.x, center.y, center.z) - position).normalize();
+            path.add_keyframe(CameraKeyframe {
+                time: t * duration,
+                position,
+                yaw: (-direction.z).atan2(direction.x),
+                pitch: direction.y.asin(),
+                fov: fov.0,
+            });
+        }
+        path
+    }
+
+    pub fn clear(&mut self) { self.keyframes.clear() }
+
+    pub fn keyframes(&self) -> &[CameraKeyframe] { &self.keyframes }
+
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    // the interpolated keyframe at a time, clamped to the start and end of the path
+    pub fn sample(&self, time: f32) -> Option<CameraKeyframe> {
+        let keyframes = &self.keyframes;
+        let first = keyframes.first()?;
+        if keyframes.len() == 1 || time <= first.time { return Some(CameraKeyframe { time, ..*first }) }
+        // the segment from keyframe i to i + 1 containing the time
+        let i = keyframes.partition_point(|keyframe| keyframe.time <= time).clamp(1, keyframes.len() - 1) - 1;
+        let (k1, k2) = (keyframes[i], keyframes[i + 1]);
+        // the end points are repeated for the tangents of the first and last segment
+        let k0 = keyframes[i.saturating_sub(1)];
+        let k3 = keyframes[(i + 2).min(keyframes.len() - 1)];
+        let span = k2.time - k1.time;
+        let t = if span > 0.0 { ((time - k1.time) / span).clamp(0.0, 1.0) } else { 1.0 };
+
+        let (p0, p1, p2, p3) = (k0.to_array(), k1.to_array(), k2.to_array(), k3.to_array());
+        Some(CameraKeyframe::from_array(time, std::array::from_fn(|c| catmull_rom(p0[c], p1[c], p2[c], p3[c], t))))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CameraPathError> {
+        let json = Json::object().with("keyframes", Json::Array(self.keyframes.iter().map(|keyframe| keyframe.to_json()).collect()));
+        std::fs::write(path, json.to_string()).map_err(|e| CameraPathError::Io { e })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CameraPathError> {
+        let str = std::fs::read_to_string(path).map_err(|e| CameraPathError::Io { e })?;
+        let json = Json::parse(&str).map_err(CameraPathError::JsonError)?;
+        let keyframes = json.get("keyframes").and_then(Json::as_array).ok_or(CameraPathError::InvalidKeyframe(0))?
+            .iter().enumerate()
+            .map(|(i, keyframe)| CameraKeyframe::from_json(keyframe).ok_or(CameraPathError::InvalidKeyframe(i)))
+            .collect::<Result<Vec<_>, _>>()?;
+        if keyframes.windows(2).any(|pair| pair[1].time < pair[0].time) { return Err(CameraPathError::UnsortedKeyframes) }
+        Ok(Self { keyframes })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PlaybackClock {
+    // follows the frame time of the window
+    RealTime,
+    // advances by the same step every frame, independent of how long frames take
+    Fixed(f32),
+}
+
+impl fmt::Display for PlaybackClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlaybackClock::RealTime => f.write_str("real time"),
+            PlaybackClock::Fixed(step) => write!(f, "fixed {:.1} fps", 1.0 / step),
+        }
+    }
+}
+
+// records keyframes from the camera in real time and flies the camera along the path
+pub struct CameraPathPlayer {
+    path: CameraPath,
+    // window time of the first keyframe while recording
+    record_start: Option<f64>,
+    // time on the path while playing
+    playback: Option<f32>,
+    pub clock: PlaybackClock,
+    pub looping: bool,
+}
+
+impl Default for CameraPathPlayer {
+    fn default() -> Self { Self::new() }
+}
+
+impl CameraPathPlayer {
+    pub fn new() -> Self {
+        Self { path: CameraPath::new(), record_start: None, playback: None, clock: PlaybackClock::RealTime, looping: false }
+    }
+
+    pub fn path(&self) -> &CameraPath { &self.path }
+
+    // keyframes are timed by when they are added, the first one starts the path
+    pub fn add_keyframe(&mut self, camera: &Camera, window_time: f64) {
+        if self.path.keyframes().is_empty() { self.record_start = Some(window_time) }
+        let time = (window_time - self.record_start.unwrap_or(window_time)) as f32;
+        self.path.add_keyframe(CameraKeyframe::from_camera(camera, time));
+    }
+
+    pub fn clear(&mut self) {
+        self.stop();
+        self.path.clear();
+        self.record_start = None;
+    }
+
+    pub fn set_path(&mut self, path: CameraPath) {
+        self.stop();
+        self.path = path;
+        self.record_start = None;
+    }
+
+    pub fn play(&mut self) {
+        if !self.path.keyframes().is_empty() { self.playback = Some(0.0) }
+    }
+
+    pub fn stop(&mut self) { self.playback = None }
+
+    pub fn is_playing(&self) -> bool { self.playback.is_some() }
+
+    pub fn playback_time(&self) -> Option<f32> { self.playback }
+
+    // moves the camera to the current time on the path and advances the clock, playback stops at
+    // the end of the path unless it loops
+    pub fn update(&mut self, camera: &mut Camera, dt: f32) {
+        let Some(time) = self.playback else { return };
+        if let Some(keyframe) = self.path.sample(time) { keyframe.apply(camera) }
+
+        let next = time + match self.clock {
+            PlaybackClock::RealTime => dt,
+            PlaybackClock::Fixed(step) => step,
+        };
+        let duration = self.path.duration();
+        self.playback = if next <= duration { Some(next) }
+            else if self.looping && duration > 0.0 { Some(next % duration) }
+            else { None };
+    }
+}
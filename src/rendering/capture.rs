@@ -0,0 +1,168 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use image::{DynamicImage, ImageBuffer};
+use crate::gl_wrapper::framebuffer::Framebuffer;
+use crate::rendering::render_graph::RenderGraph;
+use crate::util::error::CaptureError;
+
+// the texture the shade pass writes the lit image to
+pub const COLOR_TEXTURE: &str = "color";
+const SCREENSHOT_DIR: &str = "screenshots";
+const RECORDING_DIR: &str = "recordings";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureFormat {
+    Png,
+    Png16,
+    // linear floats, not clamped to 1
+    Exr,
+}
+
+impl CaptureFormat {
+    pub const ALL: [CaptureFormat; 3] = [CaptureFormat::Png, CaptureFormat::Png16, CaptureFormat::Exr];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            CaptureFormat::Png | CaptureFormat::Png16 => "png",
+            CaptureFormat::Exr => "exr",
+        }
+    }
+}
+
+impl fmt::Display for CaptureFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CaptureFormat::Png => "PNG",
+            CaptureFormat::Png16 => "16-bit PNG",
+            CaptureFormat::Exr => "EXR",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureSource {
+    // what is on screen, including the gui and the debug views
+    Window,
+    // the lit image before it is displayed
+    Color,
+}
+
+impl CaptureSource {
+    pub const ALL: [CaptureSource; 2] = [CaptureSource::Window, CaptureSource::Color];
+}
+
+impl fmt::Display for CaptureSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CaptureSource::Window => "window",
+            CaptureSource::Color => "color texture",
+        })
+    }
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    let c = c.clamp(0.0, 1.0);
+    if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+}
+
+// seconds and milliseconds, so that captures sort by the time they were taken
+fn timestamp() -> String {
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    format!("{}_{:03}", time.as_secs(), time.subsec_millis())
+}
+
+// a frame read back from the gpu as rgb, rows from the top down
+pub struct CapturedFrame {
+    width: u32,
+    height: u32,
+    pixels: Vec<f32>,
+    // window pixels are srgb encoded, the color texture is linear
+    srgb: bool,
+}
+
+impl CapturedFrame {
+    // has to be called after the frame was rendered and before the buffers are swapped
+    pub fn capture(source: CaptureSource, graph: &RenderGraph, window_size: (u32, u32)) -> Result<Self, CaptureError> {
+        let (width, height, rgba, srgb) = match source {
+            CaptureSource::Window => {
+                let (width, height) = window_size;
+                (width, height, Framebuffer::read_default_pixels(width, height), true)
+            }
+            CaptureSource::Color => {
+                let (width, height, rgba) = graph.read_texture(COLOR_TEXTURE)
+                    .ok_or_else(|| CaptureError::UnknownTexture(COLOR_TEXTURE.to_owned()))?;
+                (width, height, rgba, false)
+            }
+        };
+        if width == 0 || height == 0 { return Err(CaptureError::EmptyFrame) }
+        // gl rows start at the bottom, alpha is dropped
+        let pixels = rgba.chunks_exact(width as usize * 4).rev()
+            .flat_map(|row| row.chunks_exact(4).flat_map(|pixel| [pixel[0], pixel[1], pixel[2]]))
+            .collect();
+        Ok(Self { width, height, pixels, srgb })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>, format: CaptureFormat) -> Result<(), CaptureError> {
+        let encoded = |c: f32| if self.srgb { c.clamp(0.0, 1.0) } else { linear_to_srgb(c) };
+        let image = match format {
+            CaptureFormat::Png => DynamicImage::ImageRgb8(ImageBuffer::from_raw(
+                self.width, self.height,
+                self.pixels.iter().map(|c| (encoded(*c) * 255.0).round() as u8).collect(),
+            ).unwrap()),
+            CaptureFormat::Png16 => DynamicImage::ImageRgb16(ImageBuffer::from_raw(
+                self.width, self.height,
+                self.pixels.iter().map(|c| (encoded(*c) * 65535.0).round() as u16).collect(),
+            ).unwrap()),
+            CaptureFormat::Exr => DynamicImage::ImageRgb32F(ImageBuffer::from_raw(
+                self.width, self.height,
+                self.pixels.iter().map(|c| if self.srgb { srgb_to_linear(*c) } else { *c }).collect(),
+            ).unwrap()),
+        };
+        image.save(path).map_err(|e| CaptureError::ImageError { e })
+    }
+}
+
+// saves the current frame to the screenshot directory and returns the file it was written to
+pub fn screenshot(source: CaptureSource, format: CaptureFormat, graph: &RenderGraph, window_size: (u32, u32)) -> Result<PathBuf, CaptureError> {
+    std::fs::create_dir_all(SCREENSHOT_DIR).map_err(|e| CaptureError::Io { e })?;
+    let path = Path::new(SCREENSHOT_DIR).join(format!("screenshot_{}.{}", timestamp(), format.extension()));
+    CapturedFrame::capture(source, graph, window_size)?.save(&path, format)?;
+    Ok(path)
+}
+
+// writes every frame as a numbered image to a new directory, for making videos. the window should
+// run at a fixed timestep while recording, so that the frames are evenly spaced in time
+pub struct Recorder {
+    dir: PathBuf,
+    source: CaptureSource,
+    format: CaptureFormat,
+    frame_count: u32,
+}
+
+impl Recorder {
+    pub fn new(source: CaptureSource, format: CaptureFormat) -> Result<Self, CaptureError> {
+        let dir = Path::new(RECORDING_DIR).join(timestamp());
+        std::fs::create_dir_all(&dir).map_err(|e| CaptureError::Io { e })?;
+        Ok(Self { dir, source, format, frame_count: 0 })
+    }
+
+    // frames rendered while the window is minimized are left out
+    pub fn record(&mut self, graph: &RenderGraph, window_size: (u32, u32)) -> Result<(), CaptureError> {
+        let frame = match CapturedFrame::capture(self.source, graph, window_size) {
+            Err(CaptureError::EmptyFrame) => return Ok(()),
+            frame => frame?,
+        };
+        let path = self.dir.join(format!("frame_{:05}.{}", self.frame_count, self.format.extension()));
+        frame.save(path, self.format)?;
+        self.frame_count += 1;
+        Ok(())
+    }
+
+    pub fn dir(&self) -> &Path { &self.dir }
+    pub fn frame_count(&self) -> u32 { self.frame_count }
+}
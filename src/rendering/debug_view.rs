@@ -0,0 +1,148 @@
+use std::fmt;
+use cgmath::{Array, Vector3, Zero};
+use crate::gl_wrapper::shader::ShaderProgram;
+use crate::rendering::render_graph::RenderGraph;
+use crate::rendering::scene::Scene;
+
+// the render graph pass tracing camera rays for the triangle id and traversal cost views
+pub const DEBUG_TRAVERSAL_PASS: &str = "debugTraversal";
+
+// nodes and triangles tested by a camera ray that are shown as the hottest color
+const HEATMAP_MAX: f32 = 200.0;
+
+// remapping of the displayed texture, must match the REMAP_* defines in display.frag
+const REMAP_COLOR: i32 = 0;
+const REMAP_POSITION: i32 = 1;
+const REMAP_DIRECTION: i32 = 2;
+const REMAP_TEX_COORD: i32 = 3;
+const REMAP_MATERIAL_ID: i32 = 4;
+const REMAP_DISTANCE: i32 = 5;
+const REMAP_SHADOW_MASK: i32 = 6;
+const REMAP_TRIANGLE_ID: i32 = 7;
+const REMAP_HEATMAP: i32 = 8;
+
+// the texture shown by the display pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugView {
+    Color,
+    Position,
+    Normal,
+    TexCoord,
+    MaterialId,
+    ViewDir,
+    ShadowDir,
+    ReflectDir,
+    AmbientDir,
+    ShadowDistance,
+    ReflectDistance,
+    AmbientDistance,
+    ShadowMask,
+    TriangleId,
+    TraversalCost,
+}
+
+impl DebugView {
+    pub const ALL: [DebugView; 15] = [
+        DebugView::Color,
+        DebugView::Position,
+        DebugView::Normal,
+        DebugView::TexCoord,
+        DebugView::MaterialId,
+        DebugView::ViewDir,
+        DebugView::ShadowDir,
+        DebugView::ReflectDir,
+        DebugView::AmbientDir,
+        DebugView::ShadowDistance,
+        DebugView::ReflectDistance,
+        DebugView::AmbientDistance,
+        DebugView::ShadowMask,
+        DebugView::TriangleId,
+        DebugView::TraversalCost,
+    ];
+
+    pub fn next(self) -> Self {
+        let idx = Self::ALL.iter().position(|view| *view == self).unwrap();
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+
+    pub fn previous(self) -> Self {
+        let idx = Self::ALL.iter().position(|view| *view == self).unwrap();
+        Self::ALL[(idx + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+
+    // name of the render graph texture
+    pub fn texture(self) -> &'static str {
+        match self {
+            DebugView::Color => "color",
+            DebugView::Position => "position",
+            DebugView::Normal | DebugView::MaterialId => "normalMat",
+            DebugView::TexCoord => "texCoord",
+            DebugView::ViewDir => "viewDir",
+            DebugView::ShadowDir => "shadowDir",
+            DebugView::ReflectDir => "reflectDir",
+            DebugView::AmbientDir => "ambientDir",
+            DebugView::ShadowDistance | DebugView::ShadowMask => "shadowHits",
+            DebugView::ReflectDistance => "reflectHits",
+            DebugView::AmbientDistance => "ambientHits",
+            DebugView::TriangleId => "primaryHits",
+            DebugView::TraversalCost => "traversalCost",
+        }
+    }
+
+    fn remap(self) -> i32 {
+        match self {
+            DebugView::Color => REMAP_COLOR,
+            DebugView::Position => REMAP_POSITION,
+            DebugView::Normal | DebugView::ViewDir | DebugView::ShadowDir | DebugView::ReflectDir
+                | DebugView::AmbientDir => REMAP_DIRECTION,
+            DebugView::TexCoord => REMAP_TEX_COORD,
+            DebugView::MaterialId => REMAP_MATERIAL_ID,
+            DebugView::ShadowDistance | DebugView::ReflectDistance | DebugView::AmbientDistance => REMAP_DISTANCE,
+            DebugView::ShadowMask => REMAP_SHADOW_MASK,
+            DebugView::TriangleId => REMAP_TRIANGLE_ID,
+            DebugView::TraversalCost => REMAP_HEATMAP,
+        }
+    }
+
+    // whether the view needs the camera rays traced by the debug traversal pass
+    pub fn traces_camera_rays(self) -> bool {
+        matches!(self, DebugView::TriangleId | DebugView::TraversalCost)
+    }
+
+    // binds the texture of the view and sets the uniforms of the display program
+    pub fn set_uniforms(self, program: &mut ShaderProgram, graph: &RenderGraph, scene: &Scene, slot: u32) {
+        if let Some(slot) = graph.bind_texture(self.texture(), slot) {
+            program.set_uniform_texture("display", slot);
+        }
+        // positions of an empty scene are never shown, a unit box keeps the remapping finite
+        let (scene_min, scene_extent) = scene.bounds()
+            .map_or((Vector3::zero(), Vector3::from_value(1.0)), |bounds| (bounds.min, bounds.extent()));
+        program.set_uniform_1i("remap", self.remap());
+        program.set_uniform_3f("sceneMin", scene_min);
+        program.set_uniform_3f("sceneExtent", scene_extent);
+        program.set_uniform_1f("heatmapMax", HEATMAP_MAX);
+    }
+}
+
+impl fmt::Display for DebugView {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DebugView::Color => "color",
+            DebugView::Position => "position",
+            DebugView::Normal => "normal",
+            DebugView::TexCoord => "texture coordinates",
+            DebugView::MaterialId => "material id",
+            DebugView::ViewDir => "view direction",
+            DebugView::ShadowDir => "shadow ray direction",
+            DebugView::ReflectDir => "reflection ray direction",
+            DebugView::AmbientDir => "ambient ray direction",
+            DebugView::ShadowDistance => "shadow hit distance",
+            DebugView::ReflectDistance => "reflection hit distance",
+            DebugView::AmbientDistance => "ambient hit distance",
+            DebugView::ShadowMask => "shadow mask",
+            DebugView::TriangleId => "triangle id",
+            DebugView::TraversalCost => "bvh traversal cost",
+        };
+        write!(f, "{}", name)
+    }
+}
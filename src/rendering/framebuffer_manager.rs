@@ -5,20 +5,32 @@ use crate::gl_wrapper::texture::Texture;
 use crate::gl_wrapper::types::{TextureAttachment, TextureFilter, TextureFormat};
 use crate::window::window::Window;
 
+// the buffers are allocated at the render size, the window size times the resolution scale
 pub struct FramebufferManager {
     window: Arc<Mutex<Window>>,
     framebuffers: Vec<Framebuffer>,
     textures: Vec<Texture>,
     renderbuffers: Vec<Renderbuffer>,
+    resolution_scale: f32,
+    window_size: (u32, u32),
+    render_size: (u32, u32),
+}
+
+fn scaled_size((width, height): (u32, u32), scale: f32) -> (u32, u32) {
+    (((width as f32 * scale).round() as u32).max(1), ((height as f32 * scale).round() as u32).max(1))
 }
 
 impl FramebufferManager {
     pub fn new(window: Arc<Mutex<Window>>) -> Self {
+        let window_size = { let window = window.lock().unwrap(); (window.width(), window.height()) };
         Self {
             window,
             framebuffers: vec![],
             textures: vec![],
             renderbuffers: vec![],
+            resolution_scale: 1.0,
+            window_size,
+            render_size: window_size,
         }
     }
 
@@ -28,17 +40,17 @@ impl FramebufferManager {
     }
 
     pub fn attach_texture(&mut self, format: TextureFormat, attachment: TextureAttachment, attach: bool) -> usize {
-        let window = self.window.lock().unwrap();
+        let (width, height) = self.render_size;
         self.framebuffers.last_mut().unwrap().bind();
-        let texture = Texture::new(window.width(), window.height(), format, TextureFilter::Nearest);
+        let texture = Texture::new(width, height, format, TextureFilter::Nearest);
         self.framebuffers.last_mut().unwrap().attach_texture(&texture, attachment, attach);
         self.textures.push(texture);
         self.textures.len() - 1
     }
 
     pub fn attach_renderbuffer(&mut self, format: TextureFormat, attachment: TextureAttachment, attach: bool) -> usize {
-        let window = self.window.lock().unwrap();
-        let rbo = Renderbuffer::new(window.width(), window.height(), format);
+        let (width, height) = self.render_size;
+        let rbo = Renderbuffer::new(width, height, format);
         self.framebuffers.last_mut().unwrap().attach_renderbuffer(&rbo, attachment, attach);
         self.renderbuffers.push(rbo);
         self.renderbuffers.len() - 1
@@ -53,15 +65,51 @@ impl FramebufferManager {
 
     pub fn update_buffers(&mut self) {
         let window = self.window.lock().unwrap();
-        self.textures.iter_mut().for_each(|tex| tex.resize(window.width(), window.height()));
-        self.renderbuffers.iter_mut().for_each(|rbo| rbo.resize(window.width(), window.height()));
+        self.window_size = (window.width(), window.height());
+        self.render_size = scaled_size(self.window_size, self.resolution_scale);
+        let (width, height) = self.render_size;
+        self.textures.iter_mut().for_each(|tex| tex.resize(width, height));
+        self.renderbuffers.iter_mut().for_each(|rbo| rbo.resize(width, height));
+    }
+
+    pub fn resolution_scale(&self) -> f32 {
+        self.resolution_scale
+    }
+
+    pub fn set_resolution_scale(&mut self, scale: f32) {
+        self.resolution_scale = scale;
+        self.update_buffers();
+    }
+
+    pub fn window_size(&self) -> (u32, u32) {
+        self.window_size
+    }
+
+    pub fn render_size(&self) -> (u32, u32) {
+        self.render_size
     }
 
+    // the viewport is set to the size of the framebuffer
     pub fn bind_fbo(&self, handle: usize) {
         self.framebuffers[handle].bind();
+        Framebuffer::set_viewport(self.render_size.0, self.render_size.1);
+    }
+
+    pub fn bind_default(&self) {
+        Framebuffer::bind_default();
+        Framebuffer::set_viewport(self.window_size.0, self.window_size.1);
     }
 
     pub fn bind_tex_to_slot(&self, handle: usize, slot: u32) -> u32 {
         self.textures[handle].bind_to_slot(slot)
     }
+
+    pub fn read_tex_pixel(&self, handle: usize, x: u32, y: u32) -> [f32; 4] {
+        self.textures[handle].read_pixel(x, y)
+    }
+
+    pub fn read_tex_pixels(&self, handle: usize) -> (u32, u32, Vec<f32>) {
+        let texture = &self.textures[handle];
+        (texture.width(), texture.height(), texture.read_pixels())
+    }
 }
\ No newline at end of file
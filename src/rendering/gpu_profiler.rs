@@ -0,0 +1,196 @@
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::time::Instant;
+use crate::gl_wrapper::query::TimerQuery;
+use crate::util::json::Json;
+
+// frames whose timings are averaged
+const AVERAGE_FRAMES: usize = 100;
+// frames kept for writing them to a file
+const HISTORY_FRAMES: usize = 1000;
+
+struct PendingFrame {
+    frame: u64,
+    // microseconds since the profiler was created
+    start: f64,
+    passes: Vec<(String, TimerQuery)>,
+}
+
+// the gpu time of the passes of a frame in milliseconds, in the order they ran
+pub struct FrameTimings {
+    pub frame: u64,
+    pub start: f64,
+    pub passes: Vec<(String, f64)>,
+}
+
+impl FrameTimings {
+    pub fn total(&self) -> f64 {
+        self.passes.iter().map(|(_, ms)| ms).sum()
+    }
+}
+
+pub struct PassTiming {
+    pub name: String,
+    pub average_ms: f64,
+    pub max_ms: f64,
+}
+
+// times the passes of the render graph with timer queries. results are read once the gpu has
+// finished a frame, so they lag a few frames behind without stalling the pipeline
+pub struct GpuProfiler {
+    epoch: Instant,
+    frame: u64,
+    pending: VecDeque<PendingFrame>,
+    // queries of collected frames for reuse
+    free_queries: Vec<TimerQuery>,
+    history: VecDeque<FrameTimings>,
+    active: bool,
+}
+
+impl Default for GpuProfiler {
+    fn default() -> Self { Self::new() }
+}
+
+impl GpuProfiler {
+    pub fn new() -> Self {
+        Self {
+            epoch: Instant::now(),
+            frame: 0,
+            pending: VecDeque::new(),
+            free_queries: vec![],
+            history: VecDeque::with_capacity(HISTORY_FRAMES),
+            active: false,
+        }
+    }
+
+    // collects the finished frames and starts timing a new one
+    pub fn begin_frame(&mut self) {
+        self.collect();
+        self.pending.push_back(PendingFrame {
+            frame: self.frame,
+            start: self.epoch.elapsed().as_secs_f64() * 1e6,
+            passes: vec![],
+        });
+        self.frame += 1;
+    }
+
+    // timers can't be nested, a pass has to end before the next begins
+    pub fn begin(&mut self, name: &str) {
+        let Some(frame) = self.pending.back_mut() else { return };
+        let query = self.free_queries.pop().unwrap_or_default();
+        query.begin();
+        frame.passes.push((name.to_owned(), query));
+        self.active = true;
+    }
+
+    pub fn end(&mut self) {
+        if self.active {
+            if let Some((_, query)) = self.pending.back().and_then(|frame| frame.passes.last()) { query.end() }
+            self.active = false;
+        }
+    }
+
+    // waits for the gpu to finish all frames and collects them
+    pub fn finish(&mut self) {
+        unsafe { gl::Finish() }
+        self.collect();
+    }
+
+    // the number the next frame will have
+    pub fn next_frame(&self) -> u64 { self.frame }
+
+    fn collect(&mut self) {
+        // the gpu finishes frames in order, the oldest one is checked first
+        while self.pending.front().is_some_and(|frame| frame.passes.iter().all(|(_, query)| query.available())) {
+            let frame = self.pending.pop_front().unwrap();
+            let mut passes = Vec::with_capacity(frame.passes.len());
+            for (name, query) in frame.passes {
+                passes.push((name, query.elapsed_ns() as f64 / 1e6));
+                self.free_queries.push(query);
+            }
+            if self.history.len() == HISTORY_FRAMES { self.history.pop_front(); }
+            self.history.push_back(FrameTimings { frame: frame.frame, start: frame.start, passes });
+        }
+    }
+
+    pub fn history(&self) -> impl Iterator<Item = &FrameTimings> {
+        self.history.iter()
+    }
+
+    // average and max time of the passes over the last frames, in the order of the latest frame.
+    // passes that were disabled in some of the frames are averaged over the frames they ran in
+    pub fn averages(&self) -> Vec<PassTiming> {
+        let Some(latest) = self.history.back() else { return vec![] };
+        let recent: Vec<_> = self.history.iter().rev().take(AVERAGE_FRAMES).collect();
+        latest.passes.iter().map(|(name, _)| {
+            let times: Vec<f64> = recent.iter()
+                .filter_map(|frame| frame.passes.iter().find(|(pass, _)| pass == name).map(|(_, ms)| *ms))
+                .collect();
+            PassTiming {
+                name: name.clone(),
+                average_ms: times.iter().sum::<f64>() / times.len() as f64,
+                max_ms: times.iter().copied().fold(0.0, f64::max),
+            }
+        }).collect()
+    }
+
+    pub fn average_total(&self) -> f64 {
+        self.averages().iter().map(|timing| timing.average_ms).sum()
+    }
+
+    pub fn log(&self) {
+        let averages = self.averages();
+        let width = averages.iter().map(|timing| timing.name.len()).max().unwrap_or(0);
+        println!("GPU times over the last {} frames:", self.history.len().min(AVERAGE_FRAMES));
+        for timing in &averages {
+            println!("  {:width$}  {:7.3} ms avg  {:7.3} ms max", timing.name, timing.average_ms, timing.max_ms, width = width);
+        }
+        println!("  {:width$}  {:7.3} ms avg", "total", self.average_total(), width = width);
+    }
+
+    // writes the history as <name>.csv and <name>.json
+    pub fn dump(&self, name: &str) {
+        let csv = format!("{}.csv", name);
+        let trace = format!("{}.json", name);
+        match self.write_csv(&csv).and_then(|_| self.write_chrome_trace(&trace)) {
+            Ok(_) => println!("Wrote GPU times of {} frames to {} and {}", self.history.len(), csv, trace),
+            Err(e) => println!("Failed to write GPU times: {:?}", e),
+        }
+    }
+
+    // a row per pass and frame
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut csv = String::from("frame,pass,ms\n");
+        for frame in &self.history {
+            for (name, ms) in &frame.passes {
+                writeln!(csv, "{},{},{}", frame.frame, name, ms).unwrap();
+            }
+        }
+        std::fs::write(path, csv)
+    }
+
+    // the trace event format read by chrome://tracing and perfetto. the queries only measure
+    // durations, so the passes of a frame are laid out back to back from the time the cpu
+    // started the frame
+    pub fn write_chrome_trace(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let event = |name: &str, start: f64, duration_ms: f64, frame: u64| Json::object()
+            .with("name", name)
+            .with("ph", "X")
+            .with("ts", start)
+            .with("dur", duration_ms * 1000.0)
+            .with("pid", 0u32)
+            .with("tid", 0u32)
+            .with("args", Json::object().with("frame", frame as f64));
+        let mut events = vec![];
+        for frame in &self.history {
+            events.push(event("frame", frame.start, frame.total(), frame.frame));
+            let mut start = frame.start;
+            for (name, ms) in &frame.passes {
+                events.push(event(name, start, *ms, frame.frame));
+                start += ms * 1000.0;
+            }
+        }
+        std::fs::write(path, Json::object().with("traceEvents", Json::Array(events)).to_string())
+    }
+}
@@ -1,25 +1,160 @@
 use cgmath::{Array, Vector3};
+use crate::gl_wrapper::types::ColorSpace;
 use crate::util::error::ResourceParseError;
 
+pub const TEXTURE_MAP_COUNT: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureChannel {
+    Red,
+    Green,
+    Blue,
+    Matte,
+    Luminance,
+    Depth,
+}
+
+impl TextureChannel {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "r" => Some(Self::Red),
+            "g" => Some(Self::Green),
+            "b" => Some(Self::Blue),
+            "m" => Some(Self::Matte),
+            "l" => Some(Self::Luminance),
+            "z" => Some(Self::Depth),
+            _ => None,
+        }
+    }
+
+    // must match the CHANNEL_* defines in the shaders
+    fn to_gpu(self) -> i32 {
+        match self {
+            Self::Red | Self::Depth => 0,
+            Self::Green => 1,
+            Self::Blue => 2,
+            Self::Matte => 3,
+            Self::Luminance => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TextureMap {
+    pub name: String,
+    pub offset: Vector3<f32>,
+    pub scale: Vector3<f32>,
+    pub turbulence: Vector3<f32>,
+    pub bump_multiplier: f32,
+    pub boost: f32,
+    pub range: (f32, f32),
+    pub resolution: Option<u32>,
+    pub channel: Option<TextureChannel>,
+    pub clamp: bool,
+    pub blend_u: bool,
+    pub blend_v: bool,
+    pub color_correction: bool,
+}
+
+impl TextureMap {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            offset: Vector3::from_value(0.0),
+            scale: Vector3::from_value(1.0),
+            turbulence: Vector3::from_value(0.0),
+            bump_multiplier: 1.0,
+            boost: 0.0,
+            range: (0.0, 1.0),
+            resolution: None,
+            channel: None,
+            clamp: false,
+            blend_u: true,
+            blend_v: true,
+            color_correction: false,
+        }
+    }
+
+    // the layer of the texture and the texture array holding it
+    fn to_gpu(&self, layer: Option<(usize, u32)>, default_channel: TextureChannel) -> GpuTextureMap {
+        GpuTextureMap {
+            transform: [self.scale.x, self.scale.y, self.offset.x, self.offset.y],
+            range: [self.range.0, self.range.1],
+            layer: layer.map(|(i, _)| i as i32).unwrap_or(-1),
+            channel: self.channel.unwrap_or(default_channel).to_gpu(),
+            bump_multiplier: self.bump_multiplier,
+            clamp: self.clamp as u32,
+            array: layer.map(|(_, array)| array as i32).unwrap_or(0),
+            _padding: 0,
+        }
+    }
+}
+
+// std430 layout of a texture map, see the TextureMap struct in the shaders
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct GpuTextureMap {
+    transform: [f32; 4], // xy: scale, zw: offset
+    range: [f32; 2],     // x: base, y: gain
+    layer: i32,          // layer in the texture array, -1 if unused
+    channel: i32,
+    bump_multiplier: f32,
+    clamp: u32,
+    array: i32,          // the texture array, see MaterialTextureArray
+    _padding: u32,
+}
+
+impl GpuTextureMap {
+    const UNUSED: Self = Self {
+        transform: [1.0, 1.0, 0.0, 0.0], range: [0.0, 1.0], layer: -1, channel: 0, bump_multiplier: 1.0, clamp: 0, array: 0, _padding: 0,
+    };
+}
+
+// std430 layout of a material, see the Material struct in the shaders
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct GpuMaterial {
+    ambient: [f32; 4],      // rgb: ambient color
+    diffuse: [f32; 4],      // rgb: diffuse color, a: dissolve
+    specular: [f32; 4],     // rgb: specular color, a: specular exponent
+    emissive: [f32; 4],     // rgb: emissive color, a: optical density
+    transmission: [f32; 4], // rgb: transmission filter color, a: transmission
+    illum: i32,
+    metallic: f32,
+    roughness: f32,
+    alpha_cutoff: f32,
+    maps: [GpuTextureMap; TEXTURE_MAP_COUNT],
+}
+
 #[derive(Debug)]
 pub struct Material {
     ambient_color: Vector3<f32>,
     diffuse_color: Vector3<f32>,
     specular_color: Vector3<f32>,
     transmission_color: Vector3<f32>,
+    emissive_color: Vector3<f32>,
 
     specular_exp: f32,
     transmission: f32,
     optical_density: f32,
+    metallic: f32,
+    roughness: f32,
+    // alpha maps cut out texels below it
+    alpha_cutoff: f32,
+    illum: u32,
 
-    ambient_tex: Option<String>,
-    diffuse_tex: Option<String>,
-    specular_tex: Option<String>,
-    specular_exp_tex: Option<String>,
-    //alpha_tex: Option<String>,
-    //bump_tex: Option<String>,
-    //displacement_tex: Option<String>,
-    //decal_tex: Option<String>,
+    ambient_tex: Option<TextureMap>,
+    diffuse_tex: Option<TextureMap>,
+    specular_tex: Option<TextureMap>,
+    specular_exp_tex: Option<TextureMap>,
+    emissive_tex: Option<TextureMap>,
+    normal_tex: Option<TextureMap>,
+    metallic_roughness_tex: Option<TextureMap>,
+    occlusion_tex: Option<TextureMap>,
+    alpha_tex: Option<TextureMap>,
+    bump_tex: Option<TextureMap>,
+    displacement_tex: Option<TextureMap>,
+    decal_tex: Option<TextureMap>,
 }
 
 impl Material {
@@ -29,23 +164,78 @@ impl Material {
             diffuse_color: Vector3::from_value(1.0),
             specular_color: Vector3::from_value(1.0),
             transmission_color: Vector3::from_value(1.0),
+            emissive_color: Vector3::from_value(0.0),
             specular_exp: 10.0,
             transmission: 0.0,
             optical_density: 1.0,
+            metallic: 0.0,
+            roughness: 1.0,
+            alpha_cutoff: 0.5,
+            illum: 2,
             ambient_tex: None,
             diffuse_tex: None,
             specular_tex: None,
             specular_exp_tex: None,
+            emissive_tex: None,
+            normal_tex: None,
+            metallic_roughness_tex: None,
+            occlusion_tex: None,
+            alpha_tex: None,
+            bump_tex: None,
+            displacement_tex: None,
+            decal_tex: None,
         }
     }
 
-    pub fn get_texture_names(&self) -> Vec<String> {
-        let mut names = vec![];
-        if let Some(t) = &self.ambient_tex { names.push(t.to_owned()) };
-        if let Some(t) = &self.diffuse_tex { names.push(t.to_owned()) };
-        if let Some(t) = &self.specular_tex { names.push(t.to_owned()) };
-        if let Some(t) = &self.specular_exp_tex { names.push(t.to_owned()) };
-        names
+    pub fn illum(&self) -> u32 { self.illum }
+
+    // the order matches the MAP_* defines in the shaders, the channel is used for scalar maps.
+    // color maps are stored in srgb, all other maps hold linear data
+    fn texture_maps(&self) -> [(&Option<TextureMap>, TextureChannel, ColorSpace); TEXTURE_MAP_COUNT] {
+        [
+            (&self.ambient_tex, TextureChannel::Luminance, ColorSpace::Srgb),
+            (&self.diffuse_tex, TextureChannel::Luminance, ColorSpace::Srgb),
+            (&self.specular_tex, TextureChannel::Luminance, ColorSpace::Srgb),
+            (&self.specular_exp_tex, TextureChannel::Luminance, ColorSpace::Linear),
+            (&self.emissive_tex, TextureChannel::Luminance, ColorSpace::Srgb),
+            (&self.alpha_tex, TextureChannel::Luminance, ColorSpace::Linear),
+            (&self.bump_tex, TextureChannel::Luminance, ColorSpace::Linear),
+            (&self.normal_tex, TextureChannel::Luminance, ColorSpace::Linear),
+            (&self.displacement_tex, TextureChannel::Luminance, ColorSpace::Linear),
+            (&self.decal_tex, TextureChannel::Matte, ColorSpace::Linear),
+            (&self.metallic_roughness_tex, TextureChannel::Luminance, ColorSpace::Linear),
+            (&self.occlusion_tex, TextureChannel::Red, ColorSpace::Linear),
+        ]
+    }
+
+    // the textures used by the material together with the color space they are sampled in
+    pub fn get_textures(&self) -> Vec<(String, ColorSpace)> {
+        let mut textures: Vec<(String, ColorSpace)> = vec![];
+        self.texture_maps().iter()
+            .filter_map(|(map, _, color_space)| map.as_ref().map(|map| (map.name.to_owned(), *color_space)))
+            .for_each(|texture| if !textures.contains(&texture) { textures.push(texture) });
+        textures
+    }
+
+    // texture_layer returns the layer of a texture sampled in the color space and the texture array
+    // holding it
+    pub fn to_gpu(&self, texture_layer: impl Fn(&str, ColorSpace) -> Option<(usize, u32)>) -> GpuMaterial {
+        let rgba = |c: Vector3<f32>, a: f32| [c.x, c.y, c.z, a];
+        GpuMaterial {
+            ambient: rgba(self.ambient_color, 0.0),
+            diffuse: rgba(self.diffuse_color, 1.0 - self.transmission),
+            specular: rgba(self.specular_color, self.specular_exp),
+            emissive: rgba(self.emissive_color, self.optical_density),
+            transmission: rgba(self.transmission_color, self.transmission),
+            illum: self.illum as i32,
+            metallic: self.metallic,
+            roughness: self.roughness,
+            alpha_cutoff: self.alpha_cutoff,
+            maps: self.texture_maps().map(|(map, channel, color_space)| match map {
+                Some(map) => map.to_gpu(texture_layer(&map.name, color_space), channel),
+                None => GpuTextureMap::UNUSED,
+            }),
+        }
     }
 }
 
@@ -86,6 +276,10 @@ impl MaterialLibBuilder {
         self.current()?.transmission_color = col; Ok(())
     }
 
+    pub fn emissive_color(&mut self, col: Vector3<f32>) -> Result<(), ResourceParseError> {
+        self.current()?.emissive_color = col; Ok(())
+    }
+
     pub fn specular_exp(&mut self, f: f32) -> Result<(), ResourceParseError> {
         self.current()?.specular_exp = f; Ok(())
     }
@@ -98,19 +292,67 @@ impl MaterialLibBuilder {
         self.current()?.optical_density = f; Ok(())
     }
 
-    pub fn ambient_tex(&mut self, name: String) -> Result<(), ResourceParseError> {
-        self.current()?.ambient_tex = Some(name); Ok(())
+    pub fn metallic(&mut self, f: f32) -> Result<(), ResourceParseError> {
+        self.current()?.metallic = f; Ok(())
+    }
+
+    pub fn roughness(&mut self, f: f32) -> Result<(), ResourceParseError> {
+        self.current()?.roughness = f; Ok(())
+    }
+
+    pub fn alpha_cutoff(&mut self, f: f32) -> Result<(), ResourceParseError> {
+        self.current()?.alpha_cutoff = f; Ok(())
+    }
+
+    pub fn illum(&mut self, illum: u32) -> Result<(), ResourceParseError> {
+        self.current()?.illum = illum; Ok(())
+    }
+
+    pub fn ambient_tex(&mut self, map: TextureMap) -> Result<(), ResourceParseError> {
+        self.current()?.ambient_tex = Some(map); Ok(())
+    }
+
+    pub fn diffuse_tex(&mut self, map: TextureMap) -> Result<(), ResourceParseError> {
+        self.current()?.diffuse_tex = Some(map); Ok(())
+    }
+
+    pub fn specular_tex(&mut self, map: TextureMap) -> Result<(), ResourceParseError> {
+        self.current()?.specular_tex = Some(map); Ok(())
+    }
+
+    pub fn specular_exp_tex(&mut self, map: TextureMap) -> Result<(), ResourceParseError> {
+        self.current()?.specular_exp_tex = Some(map); Ok(())
+    }
+
+    pub fn emissive_tex(&mut self, map: TextureMap) -> Result<(), ResourceParseError> {
+        self.current()?.emissive_tex = Some(map); Ok(())
+    }
+
+    pub fn normal_tex(&mut self, map: TextureMap) -> Result<(), ResourceParseError> {
+        self.current()?.normal_tex = Some(map); Ok(())
+    }
+
+    pub fn metallic_roughness_tex(&mut self, map: TextureMap) -> Result<(), ResourceParseError> {
+        self.current()?.metallic_roughness_tex = Some(map); Ok(())
+    }
+
+    pub fn occlusion_tex(&mut self, map: TextureMap) -> Result<(), ResourceParseError> {
+        self.current()?.occlusion_tex = Some(map); Ok(())
+    }
+
+    pub fn alpha_tex(&mut self, map: TextureMap) -> Result<(), ResourceParseError> {
+        self.current()?.alpha_tex = Some(map); Ok(())
     }
 
-    pub fn diffuse_tex(&mut self, name: String) -> Result<(), ResourceParseError> {
-        self.current()?.diffuse_tex = Some(name); Ok(())
+    pub fn bump_tex(&mut self, map: TextureMap) -> Result<(), ResourceParseError> {
+        self.current()?.bump_tex = Some(map); Ok(())
     }
 
-    pub fn specular_tex(&mut self, name: String) -> Result<(), ResourceParseError> {
-        self.current()?.specular_tex = Some(name); Ok(())
+    pub fn displacement_tex(&mut self, map: TextureMap) -> Result<(), ResourceParseError> {
+        self.current()?.displacement_tex = Some(map); Ok(())
     }
 
-    pub fn specular_exp_tex(&mut self, name: String) -> Result<(), ResourceParseError> {
-        self.current()?.specular_exp_tex = Some(name); Ok(())
+    pub fn decal_tex(&mut self, map: TextureMap) -> Result<(), ResourceParseError> {
+        self.current()?.decal_tex = Some(map); Ok(())
     }
 }
\ No newline at end of file
@@ -0,0 +1,181 @@
+use std::sync::Arc;
+use image::imageops::{self, FilterType};
+use image::{ColorType, DynamicImage, ImageBuffer, Pixel, Rgba32FImage};
+use crate::gl_wrapper::buffer::ShaderStorageBuffer;
+use crate::gl_wrapper::shader::ShaderProgram;
+use crate::gl_wrapper::texture::TextureArray;
+use crate::gl_wrapper::types::{ColorSpace, TextureFilter, TextureFormat};
+use crate::rendering::material::{GpuMaterial, Material};
+use crate::resource::resource_manager::ResourceManager;
+use crate::util::error::ResourceError;
+
+// textures per texture array
+pub const MAX_MATERIAL_TEXTURES: usize = 64;
+// larger textures are scaled down to bound the size of the arrays
+const MAX_LAYER_SIZE: u32 = 2048;
+const MATERIAL_TEXTURE_FILTER: TextureFilter = TextureFilter::LinearMipmapLinear;
+const MATERIAL_TEXTURE_ANISOTROPY: f32 = 16.0;
+
+// the texture arrays material textures are stored in, the index is the array of a texture map in
+// the shaders. color maps are 8 bit srgb, as there are no 16 bit srgb formats, while data maps keep
+// the precision of their image, so that 16 bit height maps and float maps are not quantized
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MaterialTextureArray {
+    Srgb,
+    Linear,
+    Linear16,
+    // float images are stored with half precision, like Texture::from_image does
+    LinearFloat,
+}
+
+impl MaterialTextureArray {
+    const ALL: [Self; 4] = [Self::Srgb, Self::Linear, Self::Linear16, Self::LinearFloat];
+
+    fn of(image: &DynamicImage, color_space: ColorSpace) -> Self {
+        if color_space == ColorSpace::Srgb { return Self::Srgb }
+        match image.color() {
+            ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16 => Self::Linear16,
+            ColorType::Rgb32F | ColorType::Rgba32F => Self::LinearFloat,
+            _ => Self::Linear,
+        }
+    }
+
+    fn format(self) -> TextureFormat {
+        match self {
+            Self::Srgb => TextureFormat::SRGB8Alpha8,
+            Self::Linear => TextureFormat::RGBA8,
+            Self::Linear16 => TextureFormat::RGBA16,
+            Self::LinearFloat => TextureFormat::RGBA16F,
+        }
+    }
+
+    fn uniform(self) -> &'static str {
+        match self {
+            Self::Srgb => "srgbTextures",
+            Self::Linear => "linearTextures",
+            Self::Linear16 => "linear16Textures",
+            Self::LinearFloat => "floatTextures",
+        }
+    }
+}
+
+// the layer of a texture and the index of the texture array holding it
+type TextureLayer = (usize, u32);
+
+pub struct MaterialBuffer {
+    ssbo: ShaderStorageBuffer,
+    // indexed by MaterialTextureArray
+    texture_arrays: Vec<TextureArray>,
+}
+
+impl MaterialBuffer {
+    // uploads the materials in the order of the material indices of a model's triangles, unknown
+    // or empty names use the default material
+    pub fn new(material_names: &[String], resource_manager: &mut ResourceManager) -> Result<Self, ResourceError> {
+        let mut textures: [Vec<(String, Arc<DynamicImage>)>; 4] = Default::default();
+        // the layer and array of every texture name and the color space it is sampled in
+        let mut placed: Vec<(String, ColorSpace, Option<TextureLayer>)> = vec![];
+        let mut materials: Vec<GpuMaterial> = vec![];
+
+        for name in material_names {
+            let material = match resource_manager.get_material(name) {
+                Ok(material) => material,
+                Err(_) => {
+                    // models without materials have an unnamed material slot
+                    if !name.is_empty() { println!("Material {} is not loaded, using the default material", name) }
+                    Arc::new(Material::default())
+                }
+            };
+            for (texture_name, color_space) in material.get_textures() {
+                if placed.iter().any(|(n, c, _)| *n == texture_name && *c == color_space) { continue }
+                let image = resource_manager.get_material_image(&texture_name)?;
+                let array = MaterialTextureArray::of(&image, color_space);
+                let layers = &mut textures[array as usize];
+                let layer = match layers.iter().position(|(n, _)| *n == texture_name) {
+                    Some(layer) => Some(layer),
+                    None if layers.len() == MAX_MATERIAL_TEXTURES => {
+                        println!("Too many material textures, {} is not used", texture_name);
+                        None
+                    }
+                    None => {
+                        layers.push((texture_name.clone(), image));
+                        Some(layers.len() - 1)
+                    }
+                };
+                placed.push((texture_name, color_space, layer.map(|layer| (layer, array as u32))));
+            }
+            materials.push(material.to_gpu(|texture_name, color_space| {
+                placed.iter().find(|(n, c, _)| n == texture_name && *c == color_space).and_then(|(_, _, layer)| *layer)
+            }));
+        }
+        // triangles of models without materials use index 0
+        if materials.is_empty() { materials.push(Material::default().to_gpu(|_, _| None)) }
+
+        let ssbo = ShaderStorageBuffer::new();
+        ssbo.buffer_data(&materials);
+        let texture_arrays = MaterialTextureArray::ALL.iter().zip(&textures)
+            .map(|(array, textures)| Self::texture_array(textures, *array))
+            .collect();
+        Ok(Self { ssbo, texture_arrays })
+    }
+
+    // the layers of an array share one size, so every texture is scaled to the largest width and
+    // height among them. texture coordinates are relative, so the stretch does not show, but it
+    // costs memory and quality: every layer takes as much memory as the largest texture, and small
+    // textures are magnified, which blurs them, while sharing the array with a larger one
+    fn texture_array(textures: &[(String, Arc<DynamicImage>)], array: MaterialTextureArray) -> TextureArray {
+        let width = textures.iter().map(|(_, image)| image.width()).max().unwrap_or(1).min(MAX_LAYER_SIZE);
+        let height = textures.iter().map(|(_, image)| image.height()).max().unwrap_or(1).min(MAX_LAYER_SIZE);
+        let format = array.format();
+        match array {
+            MaterialTextureArray::Srgb | MaterialTextureArray::Linear => {
+                Self::upload(textures.iter().map(|(_, image)| image.to_rgba8()), width, height, format)
+            }
+            MaterialTextureArray::Linear16 => {
+                Self::upload(textures.iter().map(|(_, image)| image.to_rgba16()), width, height, format)
+            }
+            MaterialTextureArray::LinearFloat => {
+                let layers: Vec<Rgba32FImage> = textures.iter()
+                    .map(|(_, image)| Self::resize_float(image.to_rgba32f(), width, height))
+                    .collect();
+                TextureArray::from_images(&layers.iter().collect::<Vec<_>>(), format, MATERIAL_TEXTURE_FILTER, MATERIAL_TEXTURE_ANISOTROPY)
+            }
+        }
+    }
+
+    fn upload<P: Pixel + 'static>(images: impl Iterator<Item = ImageBuffer<P, Vec<P::Subpixel>>>, width: u32, height: u32, format: TextureFormat) -> TextureArray {
+        let layers: Vec<_> = images
+            .map(|image| if image.dimensions() == (width, height) { image } else { imageops::resize(&image, width, height, FilterType::Triangle) })
+            .collect();
+        TextureArray::from_images(&layers.iter().collect::<Vec<_>>(), format, MATERIAL_TEXTURE_FILTER, MATERIAL_TEXTURE_ANISOTROPY)
+    }
+
+    // resizing clamps float components to [0, 1], so the values are mapped into that range and back,
+    // which the linear filter commutes with
+    fn resize_float(mut image: Rgba32FImage, width: u32, height: u32) -> Rgba32FImage {
+        if image.dimensions() == (width, height) { return image }
+        let (min, max) = image.iter().filter(|c| c.is_finite())
+            .fold((0.0f32, 1.0f32), |(min, max), c| (min.min(*c), max.max(*c)));
+        let scale = max - min;
+        image.iter_mut().for_each(|c| *c = (*c - min) / scale);
+        let mut resized = imageops::resize(&image, width, height, FilterType::Triangle);
+        resized.iter_mut().for_each(|c| *c = *c * scale + min);
+        resized
+    }
+
+    pub fn bind_to_slot(&self, slot: u32) {
+        self.ssbo.bind_to_slot(slot);
+    }
+
+    pub fn buffer(&self) -> &ShaderStorageBuffer {
+        &self.ssbo
+    }
+
+    // binds the texture arrays to first_slot and the slots after it, in the order of
+    // MaterialTextureArray
+    pub fn bind_textures(&self, program: &mut ShaderProgram, first_slot: u32) {
+        MaterialTextureArray::ALL.iter().zip(&self.texture_arrays).zip(first_slot..).for_each(|((array, texture), slot)| {
+            program.set_uniform_texture(array.uniform(), texture.bind_to_slot(slot));
+        });
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use cgmath::{InnerSpace, Vector2, Vector3, Vector4, Zero};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NormalGeneration {
+    Keep,
+    GenerateMissing,
+    Recompute,
+}
+
+impl NormalGeneration {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "keep" => Some(Self::Keep),
+            "missing" => Some(Self::GenerateMissing),
+            "recompute" => Some(Self::Recompute),
+            _ => None,
+        }
+    }
+}
+
+// processing steps applied to a model when it is built, in the order welding, normals, tangents.
+// models are loaded as they are by default, every step has to be enabled
+#[derive(Debug, Clone, Copy)]
+pub struct MeshProcessing {
+    // vertices whose positions fall into the same cell of a grid with this spacing are merged,
+    // if all their other attributes are equal
+    pub weld_epsilon: Option<f32>,
+    pub normals: NormalGeneration,
+    // faces meeting at a larger angle (in degrees) keep a hard edge between them
+    pub smoothing_angle: f32,
+    // generates tangents for models with normals and tex coords but without tangents
+    pub generate_tangents: bool,
+}
+
+impl Default for MeshProcessing {
+    fn default() -> Self {
+        Self { weld_epsilon: None, normals: NormalGeneration::Keep, smoothing_angle: 60.0, generate_tangents: false }
+    }
+}
+
+// the result of a step that splits or merges vertices: every new vertex copies the attributes of
+// its source vertex, corners holds the new vertex of every triangle corner and vertex_map holds
+// a new vertex for every old vertex that is still in use
+pub struct VertexRemap {
+    pub sources: Vec<u32>,
+    pub corners: Vec<u32>,
+    pub vertex_map: Vec<Option<u32>>,
+}
+
+impl VertexRemap {
+    // splits the vertex of every corner by a key, corners with the same vertex and key share a vertex
+    fn split_corners<K: Eq + std::hash::Hash>(vertex_count: usize, corners: &[u32], key: impl Fn(usize) -> K) -> Self {
+        let mut new_vertices: HashMap<(u32, K), u32> = HashMap::with_capacity(vertex_count);
+        let mut remap = Self { sources: Vec::with_capacity(vertex_count), corners: Vec::with_capacity(corners.len()), vertex_map: vec![None; vertex_count] };
+        for (i, vertex) in corners.iter().enumerate() {
+            let next_idx = remap.sources.len() as u32;
+            let new_idx = *new_vertices.entry((*vertex, key(i))).or_insert_with(|| {
+                remap.sources.push(*vertex);
+                next_idx
+            });
+            remap.vertex_map[*vertex as usize].get_or_insert(new_idx);
+            remap.corners.push(new_idx);
+        }
+        remap
+    }
+}
+
+fn bits(v: Vector3<f32>) -> [u32; 3] {
+    [v.x.to_bits(), v.y.to_bits(), v.z.to_bits()]
+}
+
+fn unit_or_zero(v: Vector3<f32>) -> Vector3<f32> {
+    if v.magnitude2() > 0.0 { v.normalize() } else { v }
+}
+
+// merges vertices with (almost) equal positions and equal other attributes, given as raw bits.
+// merged vertices take the position of the first of them
+pub fn weld(positions: &[Vector3<f32>], epsilon: f32, corners: &[u32], attributes: impl Fn(usize) -> Vec<u32>) -> VertexRemap {
+    let cell = |v: f32| if epsilon > 0.0 { (v / epsilon).round() as i64 } else { v.to_bits() as i64 };
+    let mut welded: HashMap<([i64; 3], Vec<u32>), u32> = HashMap::with_capacity(positions.len());
+    let mut sources = Vec::with_capacity(positions.len());
+    let vertex_map: Vec<Option<u32>> = positions.iter().enumerate().map(|(i, p)| {
+        let next_idx = sources.len() as u32;
+        Some(*welded.entry(([cell(p.x), cell(p.y), cell(p.z)], attributes(i))).or_insert_with(|| {
+            sources.push(i as u32);
+            next_idx
+        }))
+    }).collect();
+    let corners = corners.iter().map(|c| vertex_map[*c as usize].unwrap()).collect();
+    VertexRemap { sources, corners, vertex_map }
+}
+
+// area weighted vertex normals, averaging only over the faces around a position whose normals
+// differ from the corner's face normal by at most the smoothing angle. vertices sharing a position
+// are smoothed together even if their other attributes differ, so seams in the tex coords stay smooth
+pub fn smooth_normals(positions: &[Vector3<f32>], corners: &[u32], smoothing_angle: f32) -> (VertexRemap, Vec<Vector3<f32>>) {
+    let cos_threshold = smoothing_angle.to_radians().cos();
+    let face_normals: Vec<Vector3<f32>> = corners.chunks(3).map(|c| {
+        let [p0, p1, p2] = [c[0], c[1], c[2]].map(|i| positions[i as usize]);
+        (p1 - p0).cross(p2 - p0)
+    }).collect();
+    let unit_face_normals: Vec<Vector3<f32>> = face_normals.iter().map(|n| unit_or_zero(*n)).collect();
+
+    let mut position_ids: HashMap<[u32; 3], u32> = HashMap::with_capacity(positions.len());
+    let position_id: Vec<u32> = positions.iter().map(|p| {
+        let next_id = position_ids.len() as u32;
+        *position_ids.entry(bits(*p)).or_insert(next_id)
+    }).collect();
+
+    // the triangles around every position, stored as consecutive rows
+    let mut offsets = vec![0usize; position_ids.len() + 1];
+    corners.iter().for_each(|c| offsets[position_id[*c as usize] as usize + 1] += 1);
+    (1..offsets.len()).for_each(|i| offsets[i] += offsets[i - 1]);
+    let mut fill = offsets.clone();
+    let mut adjacent = vec![0u32; corners.len()];
+    corners.iter().enumerate().for_each(|(i, c)| {
+        let id = position_id[*c as usize] as usize;
+        adjacent[fill[id]] = (i / 3) as u32;
+        fill[id] += 1;
+    });
+
+    let corner_normals: Vec<Vector3<f32>> = corners.iter().enumerate().map(|(i, c)| {
+        let triangle = i / 3;
+        let id = position_id[*c as usize] as usize;
+        unit_or_zero(adjacent[offsets[id]..offsets[id + 1]].iter()
+            .map(|other| *other as usize)
+            .filter(|other| *other == triangle || unit_face_normals[triangle].dot(unit_face_normals[*other]) >= cos_threshold)
+            .fold(Vector3::zero(), |sum, other| sum + face_normals[other]))
+    }).collect();
+
+    let remap = VertexRemap::split_corners(positions.len(), corners, |i| bits(corner_normals[i]));
+    let mut normals = vec![Vector3::zero(); remap.sources.len()];
+    remap.corners.iter().enumerate().for_each(|(i, v)| normals[*v as usize] = corner_normals[i]);
+    (remap, normals)
+}
+
+// angle weighted per vertex tangents: the tex coord derivatives of the faces are weighted by the
+// corner angles, orthogonalized against the vertex normal, and vertices shared by faces with
+// mirrored tex coords are split so that every vertex has a single handedness. this is not
+// mikktspace, so normal maps baked against mikktspace tangents can show slight seams
+pub fn tangents(positions: &[Vector3<f32>], tex_coords: &[Vector2<f32>], normals: &[Vector3<f32>], corners: &[u32]) -> (VertexRemap, Vec<Vector4<f32>>) {
+    let triangle_count = corners.len() / 3;
+    let mut face_tangents = Vec::with_capacity(triangle_count);
+    let mut face_bitangents = Vec::with_capacity(triangle_count);
+    let mut handedness = Vec::with_capacity(triangle_count);
+    for c in corners.chunks(3) {
+        let [p0, p1, p2] = [c[0], c[1], c[2]].map(|i| positions[i as usize]);
+        let [t0, t1, t2] = [c[0], c[1], c[2]].map(|i| tex_coords[i as usize]);
+        let (e1, e2, d1, d2) = (p1 - p0, p2 - p0, t1 - t0, t2 - t0);
+        let det = d1.x * d2.y - d2.x * d1.y;
+        let (t, b) = if det.abs() > f32::EPSILON {
+            ((e1 * d2.y - e2 * d1.y) / det, (e2 * d1.x - e1 * d2.x) / det)
+        } else { (Vector3::zero(), Vector3::zero()) };
+        face_tangents.push(t);
+        face_bitangents.push(b);
+        handedness.push(if e1.cross(e2).cross(t).dot(b) < 0.0 { -1i8 } else { 1 });
+    }
+
+    let remap = VertexRemap::split_corners(positions.len(), corners, |i| handedness[i / 3]);
+    let mut accumulated = vec![(Vector3::zero(), Vector3::zero()); remap.sources.len()];
+    corners.chunks(3).enumerate().for_each(|(triangle, c)| {
+        let p = [c[0], c[1], c[2]].map(|i| positions[i as usize]);
+        for k in 0..3 {
+            let angle = unit_or_zero(p[(k + 1) % 3] - p[k]).dot(unit_or_zero(p[(k + 2) % 3] - p[k])).clamp(-1.0, 1.0).acos();
+            let (t, b) = &mut accumulated[remap.corners[triangle * 3 + k] as usize];
+            *t += face_tangents[triangle] * angle;
+            *b += face_bitangents[triangle] * angle;
+        }
+    });
+
+    let tangents = accumulated.iter().zip(&remap.sources).map(|((t, b), source)| {
+        let n = normals[*source as usize];
+        let mut tangent = *t - n * n.dot(*t);
+        if tangent.magnitude2() <= f32::EPSILON * f32::EPSILON {
+            // no usable tex coords, any direction perpendicular to the normal will do
+            let axis = if n.x.abs() < 0.9 { Vector3::unit_x() } else { Vector3::unit_y() };
+            tangent = axis - n * n.dot(axis);
+        }
+        let tangent = unit_or_zero(tangent);
+        let w = if n.cross(tangent).dot(*b) < 0.0 { -1.0 } else { 1.0 };
+        tangent.extend(w)
+    }).collect();
+    (remap, tangents)
+}
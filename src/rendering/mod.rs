@@ -1,5 +1,16 @@
 pub mod camera;
 pub mod model;
 pub mod material;
+pub mod material_buffer;
+pub mod mesh_processing;
+pub mod scene;
 pub mod camera_controller;
 pub mod framebuffer_manager;
+pub mod render_graph;
+pub mod debug_view;
+pub mod bvh_overlay;
+pub mod render_settings;
+pub mod gpu_profiler;
+pub mod capture;
+pub mod camera_path;
+pub mod benchmark;
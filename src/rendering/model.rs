@@ -1,7 +1,14 @@
 use std::collections::{HashMap, HashSet};
-use cgmath::{Vector2, Vector3};
+use std::hash::{BuildHasherDefault, Hasher};
+use std::{iter, thread};
+use cgmath::{InnerSpace, Matrix, Matrix3, Matrix4, Rad, SquareMatrix, Vector2, Vector3, Vector4, Zero};
 use crate::raytracing::bvh::{BVH, BVHBuilder};
-use crate::raytracing::types::{IndexBundle, Triangle};
+use crate::raytracing::types::{AABB, AABBBuilder, IndexBundle, Triangle};
+use crate::rendering::mesh_processing::{self, MeshProcessing, NormalGeneration, VertexRemap};
+use crate::util::error::ResourceParseError;
+
+// triangles per thread below which deduplicating vertices in parallel is not worth the merge
+const MIN_DEDUP_CHUNK_SIZE: usize = 1 << 16;
 
 pub struct Model {
     triangles: Vec<Triangle>,
@@ -9,6 +16,12 @@ pub struct Model {
     positions: Vec<Vector3<f32>>,
     tex_coords: Option<Vec<Vector2<f32>>>,
     normals: Option<Vec<Vector3<f32>>>,
+    tangents: Option<Vec<Vector4<f32>>>,
+    colors: Option<Vec<Vector3<f32>>>,
+    lines: Vec<u32>,
+
+    groups: Vec<ModelGroup>,
+    triangle_groups: Vec<u32>,
 
     material_libs: Vec<String>,
     materials: Vec<String>,
@@ -16,15 +29,30 @@ pub struct Model {
     bvh: Option<BVH>,
 }
 
+#[derive(Clone, Hash, Eq, PartialEq)]
+pub struct ModelGroup {
+    pub object: String,
+    pub groups: Vec<String>,
+    pub smoothing_group: u32,
+}
+
 impl Model {
     pub fn triangles(&self) -> &Vec<Triangle> { &self.triangles }
     pub fn indices(&self) -> &Vec<u32> { &self.indices }
     pub fn positions(&self) -> &Vec<Vector3<f32>> { &self.positions }
     pub fn tex_coords(&self) -> &Option<Vec<Vector2<f32>>> { &self.tex_coords }
     pub fn normals(&self) -> &Option<Vec<Vector3<f32>>> { &self.normals }
+    pub fn tangents(&self) -> &Option<Vec<Vector4<f32>>> { &self.tangents }
+    pub fn colors(&self) -> &Option<Vec<Vector3<f32>>> { &self.colors }
+    pub fn lines(&self) -> &Vec<u32> { &self.lines }
+
+    pub fn groups(&self) -> &Vec<ModelGroup> { &self.groups }
+    pub fn triangle_groups(&self) -> &Vec<u32> { &self.triangle_groups }
 
     pub fn has_tex_coords(&self) -> bool { self.tex_coords.is_some() }
     pub fn has_normals(&self) -> bool { self.normals.is_some() }
+    pub fn has_tangents(&self) -> bool { self.tangents.is_some() }
+    pub fn has_colors(&self) -> bool { self.colors.is_some() }
 
     pub fn set_triangles(&mut self, triangles: Vec<Triangle>) {
         self.triangles = triangles;
@@ -32,6 +60,9 @@ impl Model {
     pub fn set_indices(&mut self, indices: Vec<u32>) {
         self.indices = indices;
     }
+    pub fn set_triangle_groups(&mut self, triangle_groups: Vec<u32>) {
+        self.triangle_groups = triangle_groups;
+    }
 
     pub fn build_bvh(&mut self) { self.bvh = Some(BVHBuilder::new(self).build()) }
 
@@ -39,29 +70,230 @@ impl Model {
     pub fn get_materials(&self) -> &Vec<String> { &self.materials }
 
     pub fn get_bvh(&self) -> Option<&BVH> { self.bvh.as_ref() }
+
+    // none for a model without positions
+    pub fn bounds(&self) -> Option<AABB> {
+        if self.positions.is_empty() { return None }
+        let mut builder = AABBBuilder::new();
+        self.positions.iter().for_each(|p| builder.include(p));
+        Some(builder.build())
+    }
+
+    // transforms positions, normals and tangents. mirroring transforms also flip the triangle
+    // winding, so that faces keep pointing outwards. the bvh has to be rebuilt afterwards
+    pub fn transform(&mut self, matrix: Matrix4<f32>) {
+        let linear = Matrix3::from_cols(matrix.x.truncate(), matrix.y.truncate(), matrix.z.truncate());
+        let normal_matrix = linear.invert().map(|m| m.transpose()).unwrap_or(linear);
+        let mirrored = linear.determinant() < 0.0;
+        let normalize = |v: Vector3<f32>| if v.magnitude2() > 0.0 { v.normalize() } else { v };
+
+        self.positions.iter_mut().for_each(|p| *p = (matrix * p.extend(1.0)).truncate());
+        if let Some(normals) = &mut self.normals {
+            normals.iter_mut().for_each(|n| *n = normalize(normal_matrix * *n));
+        }
+        if let Some(tangents) = &mut self.tangents {
+            tangents.iter_mut().for_each(|t| {
+                let w = if mirrored { -t.w } else { t.w };
+                *t = normalize(linear * t.truncate()).extend(w);
+            });
+        }
+        if mirrored {
+            self.triangles.iter_mut().for_each(|tri| std::mem::swap(&mut tri.p1, &mut tri.p2));
+            self.indices.chunks_mut(3).for_each(|c| c.swap(1, 2));
+        }
+        self.bvh = None;
+    }
+
+    pub fn translate(&mut self, offset: Vector3<f32>) {
+        self.transform(Matrix4::from_translation(offset))
+    }
+
+    pub fn rotate<A: Into<Rad<f32>>>(&mut self, axis: Vector3<f32>, angle: A) {
+        self.transform(Matrix4::from_axis_angle(axis.normalize(), angle))
+    }
+
+    pub fn scale(&mut self, factor: Vector3<f32>) {
+        self.transform(Matrix4::from_nonuniform_scale(factor.x, factor.y, factor.z))
+    }
+
+    // moves the center of the bounding box to the origin
+    pub fn recenter(&mut self) {
+        if let Some(bounds) = self.bounds() { self.translate(-bounds.center()) }
+    }
+
+    // recenters the model and scales it uniformly, so that its longest side has length one
+    pub fn fit_to_unit_box(&mut self) {
+        self.recenter();
+        let Some(bounds) = self.bounds() else { return };
+        let extent = bounds.extent();
+        let longest = extent.x.max(extent.y).max(extent.z);
+        if longest > 0.0 { self.transform(Matrix4::from_scale(1.0 / longest)) }
+    }
+
+    pub fn process(&mut self, processing: &MeshProcessing) {
+        if let Some(epsilon) = processing.weld_epsilon { self.weld(epsilon) }
+        match processing.normals {
+            NormalGeneration::Keep => {}
+            NormalGeneration::GenerateMissing => if !self.has_normals() { self.generate_normals(processing.smoothing_angle) },
+            NormalGeneration::Recompute => self.generate_normals(processing.smoothing_angle),
+        }
+        if processing.generate_tangents && !self.has_tangents() && self.has_normals() && self.has_tex_coords() {
+            self.generate_tangents();
+        }
+    }
+
+    pub fn weld(&mut self, epsilon: f32) {
+        let attributes = |i: usize| {
+            let mut bits = vec![];
+            if let Some(tex_coords) = &self.tex_coords { bits.extend([tex_coords[i].x, tex_coords[i].y].map(f32::to_bits)) }
+            if let Some(normals) = &self.normals { bits.extend([normals[i].x, normals[i].y, normals[i].z].map(f32::to_bits)) }
+            if let Some(tangents) = &self.tangents { bits.extend([tangents[i].x, tangents[i].y, tangents[i].z, tangents[i].w].map(f32::to_bits)) }
+            if let Some(colors) = &self.colors { bits.extend([colors[i].x, colors[i].y, colors[i].z].map(f32::to_bits)) }
+            bits
+        };
+        let remap = mesh_processing::weld(&self.positions, epsilon, &self.indices, attributes);
+        self.apply_remap(remap);
+    }
+
+    // replaces the normals with smoothed ones, existing tangents no longer match and are dropped
+    pub fn generate_normals(&mut self, smoothing_angle: f32) {
+        let (remap, mut normals) = mesh_processing::smooth_normals(&self.positions, &self.indices, smoothing_angle);
+        self.tangents = None;
+        self.apply_remap(remap);
+        normals.resize(self.positions.len(), Vector3::new(0.0, 0.0, 0.0));
+        self.normals = Some(normals);
+    }
+
+    pub fn generate_tangents(&mut self) {
+        let (Some(tex_coords), Some(normals)) = (&self.tex_coords, &self.normals) else { return };
+        let (remap, mut tangents) = mesh_processing::tangents(&self.positions, tex_coords, normals, &self.indices);
+        self.apply_remap(remap);
+        tangents.resize(self.positions.len(), Vector4::new(1.0, 0.0, 0.0, 1.0));
+        self.tangents = Some(tangents);
+    }
+
+    fn apply_remap(&mut self, remap: VertexRemap) {
+        let VertexRemap { mut sources, corners, mut vertex_map } = remap;
+        // vertices only used by lines are kept as they are
+        let lines = self.lines.iter().map(|v| *vertex_map[*v as usize].get_or_insert_with(|| {
+            sources.push(*v);
+            sources.len() as u32 - 1
+        })).collect();
+
+        fn gather<T: Copy>(values: &[T], sources: &[u32]) -> Vec<T> {
+            sources.iter().map(|s| values[*s as usize]).collect()
+        }
+        self.positions = gather(&self.positions, &sources);
+        self.tex_coords = self.tex_coords.as_ref().map(|values| gather(values, &sources));
+        self.normals = self.normals.as_ref().map(|values| gather(values, &sources));
+        self.tangents = self.tangents.as_ref().map(|values| gather(values, &sources));
+        self.colors = self.colors.as_ref().map(|values| gather(values, &sources));
+
+        self.triangles = self.triangles.iter().zip(corners.chunks(3))
+            .map(|(tri, c)| Triangle::new(c[0], c[1], c[2], tri.mat_idx))
+            .collect();
+        self.indices = corners;
+        self.lines = lines;
+        self.bvh = None;
+    }
+
+    // models without materials still take up one material slot, as their triangles use index 0
+    pub fn material_slot_count(&self) -> usize { self.materials.len().max(1) }
+
+    // concatenates the models into one, offsetting vertex, material and group indices.
+    // attributes only some of the models have are filled with defaults for the others
+    pub fn merged(models: &[&Model]) -> Model {
+        fn merge_attribute<T: Clone>(models: &[&Model], zero: T, get: impl Fn(&Model) -> &Option<Vec<T>>) -> Option<Vec<T>> {
+            if models.iter().all(|m| get(m).is_none()) { return None }
+            Some(models.iter().flat_map(|m| match get(m) {
+                Some(values) => values.clone(),
+                None => vec![zero.clone(); m.positions.len()],
+            }).collect())
+        }
+
+        let mut merged = Model {
+            triangles: Vec::with_capacity(models.iter().map(|m| m.triangles.len()).sum()),
+            indices: Vec::with_capacity(models.iter().map(|m| m.indices.len()).sum()),
+            positions: models.iter().flat_map(|m| m.positions.iter().copied()).collect(),
+            tex_coords: merge_attribute(models, Vector2::new(0.0, 0.0), Model::tex_coords),
+            normals: merge_attribute(models, Vector3::new(0.0, 0.0, 0.0), Model::normals),
+            tangents: merge_attribute(models, Vector4::new(0.0, 0.0, 0.0, 0.0), Model::tangents),
+            colors: merge_attribute(models, Vector3::new(1.0, 1.0, 1.0), Model::colors),
+            lines: vec![],
+            groups: vec![],
+            triangle_groups: vec![],
+            material_libs: vec![],
+            materials: vec![],
+            bvh: None,
+        };
+
+        let mut vertex_offset = 0;
+        for model in models {
+            let material_offset = merged.materials.len() as u32;
+            let group_offset = merged.groups.len() as u32;
+            merged.triangles.extend(model.triangles.iter().map(|tri| Triangle::new(
+                tri.p0 + vertex_offset, tri.p1 + vertex_offset, tri.p2 + vertex_offset, tri.mat_idx + material_offset,
+            )));
+            merged.indices.extend(model.indices.iter().map(|i| i + vertex_offset));
+            merged.lines.extend(model.lines.iter().map(|i| i + vertex_offset));
+            merged.groups.extend(model.groups.iter().cloned());
+            merged.triangle_groups.extend(model.triangle_groups.iter().map(|g| g + group_offset));
+            for lib in &model.material_libs {
+                if !merged.material_libs.contains(lib) { merged.material_libs.push(lib.clone()) }
+            }
+            merged.materials.extend(model.materials.iter().cloned());
+            merged.materials.resize(material_offset as usize + model.material_slot_count(), String::new());
+            vertex_offset += model.positions.len() as u32;
+        }
+        merged
+    }
 }
 
 struct IBTriangle {
     index_bundles: [IndexBundle; 3],
     mat_idx: u32,
+    group: u32,
 }
 
 impl IBTriangle {
-    pub fn new(i0: IndexBundle, i1: IndexBundle, i2: IndexBundle, mat_idx: u32) -> Self {
-        Self { index_bundles: [i0, i1, i2], mat_idx, }
+    pub fn new(i0: IndexBundle, i1: IndexBundle, i2: IndexBundle, mat_idx: u32, group: u32) -> Self {
+        Self { index_bundles: [i0, i1, i2], mat_idx, group }
     }
 }
 
+type BundleMap<'a> = HashMap<&'a IndexBundle, u32, BuildHasherDefault<BundleHasher>>;
+
+// index bundles are small integers, so a multiplicative hash is much faster than the default SipHash
+#[derive(Default)]
+struct BundleHasher {
+    hash: u64,
+}
+
+impl Hasher for BundleHasher {
+    fn finish(&self) -> u64 { self.hash }
+    fn write(&mut self, bytes: &[u8]) { bytes.iter().for_each(|b| self.write_u64(*b as u64)) }
+    fn write_i32(&mut self, i: i32) { self.write_u64(i as u32 as u64) }
+    fn write_u64(&mut self, i: u64) { self.hash = (self.hash.rotate_left(5) ^ i).wrapping_mul(0x517c_c1b7_2722_0a95) }
+}
+
 pub struct ModelBuilder {
     indices: Vec<IBTriangle>,
     positions: Vec<Vector3<f32>>,
     tex_coords: Vec<Vector2<f32>>,
     normals: Vec<Vector3<f32>>,
+    tangents: Vec<Vector4<f32>>,
+    colors: Vec<Vector3<f32>>,
+    lines: Vec<[IndexBundle; 2]>,
 
     material_libs: HashSet<String>,
     materials: HashMap<String, u32>,
+    groups: HashMap<ModelGroup, u32>,
 
     current_mat: u32,
+    current_group: ModelGroup,
+    current_group_idx: Option<u32>,
+
+    processing: MeshProcessing,
 }
 
 impl ModelBuilder {
@@ -71,19 +303,39 @@ impl ModelBuilder {
             positions: vec![],
             tex_coords: vec![],
             normals: vec![],
+            tangents: vec![],
+            colors: vec![],
+            lines: vec![],
             material_libs: HashSet::new(),
             materials: HashMap::new(),
+            groups: HashMap::new(),
             current_mat: 0,
+            current_group: ModelGroup { object: String::new(), groups: vec![], smoothing_group: 0 },
+            current_group_idx: None,
+            processing: MeshProcessing::default(),
         }
     }
 
+    pub fn set_mesh_processing(&mut self, processing: MeshProcessing) { self.processing = processing }
+
     pub fn add_indices(&mut self, i0: IndexBundle, i1: IndexBundle, i2: IndexBundle, mat_idx: u32) {
-        self.indices.push(IBTriangle::new(i0, i1, i2, mat_idx));
+        let group = self.current_group_idx();
+        self.indices.push(IBTriangle::new(i0, i1, i2, mat_idx, group));
     }
 
+    pub fn add_line(&mut self, i0: IndexBundle, i1: IndexBundle) { self.lines.push([i0, i1]) }
+
+    pub fn position_count(&self) -> usize { self.positions.len() }
+    pub fn tex_coord_count(&self) -> usize { self.tex_coords.len() }
+    pub fn normal_count(&self) -> usize { self.normals.len() }
+
     pub fn add_position(&mut self, position: Vector3<f32>) { self.positions.push(position) }
     pub fn add_tex_coord(&mut self, tex_coord: Vector2<f32>) { self.tex_coords.push(tex_coord) }
     pub fn add_normal(&mut self, normal: Vector3<f32>) { self.normals.push(normal) }
+    // tangents share the normal index of a vertex
+    pub fn add_tangent(&mut self, tangent: Vector4<f32>) { self.tangents.push(tangent) }
+    // colors share the position index of a vertex
+    pub fn add_color(&mut self, color: Vector3<f32>) { self.colors.push(color) }
 
     pub fn add_material_lib(&mut self, lib: String) { self.material_libs.insert(lib); }
     pub fn add_material(&mut self, mat: String) {
@@ -98,53 +350,140 @@ impl ModelBuilder {
 
     pub fn get_current_mat(&self) -> u32 { self.current_mat }
 
-    pub fn build(self) -> Model {
-        let mut bundle_map: HashMap<IndexBundle, u32> = HashMap::new();
-        let mut new_triangles: Vec<Triangle> = vec![];
-        let mut new_indices: Vec<u32> = vec![];
-        let mut new_positions: Vec<Vector3<f32>> = vec![];
-        let mut new_tex_coords: Vec<Vector2<f32>> = vec![];
-        let mut new_normals: Vec<Vector3<f32>> = vec![];
-
-        let has_tex_coords = self.tex_coords.len() > 0;
-        let has_normals = self.normals.len() > 0;
-
-        let pos_len = self.positions.len() as i32;
-        let tex_len = self.tex_coords.len() as i32;
-        let nor_len = self.normals.len() as i32;
-
-        let mut new_idx = 0;
-        self.indices.into_iter().for_each(|ib_tri| {
-            let indices = ib_tri.index_bundles.into_iter().map(|mut ib| {
-                ib.normalize(pos_len, tex_len, nor_len);
-                if let Some(idx) = bundle_map.get(&ib) { *idx }
-                else {
-                    new_positions.push(self.positions[ib.pos_idx as usize]);
-                    if has_tex_coords { new_tex_coords.push(self.tex_coords[ib.tex_idx as usize]) }
-                    if has_normals { new_normals.push(self.normals[ib.nor_idx as usize]) }
-                    bundle_map.insert(ib, new_idx);
-                    new_idx += 1;
-                    new_idx - 1
-                }
-            }).collect::<Vec<u32>>();
-            new_triangles.push(Triangle::new(indices[0], indices[1], indices[2], ib_tri.mat_idx));
-            new_indices.push(indices[0]);
-            new_indices.push(indices[1]);
-            new_indices.push(indices[2]);
+    pub fn set_object(&mut self, object: String) {
+        self.current_group.object = object;
+        self.current_group_idx = None;
+    }
+    pub fn set_groups(&mut self, groups: Vec<String>) {
+        self.current_group.groups = groups;
+        self.current_group_idx = None;
+    }
+    pub fn set_smoothing_group(&mut self, smoothing_group: u32) {
+        self.current_group.smoothing_group = smoothing_group;
+        self.current_group_idx = None;
+    }
+
+    fn current_group_idx(&mut self) -> u32 {
+        if let Some(idx) = self.current_group_idx { return idx }
+        let next_idx = self.groups.len() as u32;
+        let idx = *self.groups.entry(self.current_group.clone()).or_insert(next_idx);
+        self.current_group_idx = Some(idx);
+        idx
+    }
+
+    // deduplicates a range of index bundles, returning the unique bundles in order of first use
+    // and for every bundle of the range its index into them
+    fn dedup_bundles<'a>(bundles: impl Iterator<Item = &'a IndexBundle>, capacity: usize) -> (Vec<&'a IndexBundle>, Vec<u32>) {
+        let mut map: BundleMap = HashMap::with_capacity_and_hasher(capacity, Default::default());
+        let mut unique = vec![];
+        let indices = bundles.map(|ib| *map.entry(ib).or_insert_with(|| {
+            unique.push(ib);
+            unique.len() as u32 - 1
+        })).collect();
+        (unique, indices)
+    }
+
+    pub fn build(self) -> Result<Model, ResourceParseError> {
+        // the triangles are deduplicated in ranges on separate threads, the lines form the last range.
+        // merging the unique bundles of the ranges in order numbers the vertices exactly like a
+        // sequential pass would, while only the much fewer unique bundles are hashed serially
+        let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        let chunk_size = (self.indices.len() / threads).max(MIN_DEDUP_CHUNK_SIZE);
+        let ranges: Vec<(Vec<&IndexBundle>, Vec<u32>)> = thread::scope(|s| {
+            let handles: Vec<_> = self.indices.chunks(chunk_size)
+                .map(|chunk| s.spawn(move || Self::dedup_bundles(chunk.iter().flat_map(|t| &t.index_bundles), chunk.len())))
+                .chain(iter::once(s.spawn(|| Self::dedup_bundles(self.lines.iter().flatten(), self.lines.len()))))
+                .collect();
+            handles.into_iter().map(|h| h.join().expect("vertex deduplication thread panicked")).collect()
         });
+        let (uniques, mut range_indices): (Vec<_>, Vec<_>) = ranges.into_iter().unzip();
+
+        // the parsers check the indices against the counts at the time they are parsed, they are
+        // checked again against the final counts before indexing with them
+        let lens = [self.positions.len(), self.tex_coords.len(), self.normals.len()].map(|len| len as i32);
+        for &ib in uniques.iter().flatten() {
+            IndexBundle::check_range(ib.pos_idx, lens[0])?;
+            if !self.tex_coords.is_empty() && ib.tex_idx != IndexBundle::MISSING { IndexBundle::check_range(ib.tex_idx, lens[1])? }
+            if !self.normals.is_empty() && ib.nor_idx != IndexBundle::MISSING { IndexBundle::check_range(ib.nor_idx, lens[2])? }
+        }
+        // triangle vertices without a tex coord get (0, 0), while a single one without a normal drops
+        // the normals of all vertices, as partial normals cannot be shaded. mesh processing can
+        // generate them instead. lines are not shaded and get zero vectors for what they lack
+        let triangle_uniques = &uniques[..uniques.len() - 1];
+        let missing_tex_coords = triangle_uniques.iter().flatten().filter(|ib| ib.tex_idx == IndexBundle::MISSING).count();
+        let missing_normals = triangle_uniques.iter().flatten().any(|ib| ib.nor_idx == IndexBundle::MISSING);
+        if missing_tex_coords > 0 && !self.tex_coords.is_empty() {
+            println!("{} vertices have no texture coordinates, they use (0, 0)", missing_tex_coords);
+        }
+        if missing_normals && !self.normals.is_empty() {
+            println!("Some vertices have no normal, dropping the normals of the model");
+        }
+
+        let has_tex_coords = !self.tex_coords.is_empty();
+        let has_normals = !self.normals.is_empty() && !missing_normals;
+        let has_tangents = has_normals && self.tangents.len() == self.normals.len();
+        let has_colors = !self.colors.is_empty() && self.colors.len() == self.positions.len();
+
+        // most vertices are shared by several triangles, so the position count is a good estimate
+        // of the final vertex count and avoids rehashing large models repeatedly
+        let vertex_estimate = self.positions.len();
+        let with_capacity = |has: bool| if has { vertex_estimate } else { 0 };
+        let mut new_positions: Vec<Vector3<f32>> = Vec::with_capacity(vertex_estimate);
+        let mut new_tex_coords: Vec<Vector2<f32>> = Vec::with_capacity(with_capacity(has_tex_coords));
+        let mut new_normals: Vec<Vector3<f32>> = Vec::with_capacity(with_capacity(has_normals));
+        let mut new_tangents: Vec<Vector4<f32>> = Vec::with_capacity(with_capacity(has_tangents));
+        let mut new_colors: Vec<Vector3<f32>> = Vec::with_capacity(with_capacity(has_colors));
+
+        let mut bundle_map: BundleMap = HashMap::with_capacity_and_hasher(vertex_estimate, Default::default());
+        let mut remaps: Vec<Vec<u32>> = Vec::with_capacity(uniques.len());
+        for unique in &uniques {
+            remaps.push(unique.iter().map(|&ib| {
+                if let Some(idx) = bundle_map.get(ib) { return *idx }
+                new_positions.push(self.positions[ib.pos_idx as usize]);
+                if has_tex_coords { new_tex_coords.push(self.tex_coords.get(ib.tex_idx as usize).copied().unwrap_or(Vector2::zero())) }
+                if has_normals { new_normals.push(self.normals.get(ib.nor_idx as usize).copied().unwrap_or(Vector3::zero())) }
+                if has_tangents { new_tangents.push(self.tangents.get(ib.nor_idx as usize).copied().unwrap_or(Vector4::zero())) }
+                if has_colors { new_colors.push(self.colors[ib.pos_idx as usize]) }
+                let new_idx = bundle_map.len() as u32;
+                bundle_map.insert(ib, new_idx);
+                new_idx
+            }).collect());
+        }
+
+        thread::scope(|s| {
+            range_indices.iter_mut().zip(&remaps).for_each(|(indices, remap)| {
+                s.spawn(move || indices.iter_mut().for_each(|i| *i = remap[*i as usize]));
+            });
+        });
+        let new_lines = range_indices.pop().unwrap_or_default();
+        let new_indices = range_indices.concat();
+        let new_triangles: Vec<Triangle> = self.indices.iter().zip(new_indices.chunks_exact(3))
+            .map(|(ib_tri, i)| Triangle::new(i[0], i[1], i[2], ib_tri.mat_idx))
+            .collect();
+        let new_triangle_groups: Vec<u32> = self.indices.iter().map(|ib_tri| ib_tri.group).collect();
 
         let mut sorted_materials: Vec<String> = vec![String::new(); self.materials.len()];
         self.materials.into_iter().for_each(|(k, v)| sorted_materials[v as usize] = k);
 
-        Model {
+        let mut sorted_groups: Vec<Option<ModelGroup>> = vec![None; self.groups.len()];
+        self.groups.into_iter().for_each(|(k, v)| sorted_groups[v as usize] = Some(k));
+
+        let mut model = Model {
             triangles: new_triangles,
             indices: new_indices,
             positions: new_positions,
             tex_coords: if has_tex_coords { Some(new_tex_coords) } else { None },
             normals: if has_normals { Some(new_normals) } else { None },
+            tangents: if has_tangents { Some(new_tangents) } else { None },
+            colors: if has_colors { Some(new_colors) } else { None },
+            lines: new_lines,
+            groups: sorted_groups.into_iter().flatten().collect(),
+            triangle_groups: new_triangle_groups,
             material_libs: self.material_libs.into_iter().collect(),
             materials: sorted_materials,
             bvh: None,
-        }
+        };
+        model.process(&self.processing);
+        Ok(model)
     }
 }
\ No newline at end of file
@@ -0,0 +1,315 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use crate::gl_wrapper::buffer::{IndexBuffer, VertexBuffer};
+use crate::gl_wrapper::framebuffer::Framebuffer;
+use crate::gl_wrapper::geometry_set::{GeometrySet, GeometrySetBuilder};
+use crate::gl_wrapper::shader::ShaderProgram;
+use crate::gl_wrapper::types::{TextureAttachment, TextureFormat};
+use crate::rendering::framebuffer_manager::FramebufferManager;
+use crate::rendering::gpu_profiler::GpuProfiler;
+use crate::util::error::RenderGraphError;
+use crate::window::window::Window;
+
+struct Output {
+    texture: String,
+    format: TextureFormat,
+    clear: Option<[f32; 4]>,
+}
+
+// a pass declares the textures it samples and renders to, the graph allocates them, clears the
+// outputs and binds the inputs to the sampler uniforms before the pass runs
+pub struct RenderPass {
+    name: String,
+    program: Arc<Mutex<ShaderProgram>>,
+    // sampler uniform and texture name
+    inputs: Vec<(String, String)>,
+    outputs: Vec<Output>,
+    depth: bool,
+    fullscreen: bool,
+    reads_buffers: Vec<String>,
+    writes_buffers: Vec<String>,
+}
+
+impl RenderPass {
+    // a pass without outputs renders to the window
+    pub fn new(name: &str, program: Arc<Mutex<ShaderProgram>>) -> Self {
+        Self {
+            name: name.to_owned(),
+            program,
+            inputs: vec![],
+            outputs: vec![],
+            depth: false,
+            fullscreen: false,
+            reads_buffers: vec![],
+            writes_buffers: vec![],
+        }
+    }
+
+    pub fn input(mut self, uniform: &str, texture: &str) -> Self {
+        self.inputs.push((uniform.to_owned(), texture.to_owned()));
+        self
+    }
+
+    // outputs are bound to consecutive color attachments in the order they are added
+    pub fn output(mut self, texture: &str, format: TextureFormat) -> Self {
+        self.outputs.push(Output { texture: texture.to_owned(), format, clear: None });
+        self
+    }
+
+    pub fn cleared_output(mut self, texture: &str, format: TextureFormat, clear: [f32; 4]) -> Self {
+        self.outputs.push(Output { texture: texture.to_owned(), format, clear: Some(clear) });
+        self
+    }
+
+    // a depth buffer that is cleared to the far plane and depth tested against
+    pub fn depth_buffer(mut self) -> Self {
+        self.depth = true;
+        self
+    }
+
+    // the graph draws a screen filling quad after the pass callback
+    pub fn fullscreen(mut self) -> Self {
+        self.fullscreen = true;
+        self
+    }
+
+    // storage buffers written by a pass are made visible to later passes reading them with a barrier
+    pub fn reads_buffer(mut self, name: &str) -> Self {
+        self.reads_buffers.push(name.to_owned());
+        self
+    }
+
+    pub fn writes_buffer(mut self, name: &str) -> Self {
+        self.writes_buffers.push(name.to_owned());
+        self
+    }
+}
+
+// handed to the pass callback, texture slots below free_texture_slot are taken by the pass inputs
+pub struct PassContext<'a> {
+    pub name: &'a str,
+    pub program: &'a mut ShaderProgram,
+    pub free_texture_slot: u32,
+}
+
+struct CompiledPass {
+    pass: RenderPass,
+    framebuffer: Option<usize>,
+    inputs: Vec<(String, usize)>,
+    barrier: bool,
+    enabled: bool,
+}
+
+pub struct RenderGraph {
+    passes: Vec<CompiledPass>,
+    textures: HashMap<String, usize>,
+    fbo_manager: FramebufferManager,
+    quad: (GeometrySet, IndexBuffer, VertexBuffer),
+}
+
+impl RenderGraph {
+    // runs the passes in dependency order, the callback sets the uniforms specific to a pass and
+    // issues its draw calls
+    pub fn execute(&self, run: impl FnMut(PassContext)) {
+        self.run_passes(None, run)
+    }
+
+    // like execute, timing each pass as a frame of the profiler
+    pub fn execute_profiled(&self, profiler: &mut GpuProfiler, run: impl FnMut(PassContext)) {
+        profiler.begin_frame();
+        self.run_passes(Some(profiler), run)
+    }
+
+    fn run_passes(&self, mut profiler: Option<&mut GpuProfiler>, mut run: impl FnMut(PassContext)) {
+        for compiled in &self.passes {
+            let pass = &compiled.pass;
+            // outputs of disabled passes are still cleared, so passes reading them see no results
+            if !compiled.enabled && compiled.framebuffer.is_none() { continue }
+            if compiled.enabled && compiled.barrier { unsafe { gl::MemoryBarrier(gl::SHADER_STORAGE_BARRIER_BIT) } }
+            // passes with outputs render at the render size, passes to the window are upscaled
+            match compiled.framebuffer {
+                Some(fbo) => self.fbo_manager.bind_fbo(fbo),
+                None => self.fbo_manager.bind_default(),
+            }
+            pass.outputs.iter().enumerate()
+                .filter_map(|(i, output)| output.clear.map(|clear| (i, clear)))
+                .for_each(|(i, clear)| Framebuffer::clear_color_attachment(i as u32, clear));
+            if !compiled.enabled { continue }
+            if let Some(profiler) = profiler.as_deref_mut() { profiler.begin(&pass.name) }
+            if pass.depth {
+                Framebuffer::clear_depth_attachment(1.0);
+                Framebuffer::enable_depth_test();
+            }
+
+            let mut program = pass.program.lock().unwrap();
+            program.bind();
+            for (slot, (uniform, texture)) in compiled.inputs.iter().enumerate() {
+                program.set_uniform_texture(uniform, self.fbo_manager.bind_tex_to_slot(*texture, slot as u32));
+            }
+            run(PassContext { name: &pass.name, program: &mut program, free_texture_slot: compiled.inputs.len() as u32 });
+            if pass.fullscreen { self.quad.0.draw() }
+
+            if pass.depth { Framebuffer::disable_depth_test() }
+            if let Some(profiler) = profiler.as_deref_mut() { profiler.end() }
+        }
+    }
+
+    // replaces the program of a pass, e.g. with another variant
+    pub fn set_program(&mut self, pass: &str, program: Arc<Mutex<ShaderProgram>>) {
+        if let Some(compiled) = self.passes.iter_mut().find(|compiled| compiled.pass.name == pass) {
+            compiled.pass.program = program;
+        }
+    }
+
+    // disabled passes are skipped but their cleared outputs are still cleared, passes reading them
+    // are still executed
+    pub fn set_pass_enabled(&mut self, pass: &str, enabled: bool) {
+        if let Some(compiled) = self.passes.iter_mut().find(|compiled| compiled.pass.name == pass) {
+            compiled.enabled = enabled;
+        }
+    }
+
+    pub fn bind_texture(&self, name: &str, slot: u32) -> Option<u32> {
+        self.textures.get(name).map(|texture| self.fbo_manager.bind_tex_to_slot(*texture, slot))
+    }
+
+    // the texel at render size coordinates with the origin in the lower left corner
+    pub fn read_pixel(&self, name: &str, x: u32, y: u32) -> Option<[f32; 4]> {
+        self.textures.get(name).map(|texture| self.fbo_manager.read_tex_pixel(*texture, x, y))
+    }
+
+    // the size and rgba texels of a whole texture, rows from the bottom up
+    pub fn read_texture(&self, name: &str) -> Option<(u32, u32, Vec<f32>)> {
+        self.textures.get(name).map(|texture| self.fbo_manager.read_tex_pixels(*texture))
+    }
+
+    pub fn pass_names(&self) -> Vec<&str> {
+        self.passes.iter().map(|compiled| compiled.pass.name.as_str()).collect()
+    }
+
+    // resizes all textures to the window
+    pub fn resize(&mut self) {
+        self.fbo_manager.update_buffers();
+    }
+
+    // the textures are rendered at the window size times the scale, below 1 traces fewer rays and
+    // above 1 supersamples the color, which the display pass box filters down to the window
+    pub fn set_resolution_scale(&mut self, scale: f32) {
+        self.fbo_manager.set_resolution_scale(scale);
+    }
+
+    pub fn resolution_scale(&self) -> f32 {
+        self.fbo_manager.resolution_scale()
+    }
+
+    pub fn render_size(&self) -> (u32, u32) {
+        self.fbo_manager.render_size()
+    }
+
+    // the texel of the textures under a framebuffer position, which has its origin in the upper
+    // left corner like cursor positions
+    pub fn texel_at(&self, (x, y): (f32, f32)) -> (u32, u32) {
+        let (window_width, window_height) = self.fbo_manager.window_size();
+        let (width, height) = self.fbo_manager.render_size();
+        let texel_x = (x / window_width.max(1) as f32 * width as f32) as u32;
+        let texel_y = (y / window_height.max(1) as f32 * height as f32) as u32;
+        (texel_x.min(width - 1), height.saturating_sub(texel_y + 1))
+    }
+}
+
+#[derive(Default)]
+pub struct RenderGraphBuilder {
+    passes: Vec<RenderPass>,
+}
+
+impl RenderGraphBuilder {
+    pub fn new() -> Self {
+        Self { passes: vec![] }
+    }
+
+    pub fn add_pass(mut self, pass: RenderPass) -> Self {
+        self.passes.push(pass);
+        self
+    }
+
+    pub fn build(self, window: Arc<Mutex<Window>>) -> Result<RenderGraph, RenderGraphError> {
+        let order = self.sort_passes()?;
+        let mut passes: Vec<Option<RenderPass>> = self.passes.into_iter().map(Some).collect();
+
+        let mut fbo_manager = FramebufferManager::new(window);
+        let mut textures: HashMap<String, usize> = HashMap::new();
+        let mut written_buffers: HashSet<String> = HashSet::new();
+        let mut compiled = Vec::with_capacity(order.len());
+        for idx in order {
+            let pass = passes[idx].take().unwrap();
+            let framebuffer = if pass.outputs.is_empty() { None } else {
+                let fbo = fbo_manager.new_framebuffer();
+                for (i, output) in pass.outputs.iter().enumerate() {
+                    let texture = fbo_manager.attach_texture(output.format.clone(), TextureAttachment::Color(i as u32), true);
+                    textures.insert(output.texture.clone(), texture);
+                }
+                if pass.depth { fbo_manager.attach_renderbuffer(TextureFormat::Depth, TextureAttachment::Depth, false); }
+                Some(fbo)
+            };
+            // every texture is written by a single pass, which the sort placed before its readers
+            let inputs = pass.inputs.iter()
+                .map(|(uniform, texture)| (uniform.clone(), textures[texture]))
+                .collect();
+            let barrier = pass.reads_buffers.iter().any(|buffer| written_buffers.contains(buffer));
+            if barrier { written_buffers.clear() }
+            written_buffers.extend(pass.writes_buffers.iter().cloned());
+            compiled.push(CompiledPass { pass, framebuffer, inputs, barrier, enabled: true });
+        }
+        fbo_manager.build_framebuffers();
+        Framebuffer::bind_default();
+
+        Ok(RenderGraph { passes: compiled, textures, fbo_manager, quad: GeometrySetBuilder::create_square_geometry() })
+    }
+
+    // orders the passes so that every texture is written before it is read, passes that do not
+    // depend on each other keep the order they were added in
+    fn sort_passes(&self) -> Result<Vec<usize>, RenderGraphError> {
+        let mut writers: HashMap<&str, usize> = HashMap::new();
+        for (idx, pass) in self.passes.iter().enumerate() {
+            if self.passes[..idx].iter().any(|other| other.name == pass.name) {
+                return Err(RenderGraphError::DuplicatePass(pass.name.clone()));
+            }
+            for output in &pass.outputs {
+                if writers.insert(&output.texture, idx).is_some() {
+                    return Err(RenderGraphError::DuplicateOutput { texture: output.texture.clone(), pass: pass.name.clone() });
+                }
+            }
+        }
+
+        let mut dependencies: Vec<Vec<usize>> = Vec::with_capacity(self.passes.len());
+        for (idx, pass) in self.passes.iter().enumerate() {
+            let mut pass_dependencies = vec![];
+            for (_, texture) in &pass.inputs {
+                match writers.get(texture.as_str()) {
+                    Some(writer) if *writer == idx =>
+                        return Err(RenderGraphError::FeedbackLoop { texture: texture.clone(), pass: pass.name.clone() }),
+                    Some(writer) => pass_dependencies.push(*writer),
+                    None => return Err(RenderGraphError::UnknownTexture { texture: texture.clone(), pass: pass.name.clone() }),
+                }
+            }
+            dependencies.push(pass_dependencies);
+        }
+
+        let mut order = Vec::with_capacity(self.passes.len());
+        let mut scheduled = vec![false; self.passes.len()];
+        while order.len() < self.passes.len() {
+            let next = (0..self.passes.len())
+                .find(|idx| !scheduled[*idx] && dependencies[*idx].iter().all(|dependency| scheduled[*dependency]));
+            match next {
+                Some(idx) => {
+                    scheduled[idx] = true;
+                    order.push(idx);
+                }
+                None => return Err(RenderGraphError::Cycle(
+                    (0..self.passes.len()).filter(|idx| !scheduled[*idx]).map(|idx| self.passes[idx].name.clone()).collect()
+                )),
+            }
+        }
+        Ok(order)
+    }
+}
@@ -0,0 +1,93 @@
+use cgmath::Vector3;
+use crate::rendering::capture::{CaptureFormat, CaptureSource};
+use crate::rendering::debug_view::DebugView;
+use crate::window::window::DisplayMode;
+
+// settings that can be changed while running, from the gui or with shortcuts
+pub struct RenderSettings {
+    pub show_gui: bool,
+
+    pub display_mode: DisplayMode,
+    pub vsync: bool,
+    // size of the rendered images relative to the window, they are upscaled for display
+    pub resolution_scale: f32,
+
+    // index of the active camera controller
+    pub camera_controller: usize,
+    pub movement_speed: f32,
+    pub mouse_sensitivity: f32,
+
+    // the light circles around the y axis
+    pub light_radius: f32,
+    pub light_height: f32,
+    // radians per second
+    pub light_speed: f32,
+    pub light_color: [f32; 3],
+    pub light_intensity: f32,
+
+    pub ambient_strength: f32,
+    pub reflection_strength: f32,
+
+    // replaces the illumination model of all materials
+    pub illum_override: Option<i32>,
+    // shades all materials with a white diffuse color, to look at the lighting only
+    pub white_diffuse: bool,
+
+    pub trace_shadows: bool,
+    pub trace_reflections: bool,
+    pub trace_ambient: bool,
+
+    pub debug_view: DebugView,
+    pub bvh_overlay: bool,
+
+    pub capture_source: CaptureSource,
+    pub capture_format: CaptureFormat,
+    // set to take a screenshot at the end of the frame
+    pub screenshot_requested: bool,
+    pub recording: bool,
+    // frames per second of the recorded video, the timestep the window runs at while recording
+    pub record_fps: u32,
+}
+
+impl RenderSettings {
+    pub fn light_position(&self, angle: f32) -> Vector3<f32> {
+        Vector3::new(angle.sin() * self.light_radius, self.light_height, angle.cos() * self.light_radius)
+    }
+
+    pub fn light_color(&self) -> Vector3<f32> {
+        Vector3::from(self.light_color) * self.light_intensity
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            show_gui: true,
+            display_mode: DisplayMode::Windowed,
+            vsync: false,
+            resolution_scale: 1.0,
+            camera_controller: 0,
+            movement_speed: 1.0,
+            mouse_sensitivity: 8.0,
+            light_radius: 20.0,
+            light_height: 20.0,
+            light_speed: 1.0,
+            light_color: [1.0, 1.0, 1.0],
+            light_intensity: 1.0,
+            ambient_strength: 1.0,
+            reflection_strength: 1.0,
+            illum_override: None,
+            white_diffuse: false,
+            trace_shadows: true,
+            trace_reflections: true,
+            trace_ambient: true,
+            debug_view: DebugView::Color,
+            bvh_overlay: false,
+            capture_source: CaptureSource::Window,
+            capture_format: CaptureFormat::Png,
+            screenshot_requested: false,
+            recording: false,
+            record_fps: 30,
+        }
+    }
+}
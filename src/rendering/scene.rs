@@ -0,0 +1,139 @@
+use std::sync::{Arc, Mutex};
+use crate::gl_wrapper::buffer::{IndexBuffer, ShaderStorageBuffer, VertexBuffer};
+use crate::gl_wrapper::geometry_set::{GeometrySet, GeometrySetBuilder};
+use crate::rendering::material_buffer::MaterialBuffer;
+use crate::rendering::model::Model;
+use crate::raytracing::bvh::BVH;
+use crate::raytracing::types::AABB;
+use crate::resource::resource_manager::ResourceManager;
+use crate::util::error::ResourceError;
+
+pub struct SceneModel {
+    model: Arc<Mutex<Model>>,
+    geometry: GeometrySet,
+    _ibo: IndexBuffer,
+    _vbos: Vec<VertexBuffer>,
+    material_offset: u32,
+    triangle_material_ssbo: ShaderStorageBuffer,
+}
+
+impl SceneModel {
+    pub fn model(&self) -> &Arc<Mutex<Model>> { &self.model }
+
+    // index of the model's first material in the scene's material buffer
+    pub fn material_offset(&self) -> u32 { self.material_offset }
+
+    // scene material index of every triangle in draw order, looked up with gl_PrimitiveID
+    pub fn triangle_material_buffer(&self) -> &ShaderStorageBuffer { &self.triangle_material_ssbo }
+
+    pub fn draw(&self) { self.geometry.draw() }
+}
+
+// every model is rasterized with its own draw call, while ray tracing uses a single model merging
+// all of them, so that one bvh covers the whole scene
+pub struct Scene {
+    models: Vec<SceneModel>,
+    trace_model: Model,
+    material_buffer: MaterialBuffer,
+
+    node_ssbo: ShaderStorageBuffer,
+    triangle_ssbo: ShaderStorageBuffer,
+    position_ssbo: ShaderStorageBuffer,
+    tex_coord_ssbo: ShaderStorageBuffer,
+    normal_ssbo: ShaderStorageBuffer,
+}
+
+impl Scene {
+    pub fn models(&self) -> &Vec<SceneModel> { &self.models }
+    pub fn trace_model(&self) -> &Model { &self.trace_model }
+    pub fn material_buffer(&self) -> &MaterialBuffer { &self.material_buffer }
+
+    pub fn node_buffer(&self) -> &ShaderStorageBuffer { &self.node_ssbo }
+    pub fn triangle_buffer(&self) -> &ShaderStorageBuffer { &self.triangle_ssbo }
+    pub fn position_buffer(&self) -> &ShaderStorageBuffer { &self.position_ssbo }
+    pub fn tex_coord_buffer(&self) -> &ShaderStorageBuffer { &self.tex_coord_ssbo }
+    pub fn normal_buffer(&self) -> &ShaderStorageBuffer { &self.normal_ssbo }
+
+    pub fn has_tex_coords(&self) -> bool { self.trace_model.has_tex_coords() }
+    pub fn has_normals(&self) -> bool { self.trace_model.has_normals() }
+
+    pub fn bvh(&self) -> &BVH { self.trace_model.get_bvh().unwrap() }
+
+    // the root node of the bvh bounds the whole scene, none if the scene has no triangles
+    pub fn bounds(&self) -> Option<AABB> {
+        if self.trace_model.triangles().is_empty() { return None }
+        self.bvh().data().first().map(|node| *node.bounds())
+    }
+
+    // builds the scene again from the same models, after they or their materials were reloaded
+    pub fn rebuild(&self, resource_manager: &mut ResourceManager) -> Result<Scene, ResourceError> {
+        self.models.iter()
+            .fold(SceneBuilder::new(), |builder, model| builder.add_model(model.model.clone()))
+            .build(resource_manager)
+    }
+}
+
+pub struct SceneBuilder {
+    models: Vec<Arc<Mutex<Model>>>,
+}
+
+impl Default for SceneBuilder {
+    fn default() -> Self { Self::new() }
+}
+
+impl SceneBuilder {
+    pub fn new() -> Self {
+        Self { models: vec![] }
+    }
+
+    pub fn add_model(mut self, model: Arc<Mutex<Model>>) -> Self {
+        self.models.push(model);
+        self
+    }
+
+    pub fn build(self, resource_manager: &mut ResourceManager) -> Result<Scene, ResourceError> {
+        let mut trace_model = {
+            let guards: Vec<_> = self.models.iter().map(|model| model.lock().unwrap()).collect();
+            Model::merged(&guards.iter().map(|guard| &**guard).collect::<Vec<_>>())
+        };
+        trace_model.build_bvh();
+        let material_buffer = MaterialBuffer::new(trace_model.get_materials(), resource_manager)?;
+
+        let mut material_offset = 0;
+        let models = self.models.into_iter().map(|model| {
+            let (geometry, ibo, vbos) = GeometrySetBuilder::from_model(model.clone());
+            let model_guard = model.lock().unwrap();
+            let triangle_materials: Vec<u32> = model_guard.triangles().iter()
+                .map(|tri| tri.mat_idx + material_offset)
+                .collect();
+            let triangle_material_ssbo = ShaderStorageBuffer::new();
+            triangle_material_ssbo.buffer_data(&triangle_materials);
+            let scene_model_offset = material_offset;
+            material_offset += model_guard.material_slot_count() as u32;
+            drop(model_guard);
+            SceneModel { geometry, _ibo: ibo, _vbos: vbos, material_offset: scene_model_offset, triangle_material_ssbo, model }
+        }).collect();
+
+        let node_ssbo = ShaderStorageBuffer::new();
+        let triangle_ssbo = ShaderStorageBuffer::new();
+        let position_ssbo = ShaderStorageBuffer::new();
+        let tex_coord_ssbo = ShaderStorageBuffer::new();
+        let normal_ssbo = ShaderStorageBuffer::new();
+        node_ssbo.buffer_data(trace_model.get_bvh().unwrap().data());
+        triangle_ssbo.buffer_data(trace_model.triangles());
+        position_ssbo.buffer_data(trace_model.positions());
+        if let Some(tex_coords) = trace_model.tex_coords() { tex_coord_ssbo.buffer_data(tex_coords) }
+        if let Some(normals) = trace_model.normals() { normal_ssbo.buffer_data(normals) }
+
+        Ok(Scene {
+            models,
+            trace_model,
+            material_buffer,
+            node_ssbo,
+            triangle_ssbo,
+            position_ssbo,
+            tex_coord_ssbo,
+            normal_ssbo,
+        })
+    }
+}
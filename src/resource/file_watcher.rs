@@ -0,0 +1,35 @@
+use std::collections::HashMap;
+use std::time::SystemTime;
+use crate::resource::resource::Resource;
+
+// polls the modification times of the files loaded from a resource directory
+pub struct FileWatcher {
+    modified: HashMap<String, Option<SystemTime>>,
+}
+
+impl Default for FileWatcher {
+    fn default() -> Self { Self::new() }
+}
+
+impl FileWatcher {
+    pub fn new() -> Self {
+        Self { modified: HashMap::new() }
+    }
+
+    pub fn watch(&mut self, resource: &Resource, name: &str) {
+        self.modified.insert(name.to_owned(), resource.modified(name));
+    }
+
+    // names of the watched files that were modified, created or deleted since the last call.
+    // deleted files are not reported, as there is nothing to reload until they reappear
+    pub fn changed(&mut self, resource: &Resource) -> Vec<String> {
+        let mut changed: Vec<String> = self.modified.iter_mut().filter_map(|(name, modified)| {
+            let current = resource.modified(name);
+            if current == *modified { return None }
+            *modified = current;
+            current.map(|_| name.clone())
+        }).collect();
+        changed.sort();
+        changed
+    }
+}
@@ -0,0 +1,804 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use cgmath::{InnerSpace, Matrix, Matrix3, Matrix4, Quaternion, SquareMatrix, Vector2, Vector3, Vector4, Zero};
+use crate::raytracing::types::IndexBundle;
+use crate::rendering::material::{Material, MaterialLibBuilder, TextureChannel, TextureMap};
+use crate::rendering::mesh_processing::MeshProcessing;
+use crate::rendering::model::{Model, ModelBuilder};
+use crate::resource::resource::Resource;
+use crate::util::error::{GltfError, ResourceError, ResourceParseError};
+use crate::util::json::Json;
+
+const GLB_MAGIC: u32 = 0x46546C67;
+const GLB_CHUNK_JSON: u32 = 0x4E4F534A;
+const GLB_CHUNK_BIN: u32 = 0x004E4942;
+
+const MODE_TRIANGLES: usize = 4;
+const MODE_TRIANGLE_STRIP: usize = 5;
+const MODE_TRIANGLE_FAN: usize = 6;
+
+// components an accessor may have, accessors without a buffer view are allocated from the count
+// alone, which would otherwise let a file request any amount of memory
+const MAX_ACCESSOR_COMPONENTS: usize = 1 << 28;
+
+pub struct GltfData {
+    pub model: Model,
+    pub materials: Vec<(String, Material)>,
+    pub images: Vec<(String, Vec<u8>)>,
+    // the buffers and images read from files next to the document, the model has to be loaded
+    // again when they change
+    pub files: Vec<String>,
+}
+
+struct Primitive {
+    positions: Vec<f32>,
+    normals: Option<Vec<f32>>,
+    tex_coords: Option<Vec<f32>>,
+    tangents: Option<Vec<f32>>,
+    triangles: Vec<[u32; 3]>,
+    material: Option<usize>,
+}
+
+struct AccessorView<'b> {
+    data: &'b [u8],
+    offset: usize,
+    stride: usize,
+    count: usize,
+    components: usize,
+    component_type: usize,
+    component_size: usize,
+    normalized: bool,
+}
+
+impl<'b> AccessorView<'b> {
+    fn read<T: Default + Clone>(&self, convert: impl Fn(usize, bool, &[u8]) -> T) -> Vec<T> {
+        if self.data.is_empty() { return vec![T::default(); self.count * self.components] }
+        (0..self.count).flat_map(|e| (0..self.components).map(move |c| (e, c))).map(|(e, c)| {
+            let at = self.offset + e * self.stride + c * self.component_size;
+            convert(self.component_type, self.normalized, &self.data[at..at + self.component_size])
+        }).collect()
+    }
+}
+
+pub struct GltfParser<'a> {
+    name: &'a str,
+    res: &'a Resource,
+    processing: MeshProcessing,
+    json: Json,
+    buffers: Vec<Vec<u8>>,
+    image_names: HashMap<usize, String>,
+    images: Vec<(String, Vec<u8>)>,
+    files: Vec<String>,
+}
+
+impl<'a> GltfParser<'a> {
+    pub fn parse(data: Vec<u8>, name: &'a str, res: &'a Resource, processing: MeshProcessing) -> Result<GltfData, ResourceError> {
+        Self::parse_gltf(data, name, res, processing).map_err(|e| ResourceError::gltf_err(e, name))
+    }
+
+    fn parse_gltf(data: Vec<u8>, name: &'a str, res: &'a Resource, processing: MeshProcessing) -> Result<GltfData, GltfError> {
+        let (json, bin) = if read_u32(&data, 0) == Some(GLB_MAGIC) { Self::split_glb(&data)? }
+        else { (std::str::from_utf8(&data).map_err(|_| GltfError::InvalidGlb("json is not valid utf-8".to_owned()))?, None) };
+
+        let mut parser = Self {
+            name, res, processing,
+            json: Json::parse(json).map_err(GltfError::JsonError)?,
+            buffers: vec![],
+            image_names: HashMap::new(),
+            images: vec![],
+            files: vec![],
+        };
+        parser.load_buffers(bin)?;
+
+        let primitives = parser.collect_primitives()?;
+        let model = parser.build_model(&primitives)?;
+
+        let mut used_materials: Vec<Option<usize>> = primitives.iter().map(|p| p.material).collect();
+        used_materials.sort();
+        used_materials.dedup();
+        let mut lib_builder = MaterialLibBuilder::new();
+        used_materials.into_iter()
+            .map(|mat| parser.add_material(&mut lib_builder, mat))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(GltfData { model, materials: lib_builder.build(), images: parser.images, files: parser.files })
+    }
+
+    fn split_glb(data: &[u8]) -> Result<(&str, Option<Vec<u8>>), GltfError> {
+        let invalid = |msg: &str| GltfError::InvalidGlb(msg.to_owned());
+        if read_u32(data, 4) != Some(2) { return Err(invalid("unsupported glb version")) }
+        let length = (read_u32(data, 8).ok_or(invalid("truncated header"))? as usize).min(data.len());
+
+        let mut json = None;
+        let mut bin = None;
+        let mut offset = 12;
+        while offset + 8 <= length {
+            let chunk_length = read_u32(data, offset).unwrap() as usize;
+            let chunk_type = read_u32(data, offset + 4).unwrap();
+            let chunk = data.get(offset + 8..offset + 8 + chunk_length).ok_or(invalid("truncated chunk"))?;
+            match chunk_type {
+                GLB_CHUNK_JSON => json = Some(std::str::from_utf8(chunk).map_err(|_| invalid("json chunk is not valid utf-8"))?),
+                GLB_CHUNK_BIN => bin = Some(chunk.to_vec()),
+                _ => {}
+            }
+            offset += 8 + ((chunk_length + 3) & !3);
+        }
+        Ok((json.ok_or(invalid("missing json chunk"))?, bin))
+    }
+
+    fn relative_path(&self, uri: &str) -> String {
+        let uri = percent_decode(uri);
+        match Path::new(self.name).parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.join(uri).to_string_lossy().into_owned(),
+            _ => uri,
+        }
+    }
+
+    fn read_uri(&mut self, uri: &str) -> Result<Vec<u8>, GltfError> {
+        if let Some(data) = uri.strip_prefix("data:") {
+            let (_, payload) = data.split_once(";base64,").ok_or(GltfError::UnsupportedUri(uri.chars().take(40).collect()))?;
+            decode_base64(payload)
+        } else {
+            let path = self.relative_path(uri);
+            let data = self.res.read_bytes(&path).map_err(|_| GltfError::UnsupportedUri(path.clone()))?;
+            if !self.files.contains(&path) { self.files.push(path) }
+            Ok(data)
+        }
+    }
+
+    fn load_buffers(&mut self, mut bin: Option<Vec<u8>>) -> Result<(), GltfError> {
+        let uris: Vec<Option<String>> = array(&self.json, "buffers").iter()
+            .map(|buffer| buffer.get("uri").and_then(Json::as_str).map(str::to_owned))
+            .collect();
+        for uri in uris {
+            let buffer = match uri {
+                Some(uri) => self.read_uri(&uri)?,
+                None => bin.take().ok_or(GltfError::MissingField("buffers.uri".to_owned()))?,
+            };
+            self.buffers.push(buffer);
+        }
+        Ok(())
+    }
+
+    fn element(&self, kind: &str, idx: usize) -> Result<&Json, GltfError> {
+        self.json.get(kind).and_then(|a| a.index(idx))
+            .ok_or(GltfError::InvalidIndex { kind: kind.to_owned(), idx })
+    }
+
+    // the node hierarchy has to be a set of disjoint trees, nodes with several parents are rejected
+    // instead of being expanded again for every path to them, and cycles are found on the path
+    fn collect_primitives(&self) -> Result<Vec<Primitive>, GltfError> {
+        let nodes = array(&self.json, "nodes");
+        let mut has_parent = vec![false; nodes.len()];
+        for child in nodes.iter().flat_map(|n| indices(n, "children")) {
+            let seen = has_parent.get_mut(child).ok_or(GltfError::InvalidIndex { kind: "nodes".to_owned(), idx: child })?;
+            if *seen { return Err(GltfError::Unsupported(format!("node {} with several parents", child))) }
+            *seen = true;
+        }
+        let roots = match self.json.get("scene").and_then(Json::as_usize).or(if array(&self.json, "scenes").is_empty() { None } else { Some(0) }) {
+            Some(scene) => indices(self.element("scenes", scene)?, "nodes"),
+            None => (0..nodes.len()).filter(|n| !has_parent[*n]).collect(),
+        };
+
+        let mut primitives = vec![];
+        let mut stack: Vec<(usize, Matrix4<f32>, usize)> = roots.into_iter().map(|n| (n, Matrix4::identity(), 0)).collect();
+        // the nodes from the root to the current one
+        let mut path: Vec<usize> = vec![];
+        let mut on_path: HashSet<usize> = HashSet::new();
+        while let Some((node_idx, parent, depth)) = stack.pop() {
+            path.drain(depth..).for_each(|n| { on_path.remove(&n); });
+            if !on_path.insert(node_idx) { return Err(GltfError::Unsupported("cyclic node hierarchy".to_owned())) }
+            path.push(node_idx);
+            let node = self.element("nodes", node_idx)?;
+            let transform = parent * node_transform(node);
+            if let Some(mesh) = node.get("mesh").and_then(Json::as_usize) {
+                for primitive in array(self.element("meshes", mesh)?, "primitives") {
+                    if let Some(primitive) = self.read_primitive(primitive, &transform)? {
+                        primitives.push(primitive);
+                    }
+                }
+            }
+            indices(node, "children").into_iter().for_each(|child| stack.push((child, transform, depth + 1)));
+        }
+        Ok(primitives)
+    }
+
+    fn read_primitive(&self, primitive: &Json, transform: &Matrix4<f32>) -> Result<Option<Primitive>, GltfError> {
+        let mode = primitive.get("mode").and_then(Json::as_usize).unwrap_or(MODE_TRIANGLES);
+        if !matches!(mode, MODE_TRIANGLES | MODE_TRIANGLE_STRIP | MODE_TRIANGLE_FAN) { return Ok(None) }
+
+        let attributes = primitive.get("attributes").ok_or(GltfError::MissingField("primitive.attributes".to_owned()))?;
+        let attribute = |name: &str| attributes.get(name).and_then(Json::as_usize);
+
+        let position_accessor = attribute("POSITION").ok_or(GltfError::MissingField("attributes.POSITION".to_owned()))?;
+        let (mut positions, _) = self.read_accessor(position_accessor, 3)?;
+        let vertex_count = positions.len() / 3;
+
+        let normal_matrix = Matrix3::from_cols(transform.x.truncate(), transform.y.truncate(), transform.z.truncate())
+            .invert().unwrap_or(Matrix3::identity()).transpose();
+        positions.chunks_mut(3).for_each(|p| {
+            let t = transform * Vector4::new(p[0], p[1], p[2], 1.0);
+            p.copy_from_slice(&[t.x, t.y, t.z]);
+        });
+
+        let normals = attribute("NORMAL").map(|a| self.read_accessor(a, 3)).transpose()?.map(|(mut normals, _)| {
+            normals.chunks_mut(3).for_each(|n| {
+                let t = (normal_matrix * Vector3::new(n[0], n[1], n[2])).normalize();
+                n.copy_from_slice(&[t.x, t.y, t.z]);
+            });
+            normals
+        });
+        let tangents = attribute("TANGENT").map(|a| self.read_accessor(a, 4)).transpose()?.map(|(mut tangents, _)| {
+            let flip = if transform.determinant() < 0.0 { -1.0 } else { 1.0 };
+            tangents.chunks_mut(4).for_each(|t| {
+                let v = (transform * Vector4::new(t[0], t[1], t[2], 0.0)).truncate().normalize();
+                // texture coordinates are flipped vertically on load, which mirrors the bitangent
+                t.copy_from_slice(&[v.x, v.y, v.z, -t[3] * flip]);
+            });
+            tangents
+        });
+        let tex_coords = attribute("TEXCOORD_0").map(|a| self.read_accessor(a, 2)).transpose()?.map(|(mut uvs, _)| {
+            uvs.chunks_mut(2).for_each(|uv| uv[1] = 1.0 - uv[1]);
+            uvs
+        });
+
+        let vertex_indices = match primitive.get("indices").and_then(Json::as_usize) {
+            Some(accessor) => self.read_indices(accessor)?,
+            None => (0..vertex_count as u32).collect(),
+        };
+        if let Some(idx) = vertex_indices.iter().find(|i| **i as usize >= vertex_count) {
+            return Err(GltfError::InvalidIndex { kind: "vertex".to_owned(), idx: *idx as usize });
+        }
+
+        let mut triangles: Vec<[u32; 3]> = match mode {
+            MODE_TRIANGLES => vertex_indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]]).collect(),
+            MODE_TRIANGLE_STRIP => vertex_indices.windows(3).enumerate()
+                .map(|(i, t)| if i % 2 == 0 { [t[0], t[1], t[2]] } else { [t[1], t[0], t[2]] }).collect(),
+            _ => (1..vertex_indices.len().saturating_sub(1))
+                .map(|i| [vertex_indices[0], vertex_indices[i], vertex_indices[i + 1]]).collect(),
+        };
+        if transform.determinant() < 0.0 {
+            triangles.iter_mut().for_each(|t| t.swap(1, 2));
+        }
+
+        Ok(Some(Primitive {
+            positions, normals, tex_coords, tangents, triangles,
+            material: primitive.get("material").and_then(Json::as_usize),
+        }))
+    }
+
+    fn accessor_view(&self, idx: usize, expected_components: usize) -> Result<AccessorView<'_>, GltfError> {
+        let accessor = self.element("accessors", idx)?;
+        if accessor.get("sparse").is_some() { return Err(GltfError::Unsupported("sparse accessors".to_owned())) }
+
+        let count = accessor.get("count").and_then(Json::as_usize).ok_or(GltfError::InvalidAccessor(idx))?;
+        let components = match accessor.get("type").and_then(Json::as_str) {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") | Some("MAT2") => 4,
+            Some("MAT3") => 9,
+            Some("MAT4") => 16,
+            _ => return Err(GltfError::InvalidAccessor(idx)),
+        };
+        if components != expected_components { return Err(GltfError::InvalidAccessor(idx)) }
+        let component_type = accessor.get("componentType").and_then(Json::as_usize).ok_or(GltfError::InvalidAccessor(idx))?;
+        let component_size = match component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            _ => return Err(GltfError::InvalidAccessor(idx)),
+        };
+        let normalized = accessor.get("normalized").and_then(Json::as_bool).unwrap_or(false);
+        if count.checked_mul(components).is_none_or(|total| total > MAX_ACCESSOR_COMPONENTS) {
+            return Err(GltfError::InvalidAccessor(idx));
+        }
+
+        let mut view = AccessorView {
+            data: &[], offset: 0, stride: 0,
+            count, components, component_type, component_size, normalized,
+        };
+        let view_idx = match accessor.get("bufferView").and_then(Json::as_usize) {
+            Some(view) => view,
+            None => return Ok(view),
+        };
+        let buffer_view = self.element("bufferViews", view_idx)?;
+        let buffer_idx = buffer_view.get("buffer").and_then(Json::as_usize).ok_or(GltfError::MissingField("bufferView.buffer".to_owned()))?;
+        view.data = self.buffers.get(buffer_idx).ok_or(GltfError::InvalidIndex { kind: "buffers".to_owned(), idx: buffer_idx })?;
+
+        let element_size = components * component_size;
+        view.stride = buffer_view.get("byteStride").and_then(Json::as_usize).unwrap_or(element_size);
+        view.offset = buffer_view.get("byteOffset").and_then(Json::as_usize).unwrap_or(0)
+            .checked_add(accessor.get("byteOffset").and_then(Json::as_usize).unwrap_or(0))
+            .ok_or(GltfError::InvalidAccessor(idx))?;
+        if count > 0 {
+            let end = view.stride.checked_mul(count - 1)
+                .and_then(|last| last.checked_add(view.offset))
+                .and_then(|last| last.checked_add(element_size));
+            if end.is_none_or(|end| end > view.data.len()) { return Err(GltfError::InvalidAccessor(idx)) }
+        }
+        Ok(view)
+    }
+
+    fn read_accessor(&self, idx: usize, expected_components: usize) -> Result<(Vec<f32>, usize), GltfError> {
+        let view = self.accessor_view(idx, expected_components)?;
+        Ok((view.read(|component_type, normalized, b| match (component_type, normalized) {
+            (5120, false) => b[0] as i8 as f32,
+            (5120, true) => (b[0] as i8 as f32 / 127.0).max(-1.0),
+            (5121, false) => b[0] as f32,
+            (5121, true) => b[0] as f32 / 255.0,
+            (5122, false) => i16::from_le_bytes([b[0], b[1]]) as f32,
+            (5122, true) => (i16::from_le_bytes([b[0], b[1]]) as f32 / 32767.0).max(-1.0),
+            (5123, false) => u16::from_le_bytes([b[0], b[1]]) as f32,
+            (5123, true) => u16::from_le_bytes([b[0], b[1]]) as f32 / 65535.0,
+            (5125, _) => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32,
+            _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        }), view.components))
+    }
+
+    fn read_indices(&self, idx: usize) -> Result<Vec<u32>, GltfError> {
+        let view = self.accessor_view(idx, 1)?;
+        if !matches!(view.component_type, 5121 | 5123 | 5125) { return Err(GltfError::InvalidAccessor(idx)) }
+        Ok(view.read(|component_type, _, b| match component_type {
+            5121 => b[0] as u32,
+            5123 => u16::from_le_bytes([b[0], b[1]]) as u32,
+            _ => u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        }))
+    }
+
+    fn build_model(&self, primitives: &[Primitive]) -> Result<Model, GltfError> {
+        let any_normals = primitives.iter().any(|p| p.normals.is_some());
+        let any_tex_coords = primitives.iter().any(|p| p.tex_coords.is_some());
+        let any_tangents = primitives.iter().any(|p| p.tangents.is_some());
+
+        let mut model_builder = ModelBuilder::new();
+        model_builder.set_mesh_processing(self.processing);
+        let mut base = 0;
+        for primitive in primitives {
+            let vertex_count = primitive.positions.len() / 3;
+            model_builder.add_material(self.material_name(primitive.material));
+
+            primitive.positions.chunks(3).for_each(|p| model_builder.add_position(Vector3::new(p[0], p[1], p[2])));
+            // tangents are only kept with normals, primitives without them get generated ones
+            if any_normals || any_tangents {
+                let normals = primitive.normals.clone().unwrap_or_else(|| vertex_normals(primitive));
+                normals.chunks(3).for_each(|n| model_builder.add_normal(Vector3::new(n[0], n[1], n[2])));
+            }
+            if any_tex_coords {
+                match &primitive.tex_coords {
+                    Some(uvs) => uvs.chunks(2).for_each(|t| model_builder.add_tex_coord(Vector2::new(t[0], t[1]))),
+                    None => (0..vertex_count).for_each(|_| model_builder.add_tex_coord(Vector2::zero())),
+                }
+            }
+            if any_tangents {
+                match &primitive.tangents {
+                    Some(tangents) => tangents.chunks(4).for_each(|t| model_builder.add_tangent(Vector4::new(t[0], t[1], t[2], t[3]))),
+                    None => (0..vertex_count).for_each(|_| model_builder.add_tangent(Vector4::new(1.0, 0.0, 0.0, 1.0))),
+                }
+            }
+
+            let bundle = |i: u32| {
+                let idx = (base + i as usize) as i32;
+                IndexBundle { pos_idx: idx, tex_idx: idx, nor_idx: idx }
+            };
+            primitive.triangles.iter().for_each(|t| {
+                model_builder.add_indices(bundle(t[0]), bundle(t[1]), bundle(t[2]), model_builder.get_current_mat());
+            });
+            base += vertex_count;
+        }
+        model_builder.build().map_err(|e| match e {
+            ResourceParseError::IndexOutOfRange { idx, .. } => GltfError::InvalidIndex { kind: "vertex".to_owned(), idx: idx as usize },
+            e => GltfError::Unsupported(format!("{:?}", e)),
+        })
+    }
+
+    fn material_name(&self, material: Option<usize>) -> String {
+        match material {
+            Some(idx) => match self.element("materials", idx).ok().and_then(|m| m.get("name")).and_then(Json::as_str) {
+                Some(name) => format!("{}#{}", self.name, name),
+                None => format!("{}#material{}", self.name, idx),
+            },
+            None => format!("{}#default", self.name),
+        }
+    }
+
+    fn add_material(&mut self, lib_builder: &mut MaterialLibBuilder, material: Option<usize>) -> Result<(), GltfError> {
+        lib_builder.add_material(self.material_name(material));
+        let material = match material {
+            Some(idx) => self.element("materials", idx)?.clone(),
+            None => return Ok(()),
+        };
+        let err = |e: ResourceParseError| GltfError::Unsupported(format!("{:?}", e));
+        let pbr = material.get("pbrMetallicRoughness").cloned().unwrap_or(Json::object());
+
+        let base_color = pbr.get("baseColorFactor").and_then(Json::as_f32_array).unwrap_or(vec![1.0; 4]);
+        let metallic = pbr.get("metallicFactor").and_then(Json::as_f32).unwrap_or(1.0);
+        let roughness = pbr.get("roughnessFactor").and_then(Json::as_f32).unwrap_or(1.0);
+        if base_color.len() == 4 {
+            let color = Vector3::new(base_color[0], base_color[1], base_color[2]);
+            lib_builder.diffuse_color(color).map_err(err)?;
+            lib_builder.ambient_color(color * 0.3).map_err(err)?;
+            lib_builder.specular_color(Vector3::new(0.04, 0.04, 0.04) * (1.0 - metallic) + color * metallic).map_err(err)?;
+            if material.get("alphaMode").and_then(Json::as_str) == Some("BLEND") {
+                lib_builder.transmission(1.0 - base_color[3]).map_err(err)?;
+            }
+        }
+        lib_builder.metallic(metallic).map_err(err)?;
+        lib_builder.roughness(roughness).map_err(err)?;
+        lib_builder.specular_exp((2.0 / roughness.max(0.01).powi(4) - 2.0).clamp(1.0, 1000.0)).map_err(err)?;
+        if let Some(emissive) = material.get("emissiveFactor").and_then(Json::as_f32_array).filter(|e| e.len() == 3) {
+            lib_builder.emissive_color(Vector3::new(emissive[0], emissive[1], emissive[2])).map_err(err)?;
+        }
+
+        if let Some(tex) = self.texture_image(pbr.get("baseColorTexture"))? {
+            // masked materials are cut out using the alpha channel of the base color
+            if material.get("alphaMode").and_then(Json::as_str) == Some("MASK") {
+                lib_builder.alpha_tex(TextureMap { channel: Some(TextureChannel::Matte), ..TextureMap::new(tex.clone()) }).map_err(err)?;
+                lib_builder.alpha_cutoff(material.get("alphaCutoff").and_then(Json::as_f32).unwrap_or(0.5)).map_err(err)?;
+            }
+            lib_builder.diffuse_tex(TextureMap::new(tex)).map_err(err)?;
+        }
+        if let Some(tex) = self.texture_image(pbr.get("metallicRoughnessTexture"))? { lib_builder.metallic_roughness_tex(TextureMap::new(tex)).map_err(err)? }
+        if let Some(tex) = self.texture_image(material.get("normalTexture"))? {
+            let scale = material.get("normalTexture").and_then(|t| t.get("scale")).and_then(Json::as_f32).unwrap_or(1.0);
+            lib_builder.normal_tex(TextureMap { bump_multiplier: scale, ..TextureMap::new(tex) }).map_err(err)?;
+        }
+        if let Some(tex) = self.texture_image(material.get("occlusionTexture"))? { lib_builder.occlusion_tex(TextureMap::new(tex)).map_err(err)? }
+        if let Some(tex) = self.texture_image(material.get("emissiveTexture"))? { lib_builder.emissive_tex(TextureMap::new(tex)).map_err(err)? }
+        Ok(())
+    }
+
+    fn texture_image(&mut self, texture_info: Option<&Json>) -> Result<Option<String>, GltfError> {
+        let texture_idx = match texture_info.and_then(|t| t.get("index")).and_then(Json::as_usize) {
+            Some(idx) => idx,
+            None => return Ok(None),
+        };
+        let image_idx = match self.element("textures", texture_idx)?.get("source").and_then(Json::as_usize) {
+            Some(idx) => idx,
+            None => return Ok(None),
+        };
+        if let Some(name) = self.image_names.get(&image_idx) { return Ok(Some(name.clone())) }
+
+        let image = self.element("images", image_idx)?.clone();
+        let (name, data) = match image.get("uri").and_then(Json::as_str) {
+            Some(uri) if !uri.starts_with("data:") => (self.relative_path(uri), self.read_uri(uri)?),
+            Some(uri) => (format!("{}#image{}", self.name, image_idx), self.read_uri(uri)?),
+            None => {
+                let view_idx = image.get("bufferView").and_then(Json::as_usize).ok_or(GltfError::MissingField("image.bufferView".to_owned()))?;
+                let view = self.element("bufferViews", view_idx)?;
+                let buffer_idx = view.get("buffer").and_then(Json::as_usize).unwrap_or(0);
+                let offset = view.get("byteOffset").and_then(Json::as_usize).unwrap_or(0);
+                let length = view.get("byteLength").and_then(Json::as_usize).unwrap_or(0);
+                let data = self.buffers.get(buffer_idx).and_then(|b| b.get(offset..offset.checked_add(length)?))
+                    .ok_or(GltfError::InvalidIndex { kind: "bufferViews".to_owned(), idx: view_idx })?;
+                (format!("{}#image{}", self.name, image_idx), data.to_vec())
+            }
+        };
+        self.image_names.insert(image_idx, name.clone());
+        self.images.push((name.clone(), data));
+        Ok(Some(name))
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn array<'j>(json: &'j Json, key: &str) -> &'j [Json] {
+    json.get(key).and_then(Json::as_array).map(|a| a.as_slice()).unwrap_or(&[])
+}
+
+fn indices(json: &Json, key: &str) -> Vec<usize> {
+    array(json, key).iter().filter_map(Json::as_usize).collect()
+}
+
+fn node_transform(node: &Json) -> Matrix4<f32> {
+    if let Some(m) = node.get("matrix").and_then(Json::as_f32_array).filter(|m| m.len() == 16) {
+        return Matrix4::new(
+            m[0], m[1], m[2], m[3], m[4], m[5], m[6], m[7],
+            m[8], m[9], m[10], m[11], m[12], m[13], m[14], m[15],
+        );
+    }
+    let t = node.get("translation").and_then(Json::as_f32_array).filter(|t| t.len() == 3).unwrap_or(vec![0.0; 3]);
+    let r = node.get("rotation").and_then(Json::as_f32_array).filter(|r| r.len() == 4).unwrap_or(vec![0.0, 0.0, 0.0, 1.0]);
+    let s = node.get("scale").and_then(Json::as_f32_array).filter(|s| s.len() == 3).unwrap_or(vec![1.0; 3]);
+    Matrix4::from_translation(Vector3::new(t[0], t[1], t[2]))
+        * Matrix4::from(Quaternion::new(r[3], r[0], r[1], r[2]))
+        * Matrix4::from_nonuniform_scale(s[0], s[1], s[2])
+}
+
+fn vertex_normals(primitive: &Primitive) -> Vec<f32> {
+    let p = |i: u32| Vector3::new(
+        primitive.positions[i as usize * 3],
+        primitive.positions[i as usize * 3 + 1],
+        primitive.positions[i as usize * 3 + 2],
+    );
+    let mut normals = vec![Vector3::zero(); primitive.positions.len() / 3];
+    primitive.triangles.iter().for_each(|t| {
+        let n = (p(t[1]) - p(t[0])).cross(p(t[2]) - p(t[0]));
+        t.iter().for_each(|i| normals[*i as usize] += n);
+    });
+    normals.into_iter().flat_map(|n: Vector3<f32>| {
+        let n = if n.magnitude2() > 0.0 { n.normalize() } else { Vector3::unit_y() };
+        [n.x, n.y, n.z]
+    }).collect()
+}
+
+fn percent_decode(str: &str) -> String {
+    let bytes = str.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok()).and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => { decoded.push(b); i += 3 }
+            (b, _) => { decoded.push(b); i += 1 }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn decode_base64(str: &str) -> Result<Vec<u8>, GltfError> {
+    let mut data = Vec::with_capacity(str.len() * 3 / 4);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for c in str.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            b' ' | b'\n' | b'\r' | b'\t' => continue,
+            _ => return Err(GltfError::InvalidBase64),
+        } as u32;
+        acc = (acc << 6) | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            data.push((acc >> bits) as u8);
+        }
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use crate::rendering::mesh_processing::MeshProcessing;
+    use crate::resource::resource::Resource;
+    use crate::util::error::GltfError;
+    use crate::util::json::Json;
+    use super::{decode_base64, percent_decode, GltfData, GltfParser, GLB_CHUNK_BIN, GLB_CHUNK_JSON, GLB_MAGIC};
+
+    // a triangle in the xy plane followed by its u16 indices, padded to 4 bytes
+    fn triangle_buffer() -> Vec<u8> {
+        let mut data: Vec<u8> = [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0].iter().flat_map(|f| f.to_le_bytes()).collect();
+        data.extend([0u16, 1, 2].iter().flat_map(|i| i.to_le_bytes()));
+        data.extend([0, 0]);
+        data
+    }
+
+    fn encode_base64(data: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        data.chunks(3).flat_map(|c| {
+            let n = (c[0] as u32) << 16 | (*c.get(1).unwrap_or(&0) as u32) << 8 | *c.get(2).unwrap_or(&0) as u32;
+            (0..4).map(move |i| if i <= c.len() { ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char } else { '=' })
+        }).collect()
+    }
+
+    // a document with one triangle mesh, the buffer is embedded as a data uri unless it is None
+    fn triangle_gltf(nodes: Json, buffer_uri: Option<String>) -> Json {
+        let buffer = match buffer_uri {
+            Some(uri) => Json::object().with("byteLength", 44u32).with("uri", uri),
+            None => Json::object().with("byteLength", 44u32),
+        };
+        let accessor = |view: u32, component_type: u32, kind: &str| Json::object()
+            .with("bufferView", view).with("componentType", component_type).with("count", 3u32).with("type", kind);
+        let primitive = Json::object()
+            .with("attributes", Json::object().with("POSITION", 0u32))
+            .with("indices", 1u32)
+            .with("material", 0u32);
+        Json::object()
+            .with("asset", Json::object().with("version", "2.0"))
+            .with("buffers", vec![buffer])
+            .with("bufferViews", vec![
+                Json::object().with("buffer", 0u32).with("byteLength", 36u32),
+                Json::object().with("buffer", 0u32).with("byteOffset", 36u32).with("byteLength", 6u32),
+            ])
+            .with("accessors", vec![accessor(0, 5126, "VEC3"), accessor(1, 5123, "SCALAR")])
+            .with("meshes", vec![Json::object().with("primitives", vec![primitive])])
+            .with("materials", vec![Json::object().with("name", "red")])
+            .with("nodes", nodes)
+    }
+
+    fn embedded(nodes: Json) -> Json {
+        triangle_gltf(nodes, Some(format!("data:application/octet-stream;base64,{}", encode_base64(&triangle_buffer()))))
+    }
+
+    fn mesh_node() -> Json { Json::object().with("mesh", 0u32) }
+
+    fn glb(json: &str, bin: &[u8]) -> Vec<u8> {
+        let chunk = |kind: u32, data: &[u8], pad: u8| {
+            let mut chunk = ((data.len() + 3) as u32 & !3).to_le_bytes().to_vec();
+            chunk.extend(kind.to_le_bytes());
+            chunk.extend(data);
+            chunk.resize(chunk.len() + (4 - data.len() % 4) % 4, pad);
+            chunk
+        };
+        let body: Vec<u8> = [chunk(GLB_CHUNK_JSON, json.as_bytes(), b' '), chunk(GLB_CHUNK_BIN, bin, 0)].concat();
+        let mut data = GLB_MAGIC.to_le_bytes().to_vec();
+        data.extend(2u32.to_le_bytes());
+        data.extend((12 + body.len() as u32).to_le_bytes());
+        data.extend(body);
+        data
+    }
+
+    fn parse(data: Vec<u8>) -> Result<GltfData, GltfError> {
+        // embedded documents never read from the resource directory
+        let res = Resource::new(PathBuf::from("/nonexistent"));
+        GltfParser::parse_gltf(data, "test.gltf", &res, MeshProcessing::default())
+    }
+
+    fn parse_json(json: &Json) -> Result<GltfData, GltfError> { parse(json.to_string().into_bytes()) }
+
+    // the corners of every triangle in order
+    fn corners(data: &GltfData) -> Vec<[[f32; 3]; 3]> {
+        let model = &data.model;
+        let p = |i: u32| { let p = model.positions()[i as usize]; [p.x, p.y, p.z] };
+        model.triangles().iter().map(|t| [p(t.p0), p(t.p1), p(t.p2)]).collect()
+    }
+
+    const TRIANGLE: [[f32; 3]; 3] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+
+    #[test]
+    fn embedded_buffers_are_decoded() {
+        let data = parse_json(&embedded(vec![mesh_node()].into())).unwrap();
+        assert_eq!(corners(&data), vec![TRIANGLE]);
+        assert_eq!(data.model.get_materials(), &vec!["test.gltf#red".to_owned()]);
+        assert_eq!(data.materials.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>(), vec!["test.gltf#red"]);
+        assert!(!data.model.has_normals() && !data.model.has_tex_coords());
+    }
+
+    #[test]
+    fn glb_files_use_the_binary_chunk() {
+        let json = triangle_gltf(vec![mesh_node()].into(), None).to_string();
+        let data = parse(glb(&json, &triangle_buffer())).unwrap();
+        assert_eq!(corners(&data), vec![TRIANGLE]);
+    }
+
+    #[test]
+    fn invalid_glb_files_are_rejected() {
+        let json = triangle_gltf(vec![mesh_node()].into(), None).to_string();
+        let valid = glb(&json, &triangle_buffer());
+        let mut version = valid.clone();
+        version[4] = 1;
+        assert!(matches!(parse(version), Err(GltfError::InvalidGlb(_))));
+        // the declared length of the json chunk reaches past the end of the file
+        let mut chunk_length = valid.clone();
+        chunk_length[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(parse(chunk_length), Err(GltfError::InvalidGlb(_))));
+        for end in 0..valid.len() {
+            assert!(parse(valid[..end].to_vec()).is_err(), "truncated to {} bytes", end);
+        }
+    }
+
+    #[test]
+    fn node_transforms_are_applied_down_the_hierarchy() {
+        let nodes: Json = vec![
+            Json::object().with("translation", vec![0.0f32, 0.0, 5.0]).with("children", vec![1u32]),
+            mesh_node().with("scale", vec![2.0f32, 2.0, 2.0]),
+        ].into();
+        let data = parse_json(&embedded(nodes)).unwrap();
+        assert_eq!(corners(&data), vec![[[0.0, 0.0, 5.0], [2.0, 0.0, 5.0], [0.0, 2.0, 5.0]]]);
+
+        // a quarter turn around z as a quaternion and as a column major matrix
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        for node in [
+            mesh_node().with("rotation", vec![0.0, 0.0, half, half]),
+            mesh_node().with("matrix", vec![0.0f32, 1.0, 0.0, 0.0, -1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0]),
+        ] {
+            let data = parse_json(&embedded(vec![node].into())).unwrap();
+            let rotated = corners(&data)[0];
+            for (corner, expected) in rotated.iter().zip([[0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [-1.0, 0.0, 0.0]]) {
+                assert!(corner.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-6), "{:?}", rotated);
+            }
+        }
+    }
+
+    #[test]
+    fn mirroring_transforms_keep_the_winding() {
+        let nodes: Json = vec![mesh_node().with("scale", vec![-1.0f32, 1.0, 1.0])].into();
+        let data = parse_json(&embedded(nodes)).unwrap();
+        assert_eq!(corners(&data), vec![[[0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [-1.0, 0.0, 0.0]]]);
+    }
+
+    #[test]
+    fn scenes_select_their_root_nodes() {
+        let nodes: Json = vec![mesh_node(), mesh_node().with("translation", vec![0.0f32, 0.0, 1.0])].into();
+        let json = embedded(nodes.clone()).with("scenes", vec![Json::object().with("nodes", vec![1u32])]);
+        assert_eq!(corners(&parse_json(&json).unwrap()).len(), 1);
+        // without scenes every node without a parent is a root
+        assert_eq!(corners(&parse_json(&embedded(nodes)).unwrap()).len(), 2);
+    }
+
+    #[test]
+    fn node_hierarchies_have_to_be_trees() {
+        let cyclic: Json = vec![mesh_node().with("children", vec![1u32]), mesh_node().with("children", vec![0u32])].into();
+        let json = embedded(cyclic).with("scenes", vec![Json::object().with("nodes", vec![0u32])]);
+        assert!(matches!(parse_json(&json), Err(GltfError::Unsupported(_))));
+
+        let shared: Json = vec![
+            Json::object().with("children", vec![2u32]),
+            Json::object().with("children", vec![2u32]),
+            mesh_node(),
+        ].into();
+        assert!(matches!(parse_json(&embedded(shared)), Err(GltfError::Unsupported(_))));
+
+        let missing: Json = vec![mesh_node().with("children", vec![3u32])].into();
+        assert!(matches!(parse_json(&embedded(missing)), Err(GltfError::InvalidIndex { idx: 3, .. })));
+    }
+
+    #[test]
+    fn invalid_documents_are_rejected() {
+        let valid = embedded(vec![mesh_node()].into());
+        let with_accessor = |accessor: Json| {
+            let mut json = valid.clone();
+            if let Json::Object(entries) = &mut json {
+                entries.iter_mut().filter(|(k, _)| k == "accessors").for_each(|(_, v)| *v = vec![accessor.clone()].into());
+            }
+            json
+        };
+
+        assert!(matches!(parse(b"{\"asset\": ".to_vec()), Err(GltfError::JsonError(_))));
+        assert!(matches!(parse(vec![0xff, b'{']), Err(GltfError::InvalidGlb(_))));
+        // the accessor reads past the end of its buffer
+        let past_end = Json::object().with("bufferView", 0u32).with("componentType", 5126u32).with("count", 4u32).with("type", "VEC3");
+        assert!(matches!(parse_json(&with_accessor(past_end)), Err(GltfError::InvalidAccessor(0))));
+        // accessors without buffer views must not allocate any count they declare
+        let huge = Json::object().with("componentType", 5126u32).with("count", 4_000_000_000u32).with("type", "VEC3");
+        assert!(matches!(parse_json(&with_accessor(huge)), Err(GltfError::InvalidAccessor(0))));
+        let wrong_type = Json::object().with("bufferView", 0u32).with("componentType", 5126u32).with("count", 3u32).with("type", "VEC2");
+        assert!(matches!(parse_json(&with_accessor(wrong_type)), Err(GltfError::InvalidAccessor(0))));
+
+        let external = triangle_gltf(vec![mesh_node()].into(), Some("missing.bin".to_owned()));
+        assert!(matches!(parse_json(&external), Err(GltfError::UnsupportedUri(_))));
+        let invalid_base64 = triangle_gltf(vec![mesh_node()].into(), Some("data:;base64,AA*A".to_owned()));
+        assert!(matches!(parse_json(&invalid_base64), Err(GltfError::InvalidBase64)));
+        let no_buffer = triangle_gltf(vec![mesh_node()].into(), None);
+        assert!(matches!(parse_json(&no_buffer), Err(GltfError::MissingField(_))));
+    }
+
+    #[test]
+    fn indices_have_to_refer_to_vertices() {
+        let mut buffer = triangle_buffer();
+        buffer[40..42].copy_from_slice(&3u16.to_le_bytes());
+        let json = triangle_gltf(vec![mesh_node()].into(), Some(format!("data:;base64,{}", encode_base64(&buffer))));
+        assert!(matches!(parse_json(&json), Err(GltfError::InvalidIndex { idx: 3, .. })));
+    }
+
+    #[test]
+    fn external_buffers_are_reported_as_files() {
+        let dir = std::env::temp_dir().join(format!("gltf_parser_test_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("models")).unwrap();
+        std::fs::write(dir.join("models/triangle data.bin"), triangle_buffer()).unwrap();
+        let json = triangle_gltf(vec![mesh_node()].into(), Some("triangle%20data.bin".to_owned())).to_string();
+        let res = Resource::new(dir.clone());
+        let result = GltfParser::parse_gltf(json.into_bytes(), "models/triangle.gltf", &res, MeshProcessing::default());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let data = result.unwrap();
+        assert_eq!(corners(&data), vec![TRIANGLE]);
+        assert_eq!(data.files, vec!["models/triangle data.bin".to_owned()]);
+        assert!(parse_json(&embedded(vec![mesh_node()].into())).unwrap().files.is_empty());
+    }
+
+    #[test]
+    fn uris_are_decoded() {
+        assert_eq!(decode_base64("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(decode_base64("aGVs\nbG8").unwrap(), b"hello");
+        assert_eq!(decode_base64(&encode_base64(&[0xfb, 0xff, 0x00, 0x7f])).unwrap(), vec![0xfb, 0xff, 0x00, 0x7f]);
+        assert_eq!(percent_decode("a%20b%2fc%zz%"), "a b/c%zz%");
+    }
+}
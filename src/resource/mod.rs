@@ -1,3 +1,9 @@
 pub mod resource_parser;
 pub mod resource;
 pub mod resource_manager;
+pub mod file_watcher;
+pub mod shader_preprocessor;
+pub mod gltf_parser;
+pub mod obj_parser;
+pub mod ply_parser;
+pub mod stl_parser;
@@ -0,0 +1,508 @@
+use std::io::Read;
+use std::thread;
+use cgmath::{Vector2, Vector3};
+use crate::raytracing::types::IndexBundle;
+use crate::rendering::mesh_processing::MeshProcessing;
+use crate::rendering::model::{Model, ModelBuilder};
+use crate::resource::resource_parser::ResourceParser;
+use crate::util::error::{ResourceError, ResourceLoadError, ResourceParseError};
+
+// the file is read in blocks of this size, so only one block of text is in memory at a time
+const BLOCK_SIZE: usize = 64 << 20;
+// blocks smaller than this are not worth splitting across threads
+const MIN_CHUNK_SIZE: usize = 1 << 20;
+
+// parse errors carry the line number they occurred on
+type ParseResult<T> = Result<T, (ResourceParseError, u32)>;
+
+#[derive(Clone, Copy, Default)]
+struct ElementCounts {
+    positions: usize,
+    tex_coords: usize,
+    normals: usize,
+}
+
+impl ElementCounts {
+    fn add(self, other: Self) -> Self {
+        Self {
+            positions: self.positions + other.positions,
+            tex_coords: self.tex_coords + other.tex_coords,
+            normals: self.normals + other.normals,
+        }
+    }
+
+    fn as_array(&self) -> [usize; 3] { [self.positions, self.tex_coords, self.normals] }
+}
+
+// the result of parsing one chunk of the file on a worker thread
+#[derive(Default)]
+struct ObjChunk {
+    positions: Vec<Vector3<f32>>,
+    tex_coords: Vec<Vector2<f32>>,
+    normals: Vec<Vector3<f32>>,
+    colors: Vec<Vector3<f32>>,
+    triangles: Vec<[IndexBundle; 3]>,
+    lines: Vec<[IndexBundle; 2]>,
+    // statements changing the material or group state, stored with the number of triangles
+    // preceding them, as the state depends on previous chunks and is only known while merging
+    statements: Vec<(usize, String, u32)>,
+}
+
+impl ObjChunk {
+    fn parse(data: &str, offsets: ElementCounts) -> ParseResult<Self> {
+        let mut chunk = Self::default();
+        ResourceParser::logical_lines(data)
+            .try_for_each(|(str, i)| chunk.parse_line(&str, i, offsets).map_err(|e| (e, i)))?;
+        Ok(chunk)
+    }
+
+    fn parse_line(&mut self, str: &str, line: u32, offsets: ElementCounts) -> Result<(), ResourceParseError> {
+        let (keyword, _) = ResourceParser::split_keyword(str);
+        match keyword {
+            "v" => {
+                let (position, color) = ResourceParser::parse_position_line(str)?;
+                self.positions.push(position);
+                if let Some(color) = color { self.colors.push(color) }
+            }
+            "vt" => self.tex_coords.push(ResourceParser::parse_tex_coord_line(str)?),
+            "vn" => self.normals.push(ResourceParser::parse_normal_line(str)?),
+            "f" => {
+                let bundles = ResourceParser::parse_index_bundles(str, 3, self.counts(offsets))?;
+                (1..bundles.len() - 1).for_each(|i| {
+                    self.triangles.push([bundles[0].clone(), bundles[i].clone(), bundles[i + 1].clone()]);
+                });
+            }
+            "l" => {
+                let bundles = ResourceParser::parse_index_bundles(str, 2, self.counts(offsets))?;
+                bundles.windows(2).for_each(|b| self.lines.push([b[0].clone(), b[1].clone()]));
+            }
+            "o" | "g" | "s" | "mtllib" | "usemtl" => self.statements.push((self.triangles.len(), str.to_owned(), line)),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn counts(&self, offsets: ElementCounts) -> [usize; 3] {
+        offsets.add(ElementCounts {
+            positions: self.positions.len(),
+            tex_coords: self.tex_coords.len(),
+            normals: self.normals.len(),
+        }).as_array()
+    }
+
+    fn merge_into(self, model_builder: &mut ModelBuilder, line_offset: u32) -> ParseResult<()> {
+        self.positions.into_iter().for_each(|p| model_builder.add_position(p));
+        self.tex_coords.into_iter().for_each(|t| model_builder.add_tex_coord(t));
+        self.normals.into_iter().for_each(|n| model_builder.add_normal(n));
+        self.colors.into_iter().for_each(|c| model_builder.add_color(c));
+
+        let mut statements = self.statements.into_iter().peekable();
+        let apply = |model_builder: &mut ModelBuilder, (_, str, line): (usize, String, u32)| {
+            ResourceParser::parse_model_line(model_builder, &str).map_err(|e| (e, line + line_offset))
+        };
+        for (i, [i0, i1, i2]) in self.triangles.into_iter().enumerate() {
+            while let Some(statement) = statements.next_if(|(idx, _, _)| *idx <= i) {
+                apply(model_builder, statement)?;
+            }
+            model_builder.add_indices(i0, i1, i2, model_builder.get_current_mat());
+        }
+        statements.try_for_each(|statement| apply(model_builder, statement))?;
+
+        self.lines.into_iter().for_each(|[i0, i1]| model_builder.add_line(i0, i1));
+        Ok(())
+    }
+}
+
+pub struct ObjParser {}
+
+impl ObjParser {
+    // parses an obj file block by block, splitting every block into chunks that are parsed in parallel.
+    // a first pass counts the vertex elements of each chunk, so that every chunk knows the element
+    // counts at its start and can resolve relative indices exactly like a sequential parse would
+    pub fn parse_model<R: Read>(mut reader: R, name: &str, processing: MeshProcessing) -> Result<Model, ResourceError> {
+        let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        let mut model_builder = ModelBuilder::new();
+        model_builder.set_mesh_processing(processing);
+        let mut line_offset = 0;
+        let mut block: Vec<u8> = vec![];
+
+        loop {
+            let read = (&mut reader).take(BLOCK_SIZE as u64).read_to_end(&mut block)
+                .map_err(|e| ResourceError::load_err(ResourceLoadError::Io { e }, name))?;
+            let eof = read < BLOCK_SIZE;
+
+            // the block is cut after its last complete line, the rest is carried over to the next block
+            let end = if eof { block.len() } else {
+                match Self::last_line_end(&block) {
+                    Some(end) => end,
+                    None => continue,
+                }
+            };
+            let rest = block.split_off(end);
+            Self::parse_block(&block, threads, &mut model_builder, &mut line_offset)
+                .map_err(|(e, l)| ResourceError::parse_err(e, l, name))?;
+            block = rest;
+
+            if eof { break }
+        }
+
+        // errors of the whole model have no line
+        model_builder.build().map_err(|e| ResourceError::parse_err(e, 0, name))
+    }
+
+    fn parse_block(data: &[u8], threads: usize, model_builder: &mut ModelBuilder, line_offset: &mut u32) -> ParseResult<()> {
+        let chunks = Self::split_chunks(data, threads);
+
+        let counted: Vec<ParseResult<(&str, ElementCounts, u32)>> = thread::scope(|s| {
+            let handles: Vec<_> = chunks.iter().map(|chunk| s.spawn(move || Self::count_elements(chunk))).collect();
+            handles.into_iter().map(|h| h.join().expect("obj counting thread panicked")).collect()
+        });
+
+        let mut offsets = ElementCounts {
+            positions: model_builder.position_count(),
+            tex_coords: model_builder.tex_coord_count(),
+            normals: model_builder.normal_count(),
+        };
+        let mut line = *line_offset;
+        let mut jobs = vec![];
+        for result in counted {
+            let (str, counts, line_count) = result.map_err(|(e, l)| (e, l + line))?;
+            jobs.push((str, offsets, line));
+            offsets = offsets.add(counts);
+            line += line_count;
+        }
+        *line_offset = line;
+
+        let parsed: Vec<(ParseResult<ObjChunk>, u32)> = thread::scope(|s| {
+            let handles: Vec<_> = jobs.iter()
+                .map(|(str, offsets, line)| (s.spawn(move || ObjChunk::parse(str, *offsets)), *line))
+                .collect();
+            handles.into_iter().map(|(h, line)| (h.join().expect("obj parsing thread panicked"), line)).collect()
+        });
+
+        parsed.into_iter().try_for_each(|(chunk, line)| {
+            chunk.map_err(|(e, l)| (e, l + line))?.merge_into(model_builder, line)
+        })
+    }
+
+    fn count_elements(data: &[u8]) -> ParseResult<(&str, ElementCounts, u32)> {
+        let str = std::str::from_utf8(data).map_err(|e| {
+            let line = Self::physical_line_count(&data[..e.valid_up_to()]) + 1;
+            (ResourceParseError::InvalidUtf8, line)
+        })?;
+        // a plain scan of the line keywords, much cheaper than building the logical lines
+        let mut counts = ElementCounts::default();
+        let mut continued = false;
+        for line in data.split(|b| *b == b'\n').flat_map(|l| l.strip_suffix(b"\r").unwrap_or(l).split(|b| *b == b'\r')) {
+            if !continued {
+                let keyword = line.trim_ascii().split(u8::is_ascii_whitespace).next().unwrap_or_default();
+                match keyword.strip_suffix(b"\\").unwrap_or(keyword) {
+                    b"v" => counts.positions += 1,
+                    b"vt" => counts.tex_coords += 1,
+                    b"vn" => counts.normals += 1,
+                    _ => {}
+                }
+            }
+            continued = Self::is_continued(line);
+        }
+        Ok((str, counts, Self::physical_line_count(data)))
+    }
+
+    // counts lines the same way as ResourceParser::logical_lines numbers them
+    fn physical_line_count(data: &[u8]) -> u32 {
+        data.iter().enumerate()
+            .filter(|(i, b)| **b == b'\n' || (**b == b'\r' && data.get(i + 1) != Some(&b'\n')))
+            .count() as u32
+    }
+
+    fn split_chunks(data: &[u8], threads: usize) -> Vec<&[u8]> {
+        let chunk_size = (data.len() / threads).max(MIN_CHUNK_SIZE);
+        let mut chunks = vec![];
+        let mut start = 0;
+        while start < data.len() {
+            let end = Self::next_line_end(data, start + chunk_size);
+            chunks.push(&data[start..end]);
+            start = end;
+        }
+        chunks
+    }
+
+    // returns the position after the first line end at or after from, skipping "\" continuations
+    fn next_line_end(data: &[u8], from: usize) -> usize {
+        let mut pos = from;
+        while pos < data.len() {
+            match data[pos..].iter().position(|b| *b == b'\n') {
+                Some(i) if Self::ends_continued(data, pos + i) => pos += i + 1,
+                Some(i) => return pos + i + 1,
+                None => break,
+            }
+        }
+        data.len()
+    }
+
+    // returns the position after the last line end that does not continue onto the next line.
+    // files using only "\r" line endings have none and are therefore read as a single block
+    fn last_line_end(data: &[u8]) -> Option<usize> {
+        let mut end = data.len();
+        while let Some(i) = data[..end].iter().rposition(|b| *b == b'\n') {
+            if !Self::ends_continued(data, i) { return Some(i + 1) }
+            end = i;
+        }
+        None
+    }
+
+    // whether the physical line ending at the line break at end continues onto the next line
+    fn ends_continued(data: &[u8], end: usize) -> bool {
+        let end = if end > 0 && data[end - 1] == b'\r' { end - 1 } else { end };
+        let start = data[..end].iter().rposition(|b| *b == b'\n' || *b == b'\r').map_or(0, |i| i + 1);
+        Self::is_continued(&data[start..end])
+    }
+
+    fn is_continued(line: &[u8]) -> bool {
+        line.trim_ascii_end().ends_with(b"\\")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Vector2, Vector3, Zero};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use crate::raytracing::types::IndexBundle;
+    use crate::rendering::mesh_processing::MeshProcessing;
+    use crate::rendering::model::{Model, ModelBuilder};
+    use crate::util::error::{ResourceError, ResourceParseError};
+    use super::ObjParser;
+
+    const CUBE: &str = "\
+mtllib cube.mtl
+o cube
+v -1 -1 -1
+v 1 -1 -1
+v 1 1 -1
+v -1 1 -1
+v -1 -1 1
+v 1 -1 1
+v 1 1 1
+v -1 1 1
+vt 0 0
+vt 1 0
+vt 1 1
+vn 0 0 -1
+vn 0 0 1
+g front
+usemtl red
+f 1/1/1 2/2/1 3/3/1 4/3/1
+g back
+usemtl blue
+f -4/1/2 -3/2/2 -2/3/2
+s 1
+f 5/1/2 7/3/2 8/3/2
+l 1 2 3
+";
+
+    // the parts of a model a parse has to reproduce exactly
+    type Summary = (Vec<[f32; 3]>, Vec<u32>, Vec<u32>, Vec<(String, Vec<String>, u32)>, Vec<String>, Vec<String>);
+
+    fn parse(data: &[u8]) -> Result<Model, ResourceError> {
+        ObjParser::parse_model(data, "test.obj", MeshProcessing::default())
+    }
+
+    fn summary(model: &Model) -> Summary {
+        let mut libs = model.get_material_libs().clone();
+        libs.sort();
+        (
+            model.positions().iter().map(|p| [p.x, p.y, p.z]).collect(),
+            model.indices().clone(),
+            model.triangle_groups().clone(),
+            model.groups().iter().map(|g| (g.object.clone(), g.groups.clone(), g.smoothing_group)).collect(),
+            libs,
+            model.get_materials().clone(),
+        )
+    }
+
+    fn parse_summary(data: &str) -> Summary {
+        summary(&parse(data.as_bytes()).unwrap_or_else(|e| panic!("failed to parse {:?}: {:?}", data, e)))
+    }
+
+    // whatever the input, parsing either succeeds or fails with a parse error
+    fn assert_parses_or_parse_error(data: &[u8]) {
+        match parse(data) {
+            Ok(_) | Err(ResourceError::ResourceParseError { .. }) => {}
+            Err(e) => panic!("unexpected error {:?} for {:?}", e, String::from_utf8_lossy(data)),
+        }
+    }
+
+    fn random_obj(rng: &mut StdRng) -> Vec<u8> {
+        const TOKENS: [&str; 24] = [
+            "v", "vt", "vn", "f", "l", "o", "g", "s", "usemtl", "mtllib", "off", "1", "-1", "2", "0",
+            "1.5e3", "nan", "/", "//", "\\", "#", "a.mtl", "\t", "x",
+        ];
+        let mut data = vec![];
+        for _ in 0..rng.gen_range(0..40) {
+            match rng.gen_range(0..10) {
+                0 => data.extend_from_slice(b"\n"),
+                1 => data.extend_from_slice(b"\r\n"),
+                2 => data.push(rng.gen()),
+                _ => {
+                    data.extend_from_slice(TOKENS[rng.gen_range(0..TOKENS.len())].as_bytes());
+                    if rng.gen_bool(0.7) { data.push(b' ') }
+                }
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn random_input_never_panics() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..2000 {
+            assert_parses_or_parse_error(&random_obj(&mut rng));
+        }
+    }
+
+    #[test]
+    fn random_lines_of_a_valid_file_never_panic() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let lines: Vec<&str> = CUBE.lines().collect();
+        for _ in 0..500 {
+            let data: Vec<&str> = (0..rng.gen_range(0..30)).map(|_| lines[rng.gen_range(0..lines.len())]).collect();
+            assert_parses_or_parse_error(data.join("\n").as_bytes());
+        }
+    }
+
+    #[test]
+    fn truncated_input_never_panics() {
+        for end in 0..=CUBE.len() {
+            assert_parses_or_parse_error(&CUBE.as_bytes()[..end]);
+        }
+    }
+
+    #[test]
+    fn invalid_utf8_is_a_parse_error() {
+        assert!(matches!(parse(b"v 0 0 0\nv \xff 0 0\n"), Err(ResourceError::ResourceParseError { line: 2, .. })));
+    }
+
+    #[test]
+    fn crlf_line_endings_parse_like_lf() {
+        assert_eq!(parse_summary(&CUBE.replace('\n', "\r\n")), parse_summary(CUBE));
+        assert_eq!(parse_summary(&CUBE.replace('\n', "\r")), parse_summary(CUBE));
+    }
+
+    #[test]
+    fn continuations_join_lines() {
+        let continued = CUBE
+            .replace("f 1/1/1 2/2/1 3/3/1 4/3/1", "f 1/1/1 2/2/1 \\\n3/3/1 \\\r\n4/3/1")
+            .replace("v 1 1 1", "v 1 \\\n1 \\\n1");
+        assert_eq!(parse_summary(&continued), parse_summary(CUBE));
+    }
+
+    #[test]
+    fn errors_report_the_physical_line() {
+        let data = "v 0 0 0\nv 1 0 0\nf 1 \\\n2 \\\n1\nv x 0 0\n";
+        let result = parse(data.as_bytes());
+        assert!(matches!(result, Err(ResourceError::ResourceParseError { line: 6, .. })), "{:?}", result.err());
+    }
+
+    #[test]
+    fn chunks_end_after_continued_lines() {
+        // a blank line ends the logical line it continues
+        assert_eq!(ObjParser::next_line_end(b"f 1 \\\n\nv 0 0 0\n", 0), 7);
+        assert_eq!(ObjParser::next_line_end(b"f 1 \\\r\n2 3\r\nv 0 0 0\n", 0), 12);
+        assert_eq!(ObjParser::next_line_end(b"f 1 \\\n2 \\\n", 0), 10);
+        assert_eq!(ObjParser::last_line_end(b"v 0 0 0\nf 1 \\\n\n"), Some(15));
+        assert_eq!(ObjParser::last_line_end(b"v 0 0 0\nf 1 \\\r\n"), Some(8));
+        assert_eq!(ObjParser::last_line_end(b"f 1 \\\n"), None);
+    }
+
+    #[test]
+    fn large_models_number_vertices_in_order_of_first_use() {
+        // enough triangles to be deduplicated in several ranges
+        let size = 300;
+        let mut data = String::new();
+        (0..=size).flat_map(|y| (0..=size).map(move |x| (x, y))).for_each(|(x, y)| data += &format!("v {} {} 0\n", x, y));
+        (0..size).flat_map(|y| (0..size).map(move |x| (x, y))).for_each(|(x, y)| {
+            let i = y * (size + 1) + x + 1;
+            data += &format!("f {} {} {} {}\n", i, i + 1, i + size + 2, i + size + 1);
+        });
+        let model = parse(data.as_bytes()).unwrap();
+
+        assert_eq!(model.positions().len(), (size + 1) * (size + 1));
+        assert_eq!(model.triangles().len(), 2 * size * size);
+        let mut next = 0;
+        for (t, i) in model.indices().chunks(3).enumerate() {
+            let (x, y) = ((t / 2) % size, (t / 2) / size);
+            let corners = [(x, y), (x + 1, y), (x + 1, y + 1), (x, y + 1)];
+            let expected = if t % 2 == 0 { [corners[0], corners[1], corners[2]] } else { [corners[0], corners[2], corners[3]] };
+            for (&i, (x, y)) in i.iter().zip(expected) {
+                assert!(i <= next, "vertex {} used before vertex {}", i, next);
+                if i == next { next += 1 }
+                let p = model.positions()[i as usize];
+                assert_eq!((p.x, p.y), (x as f32, y as f32));
+            }
+        }
+    }
+
+    #[test]
+    fn mtllib_lines_with_several_files_and_spaces() {
+        let (_, _, _, _, libs, _) = parse_summary("mtllib a.mtl b.MTL\nmtllib my materials.mtl\nmtllib a.mtl\n");
+        assert_eq!(libs, ["a.mtl", "b.MTL", "my materials.mtl"]);
+    }
+
+    #[test]
+    fn objects_and_groups_are_kept() {
+        let (_, _, triangle_groups, groups, _, materials) = parse_summary(CUBE);
+        let group = |groups: &[&str], smoothing_group| ("cube".to_owned(), groups.iter().map(|g| g.to_string()).collect::<Vec<_>>(), smoothing_group);
+        assert_eq!(groups, [group(&["front"], 0), group(&["back"], 0), group(&["back"], 1)]);
+        assert_eq!(triangle_groups, [0, 0, 1, 2]);
+        assert_eq!(materials, ["red", "blue"]);
+    }
+
+    #[test]
+    fn empty_models_have_no_bounds() {
+        let mut model = parse(b"# nothing\n").unwrap();
+        assert!(model.bounds().is_none());
+        model.fit_to_unit_box();
+        assert!(model.positions().is_empty());
+    }
+
+    #[test]
+    fn indices_of_tex_coords_and_normals_defined_later_are_out_of_range() {
+        let later = parse(b"v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1/2 2/2 3/2\nvt 0 0\n");
+        assert!(matches!(later, Err(ResourceError::ResourceParseError { e: ResourceParseError::IndexOutOfRange { .. }, line: 4, .. })));
+        let missing = parse(b"v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1//1 2//1 3//1\n");
+        assert!(matches!(missing, Err(ResourceError::ResourceParseError { e: ResourceParseError::IndexOutOfRange { .. }, line: 4, .. })));
+    }
+
+    #[test]
+    fn indices_are_checked_against_the_final_counts() {
+        let mut model_builder = ModelBuilder::new();
+        [Vector3::zero(), Vector3::unit_x(), Vector3::unit_y()].into_iter().for_each(|p| model_builder.add_position(p));
+        model_builder.add_tex_coord(Vector2::zero());
+        let bundle = |tex_idx| IndexBundle { pos_idx: 0, tex_idx, nor_idx: IndexBundle::MISSING };
+        model_builder.add_indices(bundle(0), bundle(1), bundle(0), 0);
+        assert!(matches!(model_builder.build(), Err(ResourceParseError::IndexOutOfRange { idx: 1, len: 1 })));
+    }
+
+    #[test]
+    fn vertices_without_tex_coords_use_zero() {
+        let model = parse(b"v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 1 1\nf 1/1 2/1 3/1\nf 1 3 2\n").unwrap();
+        let tex_coords = model.tex_coords().as_ref().unwrap();
+        let second: Vec<_> = model.indices()[3..].iter().map(|&i| tex_coords[i as usize]).collect();
+        assert_eq!(second, [Vector2::zero(); 3]);
+    }
+
+    #[test]
+    fn vertices_without_normals_drop_all_normals() {
+        let model = parse(b"v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3//1\nf 1 3 2\nl 1 2\n").unwrap();
+        assert!(!model.has_normals());
+        assert!(parse(b"v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3//1\nl 1 2\n").unwrap().has_normals());
+    }
+
+    #[test]
+    fn relative_indices_resolve_like_absolute_ones() {
+        let absolute = parse_summary("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n");
+        let relative = parse_summary("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\n");
+        assert_eq!(relative, absolute);
+    }
+}
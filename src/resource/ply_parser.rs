@@ -0,0 +1,433 @@
+use cgmath::{Vector2, Vector3};
+use crate::raytracing::types::IndexBundle;
+use crate::rendering::mesh_processing::MeshProcessing;
+use crate::rendering::model::{Model, ModelBuilder};
+use crate::util::error::ResourceParseError;
+
+#[derive(Clone, Copy, PartialEq)]
+enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum PlyType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl PlyType {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "char" | "int8" => Some(Self::Int8),
+            "uchar" | "uint8" => Some(Self::UInt8),
+            "short" | "int16" => Some(Self::Int16),
+            "ushort" | "uint16" => Some(Self::UInt16),
+            "int" | "int32" => Some(Self::Int32),
+            "uint" | "uint32" => Some(Self::UInt32),
+            "float" | "float32" => Some(Self::Float32),
+            "double" | "float64" => Some(Self::Float64),
+            _ => None,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Self::Int8 | Self::UInt8 => 1,
+            Self::Int16 | Self::UInt16 => 2,
+            Self::Int32 | Self::UInt32 | Self::Float32 => 4,
+            Self::Float64 => 8,
+        }
+    }
+
+    // maximum value of integer color channels, used to map them to [0, 1]
+    fn color_scale(&self) -> f64 {
+        match self {
+            Self::Int8 => 127.0,
+            Self::UInt8 => 255.0,
+            Self::Int16 => 32767.0,
+            Self::UInt16 => 65535.0,
+            Self::Int32 => i32::MAX as f64,
+            Self::UInt32 => u32::MAX as f64,
+            Self::Float32 | Self::Float64 => 1.0,
+        }
+    }
+}
+
+enum PlyProperty {
+    Scalar { name: String, r#type: PlyType },
+    List { name: String, count_type: PlyType, item_type: PlyType },
+}
+
+struct PlyElement {
+    name: String,
+    count: usize,
+    properties: Vec<PlyProperty>,
+}
+
+impl PlyElement {
+    fn property_idx(&self, names: &[&str]) -> Option<usize> {
+        self.properties.iter().position(|p| match p {
+            PlyProperty::Scalar { name, .. } => names.contains(&name.as_str()),
+            _ => false,
+        })
+    }
+
+    // the fewest bytes an entry of the element takes in the body, ascii values take at least one
+    // character each and lists at least their count
+    fn min_entry_size(&self, format: PlyFormat) -> usize {
+        self.properties.iter().map(|p| match (format, p) {
+            (PlyFormat::Ascii, _) => 1,
+            (_, PlyProperty::Scalar { r#type, .. }) => r#type.size(),
+            (_, PlyProperty::List { count_type, .. }) => count_type.size(),
+        }).sum()
+    }
+
+    fn list_idx(&self, names: &[&str]) -> Option<usize> {
+        self.properties.iter().position(|p| match p {
+            PlyProperty::List { name, .. } => names.contains(&name.as_str()),
+            _ => false,
+        })
+    }
+}
+
+struct PlyBodyReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    format: PlyFormat,
+    line: u32,
+}
+
+impl<'a> PlyBodyReader<'a> {
+    fn next_token(&mut self) -> Result<&'a str, (ResourceParseError, u32)> {
+        while self.pos < self.data.len() && self.data[self.pos].is_ascii_whitespace() {
+            if self.data[self.pos] == b'\n' { self.line += 1 }
+            self.pos += 1;
+        }
+        let start = self.pos;
+        while self.pos < self.data.len() && !self.data[self.pos].is_ascii_whitespace() { self.pos += 1 }
+        if start == self.pos { return Err((ResourceParseError::UnexpectedEof, self.line)) }
+        std::str::from_utf8(&self.data[start..self.pos])
+            .map_err(|_| (ResourceParseError::InvalidHeader("non-ascii data in ascii body".to_owned()), self.line))
+    }
+
+    fn read(&mut self, r#type: PlyType) -> Result<f64, (ResourceParseError, u32)> {
+        if self.format == PlyFormat::Ascii {
+            let token = self.next_token()?;
+            return token.parse::<f64>()
+                .map_err(|err| (ResourceParseError::ParseFloatError { err, line: token.to_owned() }, self.line));
+        }
+
+        let size = r#type.size();
+        let bytes = self.data.get(self.pos..self.pos + size).ok_or((ResourceParseError::UnexpectedEof, self.line))?;
+        self.pos += size;
+        let mut b = [0u8; 8];
+        b[..size].copy_from_slice(bytes);
+        if self.format == PlyFormat::BinaryBigEndian { b[..size].reverse() }
+        Ok(match r#type {
+            PlyType::Int8 => b[0] as i8 as f64,
+            PlyType::UInt8 => b[0] as f64,
+            PlyType::Int16 => i16::from_le_bytes([b[0], b[1]]) as f64,
+            PlyType::UInt16 => u16::from_le_bytes([b[0], b[1]]) as f64,
+            PlyType::Int32 => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            PlyType::UInt32 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            PlyType::Float32 => f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            PlyType::Float64 => f64::from_le_bytes(b),
+        })
+    }
+}
+
+struct PlyHeader {
+    format: PlyFormat,
+    elements: Vec<PlyElement>,
+    body_start: usize,
+    line_count: u32,
+}
+
+pub struct PlyParser {}
+
+impl PlyParser {
+    pub fn parse_model(data: Vec<u8>, processing: MeshProcessing) -> Result<Model, (ResourceParseError, u32)> {
+        let header = Self::parse_header(&data)?;
+        let mut reader = PlyBodyReader { data: &data, pos: header.body_start, format: header.format, line: header.line_count + 1 };
+        let mut model_builder = ModelBuilder::new();
+        model_builder.set_mesh_processing(processing);
+        let mut vertex_count = 0;
+
+        for element in &header.elements {
+            // counts come from the file, so they are checked against the rest of the body before
+            // looping over them, elements without properties would not be bounded by it
+            let min_size = element.min_entry_size(header.format);
+            if element.count > 0 && min_size == 0 {
+                return Err((ResourceParseError::InvalidHeader(format!("element {} has no properties", element.name)), reader.line));
+            }
+            if element.count.checked_mul(min_size).is_none_or(|size| size > data.len() - reader.pos) {
+                return Err((ResourceParseError::UnexpectedEof, reader.line));
+            }
+
+            let mut values = vec![0.0; element.properties.len()];
+            let mut lists: Vec<Vec<f64>> = element.properties.iter().map(|_| vec![]).collect();
+
+            let position = [element.property_idx(&["x"]), element.property_idx(&["y"]), element.property_idx(&["z"])];
+            let normal = [element.property_idx(&["nx"]), element.property_idx(&["ny"]), element.property_idx(&["nz"])];
+            let color = [
+                element.property_idx(&["red", "r", "diffuse_red"]),
+                element.property_idx(&["green", "g", "diffuse_green"]),
+                element.property_idx(&["blue", "b", "diffuse_blue"]),
+            ];
+            let tex_coord = [
+                element.property_idx(&["u", "s", "texture_u", "texture_s"]),
+                element.property_idx(&["v", "t", "texture_v", "texture_t"]),
+            ];
+            let face_indices = element.list_idx(&["vertex_indices", "vertex_index"]);
+
+            for _ in 0..element.count {
+                for (i, property) in element.properties.iter().enumerate() {
+                    match property {
+                        PlyProperty::Scalar { r#type, .. } => values[i] = reader.read(*r#type)?,
+                        PlyProperty::List { count_type, item_type, .. } => {
+                            let count = reader.read(*count_type)? as usize;
+                            lists[i].clear();
+                            for _ in 0..count { lists[i].push(reader.read(*item_type)?) }
+                        }
+                    }
+                }
+
+                if element.name == "vertex" {
+                    let get = |idx: Option<usize>| idx.map(|i| values[i] as f32);
+                    let p = position.map(get);
+                    model_builder.add_position(Vector3::new(p[0].unwrap_or(0.0), p[1].unwrap_or(0.0), p[2].unwrap_or(0.0)));
+                    if let [Some(x), Some(y), Some(z)] = normal.map(get) {
+                        model_builder.add_normal(Vector3::new(x, y, z));
+                    }
+                    if let [Some(u), Some(v)] = tex_coord.map(get) {
+                        model_builder.add_tex_coord(Vector2::new(u, v));
+                    }
+                    if let [Some(r), Some(g), Some(b)] = color {
+                        let scale = |i: usize| match &element.properties[i] {
+                            PlyProperty::Scalar { r#type, .. } => (values[i] / r#type.color_scale()) as f32,
+                            _ => 0.0,
+                        };
+                        model_builder.add_color(Vector3::new(scale(r), scale(g), scale(b)));
+                    }
+                    vertex_count += 1;
+                } else if element.name == "face" {
+                    let indices = match face_indices {
+                        Some(i) => &lists[i],
+                        None => continue,
+                    };
+                    if let Some(idx) = indices.iter().find(|i| **i < 0.0 || **i as usize >= vertex_count) {
+                        return Err((ResourceParseError::IndexOutOfRange { idx: *idx as i64, len: vertex_count }, reader.line));
+                    }
+                    let bundle = |i: f64| IndexBundle { pos_idx: i as i32, tex_idx: i as i32, nor_idx: i as i32 };
+                    for i in 1..indices.len().saturating_sub(1) {
+                        model_builder.add_indices(bundle(indices[0]), bundle(indices[i]), bundle(indices[i + 1]), 0);
+                    }
+                }
+            }
+        }
+
+        model_builder.build().map_err(|e| (e, 0))
+    }
+
+    fn parse_header(data: &[u8]) -> Result<PlyHeader, (ResourceParseError, u32)> {
+        let invalid = |msg: &str, line: u32| (ResourceParseError::InvalidHeader(msg.to_owned()), line);
+        let mut format = None;
+        let mut elements: Vec<PlyElement> = vec![];
+        let mut pos = 0;
+        let mut line_nr = 0;
+
+        loop {
+            line_nr += 1;
+            let end = data[pos..].iter().position(|b| *b == b'\n').ok_or(invalid("missing end_header", line_nr))? + pos;
+            let line = std::str::from_utf8(&data[pos..end]).map_err(|_| invalid("header is not ascii", line_nr))?.trim();
+            pos = end + 1;
+
+            if line_nr == 1 {
+                if line != "ply" { return Err(invalid("missing ply magic", line_nr)) }
+                continue;
+            }
+
+            let values: Vec<&str> = line.split_whitespace().collect();
+            match values.first().copied() {
+                Some("format") => format = Some(match values.get(1).copied() {
+                    Some("ascii") => PlyFormat::Ascii,
+                    Some("binary_little_endian") => PlyFormat::BinaryLittleEndian,
+                    Some("binary_big_endian") => PlyFormat::BinaryBigEndian,
+                    _ => return Err(invalid(line, line_nr)),
+                }),
+                Some("element") => {
+                    if values.len() != 3 { return Err((ResourceParseError::InvalidLineArgCount { count: values.len(), line: line.to_owned() }, line_nr)) }
+                    let count = values[2].parse::<usize>()
+                        .map_err(|err| (ResourceParseError::ParseIntError { err, line: line.to_owned() }, line_nr))?;
+                    elements.push(PlyElement { name: values[1].to_owned(), count, properties: vec![] });
+                }
+                Some("property") => {
+                    let element = elements.last_mut().ok_or(invalid("property outside of element", line_nr))?;
+                    let r#type = |name: &str| PlyType::from_name(name).ok_or(invalid(line, line_nr));
+                    element.properties.push(match values.as_slice() {
+                        ["property", "list", count_type, item_type, name] => PlyProperty::List {
+                            name: name.to_string(), count_type: r#type(count_type)?, item_type: r#type(item_type)?,
+                        },
+                        ["property", scalar_type, name] => PlyProperty::Scalar { name: name.to_string(), r#type: r#type(scalar_type)? },
+                        _ => return Err((ResourceParseError::InvalidLineArgCount { count: values.len(), line: line.to_owned() }, line_nr)),
+                    });
+                }
+                Some("end_header") => {
+                    let format = format.ok_or(invalid("missing format", line_nr))?;
+                    return Ok(PlyHeader { format, elements, body_start: pos, line_count: line_nr });
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rendering::mesh_processing::MeshProcessing;
+    use crate::rendering::model::Model;
+    use crate::util::error::ResourceParseError;
+    use super::PlyParser;
+
+    // a unit quad of two triangles with normals and 8 bit colors
+    const QUAD_HEADER: &str = "\
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+";
+
+    const QUAD_VERTICES: [([f32; 6], [u8; 3]); 4] = [
+        ([0.0, 0.0, 0.0, 0.0, 0.0, 1.0], [255, 0, 0]),
+        ([1.0, 0.0, 0.0, 0.0, 0.0, 1.0], [0, 255, 0]),
+        ([1.0, 1.0, 0.0, 0.0, 0.0, 1.0], [0, 0, 255]),
+        ([0.0, 1.0, 0.0, 0.0, 0.0, 1.0], [255, 255, 255]),
+    ];
+
+    fn ascii_quad() -> Vec<u8> {
+        let mut data = format!("ply\nformat ascii 1.0\ncomment a quad\n{}", QUAD_HEADER);
+        for (v, c) in QUAD_VERTICES {
+            data += &format!("{} {} {} {} {} {} {} {} {}\n", v[0], v[1], v[2], v[3], v[4], v[5], c[0], c[1], c[2]);
+        }
+        data += "4 0 1 2 3\n";
+        data.into_bytes()
+    }
+
+    fn binary_quad(big_endian: bool) -> Vec<u8> {
+        let format = if big_endian { "binary_big_endian" } else { "binary_little_endian" };
+        let mut data = format!("ply\nformat {} 1.0\n{}", format, QUAD_HEADER).into_bytes();
+        let mut push = |bytes: &mut [u8]| {
+            if big_endian { bytes.reverse() }
+            data.extend_from_slice(bytes);
+        };
+        for (v, c) in QUAD_VERTICES {
+            v.iter().for_each(|f| push(&mut f.to_le_bytes()));
+            c.iter().for_each(|c| push(&mut [*c]));
+        }
+        push(&mut [4]);
+        [0i32, 1, 2, 3].iter().for_each(|i| push(&mut i.to_le_bytes()));
+        data
+    }
+
+    fn parse(data: Vec<u8>) -> Result<Model, (ResourceParseError, u32)> {
+        PlyParser::parse_model(data, MeshProcessing::default())
+    }
+
+    // the position, color and normal of a triangle corner
+    type Corner = ([f32; 3], [f32; 3], [f32; 3]);
+
+    fn corners(model: &Model) -> Vec<[Corner; 3]> {
+        let colors = model.colors().as_ref().expect("colors");
+        let normals = model.normals().as_ref().expect("normals");
+        let corner = |i: u32| {
+            let (p, c, n) = (model.positions()[i as usize], colors[i as usize], normals[i as usize]);
+            ([p.x, p.y, p.z], [c.x, c.y, c.z], [n.x, n.y, n.z])
+        };
+        model.triangles().iter().map(|t| [corner(t.p0), corner(t.p1), corner(t.p2)]).collect()
+    }
+
+    #[test]
+    fn ascii_faces_are_triangulated() {
+        let model = parse(ascii_quad()).unwrap();
+        let corners = corners(&model);
+        assert_eq!(corners.len(), 2);
+        assert_eq!(corners[0].map(|c| c.0), [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]]);
+        assert_eq!(corners[1].map(|c| c.0), [[0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]]);
+        // integer colors are mapped to [0, 1]
+        assert_eq!(corners[0].map(|c| c.1), [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]);
+        assert!(corners.iter().flatten().all(|c| c.2 == [0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn binary_files_parse_like_ascii_files() {
+        let ascii = corners(&parse(ascii_quad()).unwrap());
+        assert_eq!(corners(&parse(binary_quad(false)).unwrap()), ascii);
+        assert_eq!(corners(&parse(binary_quad(true)).unwrap()), ascii);
+    }
+
+    #[test]
+    fn truncated_input_is_an_error() {
+        for data in [ascii_quad(), binary_quad(false), binary_quad(true)] {
+            for end in 0..data.len() - 1 {
+                assert!(parse(data[..end].to_vec()).is_err(), "truncated to {} bytes: {:?}", end, String::from_utf8_lossy(&data[..end]));
+            }
+        }
+    }
+
+    #[test]
+    fn counts_larger_than_the_body_are_rejected() {
+        for format in ["ascii", "binary_little_endian"] {
+            let header = format!("ply\nformat {} 1.0\nelement vertex 4000000000\nproperty float x\nend_header\n", format);
+            assert!(matches!(parse(header.into_bytes()), Err((ResourceParseError::UnexpectedEof, _))));
+            let overflow = format!("ply\nformat {} 1.0\nelement vertex {}\nproperty double x\nend_header\n", format, usize::MAX);
+            assert!(matches!(parse(overflow.into_bytes()), Err((ResourceParseError::UnexpectedEof, _))));
+            let empty = format!("ply\nformat {} 1.0\nelement vertex 4000000000\nend_header\n0 0 0\n", format);
+            assert!(matches!(parse(empty.into_bytes()), Err((ResourceParseError::InvalidHeader(_), _))));
+        }
+        // list counts are bounded by the body as the items are read
+        let mut data = b"ply\nformat binary_little_endian 1.0\nelement face 1\nproperty list uint int vertex_indices\nend_header\n".to_vec();
+        data.extend(u32::MAX.to_le_bytes());
+        assert!(matches!(parse(data), Err((ResourceParseError::UnexpectedEof, _))));
+    }
+
+    #[test]
+    fn face_indices_have_to_refer_to_vertices() {
+        let data = String::from_utf8(ascii_quad()).unwrap().replace("4 0 1 2 3", "3 0 1 4");
+        // the face is on line 21, after 16 header lines and 4 vertices
+        assert!(matches!(parse(data.into_bytes()), Err((ResourceParseError::IndexOutOfRange { idx: 4, len: 4 }, 21))));
+        let data = String::from_utf8(ascii_quad()).unwrap().replace("4 0 1 2 3", "3 0 -1 2");
+        assert!(matches!(parse(data.into_bytes()), Err((ResourceParseError::IndexOutOfRange { idx: -1, .. }, _))));
+    }
+
+    #[test]
+    fn invalid_headers_are_rejected() {
+        for header in [
+            "plx\nformat ascii 1.0\nend_header\n",
+            "ply\nend_header\n",
+            "ply\nformat ascii 1.0\n",
+            "ply\nformat utf8 1.0\nend_header\n",
+            "ply\nformat ascii 1.0\nproperty float x\nend_header\n",
+            "ply\nformat ascii 1.0\nelement vertex 1\nproperty half x\nend_header\n0\n",
+            "ply\nformat ascii 1.0\nelement vertex -1\nend_header\n",
+        ] {
+            assert!(parse(header.as_bytes().to_vec()).is_err(), "{:?} parsed", header);
+        }
+    }
+}
@@ -1,4 +1,6 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::fs::File;
+use std::time::SystemTime;
 use std::{env, fs};
 use image::DynamicImage;
 use crate::util::error::{ResourceError, ResourceLoadError};
@@ -18,6 +20,16 @@ impl Resource {
         Ok(Self::new(exe_path.join(path)))
     }
 
+    // the resource directory in the source tree, which build.rs only copies next to the executable
+    // when building, so edits made while the program runs are not visible in the copy
+    pub fn new_rel_to_source(path: &str) -> Self {
+        Self::new(Path::new(env!("CARGO_MANIFEST_DIR")).join(path))
+    }
+
+    pub fn exists(&self) -> bool {
+        self.path.is_dir()
+    }
+
     fn resource_path(&self, name: &str) -> PathBuf {
         self.path.join(PathBuf::from(name))
     }
@@ -27,8 +39,22 @@ impl Resource {
             .map_err(|e| ResourceError::load_err(ResourceLoadError::Io { e }, name))
     }
 
+    pub fn read_bytes(&self, name: &str) -> Result<Vec<u8>, ResourceError> {
+        fs::read(self.resource_path(name))
+            .map_err(|e| ResourceError::load_err(ResourceLoadError::Io { e }, name))
+    }
+
+    pub fn open_file(&self, name: &str) -> Result<File, ResourceError> {
+        File::open(self.resource_path(name))
+            .map_err(|e| ResourceError::load_err(ResourceLoadError::Io { e }, name))
+    }
+
     pub fn read_image_file(&self, name: &str) -> Result<DynamicImage, ResourceError> {
         image::open(self.resource_path(name))
             .map_err(|e| ResourceError::load_err(ResourceLoadError::ImageError { e }, name))
     }
+
+    pub fn modified(&self, name: &str) -> Option<SystemTime> {
+        fs::metadata(self.resource_path(name)).and_then(|metadata| metadata.modified()).ok()
+    }
 }
@@ -1,63 +1,256 @@
 use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use crate::gl_wrapper::shader::{Shader, ShaderProgram, ShaderProgramBuilder};
 use crate::gl_wrapper::texture::Texture;
-use crate::gl_wrapper::types::{ShaderType, TextureFilter, TextureFormat};
+use crate::gl_wrapper::types::{ColorSpace, ShaderType, TextureFilter};
 use crate::rendering::material::Material;
+use crate::rendering::mesh_processing::MeshProcessing;
 use crate::rendering::model::Model;
+use image::DynamicImage;
+use crate::resource::file_watcher::FileWatcher;
+use crate::resource::gltf_parser::GltfParser;
+use crate::resource::obj_parser::ObjParser;
+use crate::resource::ply_parser::PlyParser;
 use crate::resource::resource::Resource;
 use crate::resource::resource_parser::ResourceParser;
-use crate::util::error::ResourceError;
+use crate::resource::shader_preprocessor::{PreprocessedShader, ShaderDefines};
+use crate::resource::stl_parser::StlParser;
+use crate::util::error::{ResourceError, ResourceLoadError, ShaderError};
+use crate::util::memory;
+
+const HOT_RELOAD_INTERVAL: Duration = Duration::from_millis(500);
+
+// shaders and shader programs are cached by their name and all defines they are compiled with, the
+// global defines merged with those of the variant
+type VariantKey = (String, ShaderDefines);
+
+// the names of the resources reloaded by ResourceManager::poll_changes. shader programs are relinked
+// in place, while gpu buffers built from models, materials and textures have to be rebuilt
+#[derive(Default)]
+pub struct ReloadedResources {
+    pub shader_programs: Vec<String>,
+    pub models: Vec<String>,
+    pub material_libs: Vec<String>,
+    pub textures: Vec<String>,
+}
+
+impl ReloadedResources {
+    pub fn scene_changed(&self) -> bool {
+        !self.models.is_empty() || !self.material_libs.is_empty() || !self.textures.is_empty()
+    }
+}
+
+// the names of the cached resources, sorted
+pub struct LoadedResources {
+    pub models: Vec<String>,
+    pub materials: Vec<String>,
+    pub textures: Vec<String>,
+    pub shader_programs: Vec<String>,
+}
+
+// how long loading a model took and how much memory it needed. memory is only measured for the
+// whole process, so the peak includes everything loaded before and is only known if it could be
+// reset before loading
+pub struct ModelLoadStats {
+    pub duration: Duration,
+    pub triangles: usize,
+    pub memory_before: Option<usize>,
+    pub peak_memory: Option<usize>,
+}
+
+impl fmt::Display for ModelLoadStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mib = |bytes: usize| bytes as f64 / (1024.0 * 1024.0);
+        write!(f, "{} triangles in {:.2?}", self.triangles, self.duration)?;
+        match (self.memory_before, self.peak_memory) {
+            (Some(before), Some(peak)) => write!(
+                f, ", peak process memory {:.1} MiB ({:+.1} MiB while loading)", mib(peak), mib(peak) - mib(before)
+            ),
+            _ => Ok(()),
+        }
+    }
+}
 
 pub struct ResourceManager {
-    loaded_material_libs: HashSet<String>,
+    mesh_processing: MeshProcessing,
+    shader_defines: ShaderDefines,
+    hot_reload: bool,
+    last_poll: Instant,
 
     models: HashMap<String, Arc<Mutex<Model>>>,
+    model_load_stats: HashMap<String, ModelLoadStats>,
     materials: HashMap<String, Arc<Material>>,
     textures: HashMap<String, Arc<Texture>>,
-    shaders: HashMap<String, Arc<Shader>>,
-    shader_programs: HashMap<String, Arc<Mutex<ShaderProgram>>>,
+    // material textures are kept as decoded images with their bit depth, MaterialBuffer packs them
+    // into texture arrays
+    material_images: HashMap<String, Arc<DynamicImage>>,
+    // images embedded in the model that is currently loaded, decoded once its materials need them
+    embedded_images: HashMap<String, DynamicImage>,
+    shaders: HashMap<VariantKey, Arc<Shader>>,
+    shader_programs: HashMap<VariantKey, Arc<Mutex<ShaderProgram>>>,
+
+    // names of the materials defined by every material lib or model file
+    material_sources: HashMap<String, Vec<String>>,
+    // the buffer and image files every glTF model was read from besides its own file
+    model_files: HashMap<String, Vec<String>>,
+    // names of the shaders linked into every shader program, compiled with the program's defines
+    shader_program_sources: HashMap<VariantKey, Vec<String>>,
+    // the shader and included files every shader was compiled from
+    shader_files: HashMap<VariantKey, Vec<String>>,
 
     model_res: Resource,
     texture_res: Resource,
     shader_res: Resource,
+
+    model_watcher: FileWatcher,
+    texture_watcher: FileWatcher,
+    shader_watcher: FileWatcher,
 }
 
 impl ResourceManager {
     pub fn new(model_res_path: &str, texture_res_path: &str, shader_res_path: &str) -> Result<Self, ResourceError> {
-        Ok(Self {
-            loaded_material_libs: HashSet::new(),
+        Ok(Self::with_resources(
+            Resource::new_rel_to_exe(model_res_path)?,
+            Resource::new_rel_to_exe(texture_res_path)?,
+            Resource::new_rel_to_exe(shader_res_path)?,
+            false,
+        ))
+    }
+
+    // loads from the resource directories in the source tree if they exist, so that edits to them
+    // are picked up by poll_changes without rebuilding
+    pub fn new_hot_reload(model_res_path: &str, texture_res_path: &str, shader_res_path: &str) -> Result<Self, ResourceError> {
+        let resource = |path: &str| {
+            let source = Resource::new_rel_to_source(path);
+            if source.exists() { Ok(source) } else { Resource::new_rel_to_exe(path) }
+        };
+        Ok(Self::with_resources(resource(model_res_path)?, resource(texture_res_path)?, resource(shader_res_path)?, true))
+    }
+
+    fn with_resources(model_res: Resource, texture_res: Resource, shader_res: Resource, hot_reload: bool) -> Self {
+        Self {
+            mesh_processing: MeshProcessing::default(),
+            shader_defines: ShaderDefines::new(),
+            hot_reload,
+            last_poll: Instant::now(),
 
             models: HashMap::new(),
+            model_load_stats: HashMap::new(),
             materials: HashMap::new(),
             textures: HashMap::new(),
+            material_images: HashMap::new(),
+            embedded_images: HashMap::new(),
             shaders: HashMap::new(),
             shader_programs: HashMap::new(),
 
-            model_res: Resource::new_rel_to_exe(model_res_path)?,
-            texture_res: Resource::new_rel_to_exe(texture_res_path)?,
-            shader_res: Resource::new_rel_to_exe(shader_res_path)?,
-        })
+            material_sources: HashMap::new(),
+            model_files: HashMap::new(),
+            shader_program_sources: HashMap::new(),
+            shader_files: HashMap::new(),
+
+            model_res,
+            texture_res,
+            shader_res,
+
+            model_watcher: FileWatcher::new(),
+            texture_watcher: FileWatcher::new(),
+            shader_watcher: FileWatcher::new(),
+        }
+    }
+
+    // applies to models loaded after the call
+    pub fn set_mesh_processing(&mut self, processing: MeshProcessing) {
+        self.mesh_processing = processing;
+    }
+
+    // defined in every shader compiled after the call, the defines of a variant take precedence
+    pub fn set_shader_define(&mut self, name: &str, value: impl ToString) {
+        self.shader_defines.set(name, value);
     }
 
     pub fn load_model(&mut self, name: &str) -> Result<(), ResourceError> {
-        let model = ResourceParser::parse_model(self.model_res.read_file(name)?)
-            .map_err(|(e, l)| ResourceError::parse_err(e, l, name))?;
-        self.load_model_material_libs(&model)?;
+        let model = self.parse_model(name)?;
+        self.model_watcher.watch(&self.model_res, name);
         self.models.insert(name.to_owned(), Arc::new(Mutex::new(model)));
         Ok(())
     }
 
+    // the stats of the last successful load or reload of the model
+    pub fn model_load_stats(&self, name: &str) -> Option<&ModelLoadStats> {
+        self.model_load_stats.get(name)
+    }
+
+    fn parse_model(&mut self, name: &str) -> Result<Model, ResourceError> {
+        let start = Instant::now();
+        let memory_before = memory::memory_usage();
+        let peak_reset = memory::reset_peak_memory_usage();
+        let model = self.parse_model_file(name)?;
+        self.model_load_stats.insert(name.to_owned(), ModelLoadStats {
+            duration: start.elapsed(),
+            triangles: model.triangles().len(),
+            memory_before,
+            peak_memory: if peak_reset { memory::peak_memory_usage() } else { None },
+        });
+        Ok(model)
+    }
+
+    fn parse_model_file(&mut self, name: &str) -> Result<Model, ResourceError> {
+        Ok(match Self::file_extension(name).as_str() {
+            "obj" => self.load_obj_model(name)?,
+            "gltf" | "glb" => self.load_gltf_model(name)?,
+            "ply" => PlyParser::parse_model(self.model_res.read_bytes(name)?, self.mesh_processing)
+                .map_err(|(e, l)| ResourceError::parse_err(e, l, name))?,
+            "stl" => StlParser::parse_model(self.model_res.read_bytes(name)?, self.mesh_processing)
+                .map_err(|(e, l)| ResourceError::parse_err(e, l, name))?,
+            _ => return Err(ResourceError::load_err(ResourceLoadError::InvalidFileExtension(name.to_owned()), name)),
+        })
+    }
+
+    fn file_extension(name: &str) -> String {
+        name.rsplit_once('.').map(|(_, ext)| ext.to_lowercase()).unwrap_or_default()
+    }
+
+    fn load_obj_model(&mut self, name: &str) -> Result<Model, ResourceError> {
+        let model = ObjParser::parse_model(self.model_res.open_file(name)?, name, self.mesh_processing)?;
+        self.load_model_material_libs(&model)?;
+        Ok(model)
+    }
+
+    fn load_gltf_model(&mut self, name: &str) -> Result<Model, ResourceError> {
+        let gltf = GltfParser::parse(self.model_res.read_bytes(name)?, name, &self.model_res, self.mesh_processing)?;
+        gltf.files.iter().for_each(|file| self.model_watcher.watch(&self.model_res, file));
+        self.model_files.insert(name.to_owned(), gltf.files);
+        gltf.images.into_iter().map(|(image_name, data)| {
+            let image = image::load_from_memory(&data)
+                .map_err(|e| ResourceError::load_err(ResourceLoadError::ImageError { e }, &image_name))?;
+            self.embedded_images.insert(image_name, image);
+            Ok(())
+        }).collect::<Result<Vec<_>, _>>()?;
+        let result = gltf.materials.into_iter()
+            .map(|(mat_name, mat)| self.handle_material(mat_name.clone(), mat, name).map(|_| mat_name))
+            .collect::<Result<Vec<_>, _>>();
+        self.embedded_images.clear();
+        self.material_sources.insert(name.to_owned(), result?);
+        Ok(gltf.model)
+    }
+
+    // material libs shared by several models are only loaded once
     fn load_model_material_libs(&mut self, model: &Model) -> Result<(), ResourceError> {
-        model.get_material_libs().iter().map(|lib| self.load_mat_lib(lib)).collect::<Result<_, _>>()
+        for lib in model.get_material_libs() {
+            if !self.material_sources.contains_key(lib) { self.load_mat_lib(lib)? }
+        }
+        Ok(())
     }
 
     fn load_mat_lib(&mut self, lib_name: &str) -> Result<(), ResourceError> {
-        self.loaded_material_libs.insert(lib_name.to_owned());
         let data = self.model_res.read_file(lib_name)?;
-        ResourceParser::parse_material_lib(data, lib_name)?
-            .into_iter().map(|(mat_name, mat)| self.handle_material(mat_name, mat, lib_name))
+        self.model_watcher.watch(&self.model_res, lib_name);
+        let names = ResourceParser::parse_material_lib(data, lib_name)?
+            .into_iter().map(|(mat_name, mat)| self.handle_material(mat_name.clone(), mat, lib_name).map(|_| mat_name))
             .collect::<Result<Vec<_>, _>>()?;
+        self.material_sources.insert(lib_name.to_owned(), names);
         Ok(())
     }
 
@@ -72,44 +265,220 @@ impl ResourceManager {
     }
 
     fn load_textures(&mut self, material: &Material) -> Result<(), ResourceError> {
-        material.get_texture_names().into_iter().map(|name| {
-            if !self.textures.contains_key(&name) {
-                self.load_texture(&name)?;
+        material.get_textures().into_iter().map(|(name, _)| {
+            // images of a model that is loaded again replace the ones it was loaded with before
+            if !self.material_images.contains_key(&name) || self.embedded_images.contains_key(&name) {
+                self.load_material_image(&name)?;
             }
             Ok(())
         }).collect::<Result<Vec<_>, _>>()?;
         Ok(())
     }
 
+    // textures not used by materials, like lookup or noise textures, are linear and not mipmapped
     fn load_texture(&mut self, name: &str) -> Result<(), ResourceError> {
-        let texture = Texture::from_data(
-            TextureFormat::RGBA8, TextureFilter::Linear,
-            &self.texture_res.read_image_file(&name)?.into_rgb8(),
-        );
+        let image = self.texture_res.read_image_file(name)?;
+        self.texture_watcher.watch(&self.texture_res, name);
+        let texture = Texture::from_image(&image, ColorSpace::Linear, TextureFilter::Linear);
         self.textures.insert(name.to_owned(), Arc::new(texture));
         Ok(())
     }
 
-    pub fn create_shader_program(&mut self, name: &str, vert: &str, frag: &str) -> Result<Arc<Mutex<ShaderProgram>>, ResourceError> {
-        let vert = self.get_shader(vert)?;
-        let frag = self.get_shader(frag)?;
-        self.shader_programs.insert(
-            name.to_owned(),
-            Arc::new(Mutex::new(ShaderProgramBuilder::new()
-                .add_shader(vert.clone())
-                .add_shader(frag.clone())
-                .build().map_err(|e| ResourceError::shader_err(e, name))?
-        )));
-        Ok(self.shader_programs.get(name).unwrap().clone())
+    fn load_material_image(&mut self, name: &str) -> Result<(), ResourceError> {
+        let image = match self.embedded_images.get(name) {
+            Some(image) => image.clone(),
+            None => {
+                let image = self.texture_res.read_image_file(name)?;
+                self.texture_watcher.watch(&self.texture_res, name);
+                image
+            }
+        };
+        self.material_images.insert(name.to_owned(), Arc::new(image));
+        Ok(())
+    }
+
+    // every set of defines creates a separate variant of the program, variants that were already
+    // created are returned from the cache
+    pub fn create_shader_program(&mut self, name: &str, vert: &str, frag: &str, defines: &ShaderDefines) -> Result<Arc<Mutex<ShaderProgram>>, ResourceError> {
+        let key = self.variant_key(name, defines);
+        if let Some(program) = self.shader_programs.get(&key) { return Ok(program.clone()) }
+        let sources = vec![vert.to_owned(), frag.to_owned()];
+        let program = self.link_shader_program(&key, &sources)?;
+        self.shader_program_sources.insert(key.clone(), sources);
+        self.shader_programs.insert(key.clone(), Arc::new(Mutex::new(program)));
+        Ok(self.shader_programs.get(&key).unwrap().clone())
     }
 
-    fn load_shader(&mut self, name: &str) -> Result<(), ResourceError> {
+    fn link_shader_program(&mut self, (name, defines): &VariantKey, sources: &[String]) -> Result<ShaderProgram, ResourceError> {
+        sources.iter()
+            .try_fold(ShaderProgramBuilder::new().set_label(&Self::variant_name(name, defines)), |builder, shader| Ok(builder.add_shader(self.get_shader(shader, defines)?)))?
+            .build().map_err(|e| ResourceError::shader_err(e, name))
+    }
+
+    // the defines are those of a variant key, so the global defines are already merged into them
+    fn load_shader(&mut self, name: &str, defines: &ShaderDefines) -> Result<(), ResourceError> {
         let r#type = ShaderType::from_file_name(name).map_err(|e| ResourceError::shader_err(e, name))?;
-        self.shaders.insert(name.to_owned(), Arc::new(Shader::new(r#type, self.shader_res.read_file(name)?)
-            .map_err(|e| ResourceError::shader_err(e, name))?));
+        let preprocessed = PreprocessedShader::new(name, &self.shader_res, defines)?;
+        preprocessed.files.iter().for_each(|file| self.shader_watcher.watch(&self.shader_res, file));
+        let key = (name.to_owned(), defines.clone());
+        self.shader_files.insert(key.clone(), preprocessed.files.clone());
+        let shader = Shader::new(r#type, preprocessed.source.clone()).map_err(|e| match e {
+            ShaderError::CompileError(log) => ShaderError::CompileError(preprocessed.map_log(&log)),
+            e => e,
+        }).map_err(|e| ResourceError::shader_err(e, name))?;
+        self.shaders.insert(key, Arc::new(shader));
+        Ok(())
+    }
+
+    // reloads the resources whose files changed since the last poll, at most every HOT_RELOAD_INTERVAL.
+    // resources that fail to reload keep their previous version and the error is printed
+    pub fn poll_changes(&mut self) -> ReloadedResources {
+        let mut reloaded = ReloadedResources::default();
+        if !self.hot_reload || self.last_poll.elapsed() < HOT_RELOAD_INTERVAL { return reloaded }
+        self.last_poll = Instant::now();
+
+        // a changed include recompiles every shader including it
+        let changed_files = self.shader_watcher.changed(&self.shader_res);
+        let changed_shaders: Vec<VariantKey> = self.shader_files.iter()
+            .filter(|(_, files)| files.iter().any(|file| changed_files.contains(file)))
+            .map(|(shader, _)| shader.clone())
+            .collect();
+        let mut programs = HashSet::new();
+        for (name, defines) in changed_shaders {
+            match self.load_shader(&name, &defines) {
+                Ok(_) => programs.extend(self.shader_program_sources.iter()
+                    .filter(|((_, program_defines), sources)| *program_defines == defines && sources.contains(&name))
+                    .map(|(program, _)| program.clone())),
+                Err(e) => Self::print_reload_error(&Self::variant_name(&name, &defines), &e),
+            }
+        }
+        for key in programs {
+            let name = Self::variant_name(&key.0, &key.1);
+            match self.relink_shader_program(&key) {
+                Ok(_) => reloaded.shader_programs.push(name),
+                Err(e) => Self::print_reload_error(&name, &e),
+            }
+        }
+
+        // material libs are reloaded before the models using them, a changed buffer or image
+        // reloads the glTF models read from it
+        let changed_model_files = self.model_watcher.changed(&self.model_res);
+        let changed_libs: Vec<String> = changed_model_files.iter()
+            .filter(|name| !self.models.contains_key(*name) && self.material_sources.contains_key(*name))
+            .cloned()
+            .collect();
+        for name in changed_libs {
+            match self.reload_mat_lib(&name) {
+                Ok(_) => reloaded.material_libs.push(name),
+                Err(e) => Self::print_reload_error(&name, &e),
+            }
+        }
+        let mut changed_models: Vec<String> = self.models.keys()
+            .filter(|model| changed_model_files.contains(model) || self.model_files.get(*model)
+                .is_some_and(|files| files.iter().any(|file| changed_model_files.contains(file))))
+            .cloned()
+            .collect();
+        changed_models.sort();
+        for name in changed_models {
+            match self.reload_model(&name) {
+                Ok(_) => reloaded.models.push(name),
+                Err(e) => Self::print_reload_error(&name, &e),
+            }
+        }
+
+        for name in self.texture_watcher.changed(&self.texture_res) {
+            match self.reload_texture(&name) {
+                Ok(_) => reloaded.textures.push(name),
+                Err(e) => Self::print_reload_error(&name, &e),
+            }
+        }
+        reloaded
+    }
+
+    // handles to the program stay valid, the previous program is deleted when it is replaced
+    fn relink_shader_program(&mut self, key: &VariantKey) -> Result<(), ResourceError> {
+        let sources = self.shader_program_sources.get(key).cloned().unwrap_or_default();
+        let program = self.link_shader_program(key, &sources)?;
+        *self.shader_programs.get(key).unwrap().lock().unwrap() = program;
         Ok(())
     }
 
+    fn reload_model(&mut self, name: &str) -> Result<(), ResourceError> {
+        // materials defined in the model file itself are parsed again
+        let materials = self.remove_material_source(name);
+        match self.parse_model(name) {
+            Ok(model) => {
+                *self.models.get(name).unwrap().lock().unwrap() = model;
+                Ok(())
+            }
+            Err(e) => {
+                self.restore_material_source(name, materials);
+                Err(e)
+            }
+        }
+    }
+
+    fn reload_mat_lib(&mut self, lib_name: &str) -> Result<(), ResourceError> {
+        let materials = self.remove_material_source(lib_name);
+        let result = self.load_mat_lib(lib_name);
+        if result.is_err() { self.restore_material_source(lib_name, materials) }
+        result
+    }
+
+    fn remove_material_source(&mut self, source: &str) -> Vec<(String, Arc<Material>)> {
+        self.material_sources.remove(source).unwrap_or_default().into_iter()
+            .filter_map(|name| self.materials.remove(&name).map(|material| (name, material)))
+            .collect()
+    }
+
+    fn restore_material_source(&mut self, source: &str, materials: Vec<(String, Arc<Material>)>) {
+        self.material_sources.insert(source.to_owned(), materials.iter().map(|(name, _)| name.clone()).collect());
+        self.materials.extend(materials);
+    }
+
+    // replaces the cached textures, holders of the previous ones have to get them again
+    fn reload_texture(&mut self, name: &str) -> Result<(), ResourceError> {
+        if self.textures.contains_key(name) { self.load_texture(name)? }
+        if self.material_images.contains_key(name) { self.load_material_image(name)? }
+        Ok(())
+    }
+
+    pub fn loaded_resources(&self) -> LoadedResources {
+        fn sorted(names: impl Iterator<Item = String>) -> Vec<String> {
+            let mut names: Vec<String> = names.collect();
+            names.sort();
+            names.dedup();
+            names
+        }
+        LoadedResources {
+            models: sorted(self.models.keys().cloned()),
+            materials: sorted(self.materials.keys().cloned()),
+            textures: sorted(self.textures.keys().chain(self.material_images.keys()).cloned()),
+            shader_programs: sorted(self.shader_programs.keys().map(|(name, defines)| Self::variant_name(name, defines))),
+        }
+    }
+
+    pub fn warn_unset_uniforms(&self) {
+        self.shader_programs.values().for_each(|program| program.lock().unwrap().warn_unset_uniforms());
+    }
+
+    fn variant_key(&self, name: &str, defines: &ShaderDefines) -> VariantKey {
+        (name.to_owned(), self.shader_defines.merged(defines))
+    }
+
+    fn variant_name(name: &str, defines: &ShaderDefines) -> String {
+        if defines.is_empty() { name.to_owned() } else { format!("{} {}", name, defines) }
+    }
+
+    // compile and link logs are printed as they are, so that their line breaks are kept
+    pub fn print_reload_error(name: &str, e: &ResourceError) {
+        match e {
+            ResourceError::ShaderError { e: ShaderError::CompileError(log) | ShaderError::LinkError(log), .. } =>
+                println!("Failed to reload {}, keeping the previous version:\n{}", name, log),
+            _ => println!("Failed to reload {}, keeping the previous version: {:?}", name, e),
+        }
+    }
+
     pub fn get_model(&mut self, name: &str) -> Result<Arc<Mutex<Model>>, ResourceError> {
         if let Some(model) = self.models.get(name) { Ok(model.clone()) }
         else {
@@ -130,15 +499,24 @@ impl ResourceManager {
         }
     }
 
-    pub fn get_shader(&mut self, name: &str) -> Result<Arc<Shader>, ResourceError> {
-        if let Some(shader) = self.shaders.get(name) { Ok(shader.clone()) }
+    pub fn get_material_image(&mut self, name: &str) -> Result<Arc<DynamicImage>, ResourceError> {
+        if let Some(image) = self.material_images.get(name) { Ok(image.clone()) }
+        else {
+            self.load_material_image(name)?;
+            Ok(self.material_images.get(name).unwrap().clone())
+        }
+    }
+
+    pub fn get_shader(&mut self, name: &str, defines: &ShaderDefines) -> Result<Arc<Shader>, ResourceError> {
+        let key = self.variant_key(name, defines);
+        if let Some(shader) = self.shaders.get(&key) { Ok(shader.clone()) }
         else {
-            self.load_shader(name)?;
-            Ok(self.shaders.get(name).unwrap().clone())
+            self.load_shader(name, &key.1)?;
+            Ok(self.shaders.get(&key).unwrap().clone())
         }
     }
 
-    pub fn get_shader_program(&self, name: &str) -> Option<Arc<Mutex<ShaderProgram>>> {
-        self.shader_programs.get(name).cloned()
+    pub fn get_shader_program(&self, name: &str, defines: &ShaderDefines) -> Option<Arc<Mutex<ShaderProgram>>> {
+        self.shader_programs.get(&self.variant_key(name, defines)).cloned()
     }
 }
\ No newline at end of file
@@ -1,150 +1,280 @@
-use std::iter::Filter;
+use std::borrow::Cow;
 use std::ops::RangeBounds;
-use std::str::Split;
 use crate::util::error::{ResourceError, ResourceParseError};
-use cgmath::{Vector2, Vector3};
+use cgmath::{Array, Vector2, Vector3};
 use crate::raytracing::types::IndexBundle;
-use crate::rendering::material::{Material, MaterialLibBuilder};
-use crate::rendering::model::{Model, ModelBuilder};
+use crate::rendering::material::{Material, MaterialLibBuilder, TextureChannel, TextureMap};
+use crate::rendering::model::ModelBuilder;
 
 pub struct ResourceParser {}
 
 impl ResourceParser {
-    pub fn parse_model(data: String) -> Result<Model, (ResourceParseError, u32)> {
-        let mut model_builder = ModelBuilder::new();
-
-        data.split('\n').zip(1..).map(|(str, i)| {
-            if str.starts_with("mtllib ") {
-                let value = Self::parse_string_line(str).map_err(|e| (e, i))?;
-                model_builder.add_material_lib(value);
-            }
-            if str.starts_with("usemtl ") {
-                let value = Self::parse_string_line(str).map_err(|e| (e, i))?;
-                model_builder.add_material(value);
+    pub fn parse_model_line(model_builder: &mut ModelBuilder, str: &str) -> Result<(), ResourceParseError> {
+        let (keyword, rest) = Self::split_keyword(str);
+        match keyword {
+            "v" => {
+                let (position, color) = Self::parse_position_line(str)?;
+                model_builder.add_position(position);
+                if let Some(color) = color { model_builder.add_color(color) }
             }
-            if str.starts_with("f ") {
-                let values = Self::parse_index_line(str).map_err(|e| (e, i))?;
-                values.into_iter().for_each(|(i0, i1, i2)| {
-                    model_builder.add_indices(i0, i1, i2, model_builder.get_current_mat());
+            "vt" => model_builder.add_tex_coord(Self::parse_tex_coord_line(str)?),
+            "vn" => model_builder.add_normal(Self::parse_normal_line(str)?),
+            "f" => {
+                let bundles = Self::parse_index_line(model_builder, str, 3)?;
+                (1..bundles.len() - 1).for_each(|i| {
+                    model_builder.add_indices(bundles[0].clone(), bundles[i].clone(), bundles[i + 1].clone(), model_builder.get_current_mat());
                 });
             }
-            if str.starts_with("v ") {
-                let values = Self::parse_line(str, 3..=3).map_err(|e| (e, i))?;
-                model_builder.add_position(Vector3::new(values[0], values[1], values[2]));
-            }
-            if str.starts_with("vt ") {
-                let values = Self::parse_line(str, 2..=3).map_err(|e| (e, i))?;
-                model_builder.add_tex_coord(Vector2::new(values[0], values[1]));
-            }
-            if str.starts_with("vn ") {
-                let values = Self::parse_line(str, 3..=3).map_err(|e| (e, i))?;
-                model_builder.add_normal(Vector3::new(values[0], values[1], values[2]));
+            "l" => {
+                let bundles = Self::parse_index_line(model_builder, str, 2)?;
+                bundles.windows(2).for_each(|b| model_builder.add_line(b[0].clone(), b[1].clone()));
             }
-            Ok(())
-        }).collect::<Result<Vec<_>, _>>()?;
-
-        Ok(model_builder.build())
+            "o" => model_builder.set_object(rest.to_owned()),
+            "g" => model_builder.set_groups(rest.split_whitespace().map(str::to_owned).collect()),
+            "s" => model_builder.set_smoothing_group(match rest {
+                "off" | "" => 0,
+                value => value.parse::<u32>().map_err(|err| ResourceParseError::ParseIntError { err, line: str.to_owned() })?,
+            }),
+            "mtllib" => Self::split_file_names(rest).into_iter().for_each(|lib| model_builder.add_material_lib(lib)),
+            "usemtl" => model_builder.add_material(Self::parse_string_line(str)?),
+            // free-form geometry, points, display and render attributes are not supported and skipped
+            _ => {}
+        }
+        Ok(())
     }
 
     pub fn parse_material_lib(data: String, name: &str) -> Result<Vec<(String, Material)>, ResourceError> {
         let mut lib_builder = MaterialLibBuilder::new();
 
-        data.split('\n').zip(1..).map(|(str, i)| {
-            if str.starts_with("newmtl ") {
-                let value = Self::parse_string_line(str).map_err(|e| (e, i))?;
-                lib_builder.add_material(value);
-            }
-            if str.starts_with("Ka ") {
-                let values = Self::parse_line(str, 3..=3).map_err(|e| (e, i))?;
-                lib_builder.ambient_color(Vector3::new(values[0], values[1], values[2])).map_err(|e| (e, i))?;
-            }
-            if str.starts_with("Kd ") {
-                let values = Self::parse_line(str, 3..=3).map_err(|e| (e, i))?;
-                lib_builder.diffuse_color(Vector3::new(values[0], values[1], values[2])).map_err(|e| (e, i))?;
-            }
-            if str.starts_with("Ks ") {
-                let values = Self::parse_line(str, 3..=3).map_err(|e| (e, i))?;
-                lib_builder.specular_color(Vector3::new(values[0], values[1], values[2])).map_err(|e| (e, i))?;
-            }
-            if str.starts_with("Tf ") {
-                let values = Self::parse_line(str, 3..=3).map_err(|e| (e, i))?;
-                lib_builder.transmission_color(Vector3::new(values[0], values[1], values[2])).map_err(|e| (e, i))?;
-            }
-            if str.starts_with("Ns ") {
-                let values = Self::parse_line(str, 1..=1).map_err(|e| (e, i))?;
-                lib_builder.specular_exp(values[0]).map_err(|e| (e, i))?;
-            }
-            if str.starts_with("d ") {
-                let values = Self::parse_line(str, 1..=1).map_err(|e| (e, i))?;
-                lib_builder.transmission(1.0 - values[0]).map_err(|e| (e, i))?;
-            }
-            if str.starts_with("Tr ") {
-                let values = Self::parse_line(str, 1..=1).map_err(|e| (e, i))?;
-                lib_builder.transmission(values[0]).map_err(|e| (e, i))?;
-            }
-            if str.starts_with("Ni ") {
-                let values = Self::parse_line(str, 1..=1).map_err(|e| (e, i))?;
-                lib_builder.optical_density(values[0]).map_err(|e| (e, i))?;
-            }
-            if str.starts_with("map_Ka ") {
-                let value = Self::parse_string_line(str).map_err(|e| (e, i))?;
-                lib_builder.ambient_tex(value).map_err(|e| (e, i))?;
+        Self::logical_lines(&data).try_for_each(|(str, i)| {
+            Self::parse_material_line(&mut lib_builder, &str).map_err(|e| (e, i))
+        }).map_err(|(e, i)| ResourceError::parse_err(e, i, name))?;
+
+        Ok(lib_builder.build())
+    }
+
+    fn parse_material_line(lib_builder: &mut MaterialLibBuilder, str: &str) -> Result<(), ResourceParseError> {
+        let (keyword, _) = Self::split_keyword(str);
+        match keyword {
+            "newmtl" => lib_builder.add_material(Self::parse_string_line(str)?),
+            "Ka" => lib_builder.ambient_color(Self::parse_color_line(str)?)?,
+            "Kd" => lib_builder.diffuse_color(Self::parse_color_line(str)?)?,
+            "Ks" => lib_builder.specular_color(Self::parse_color_line(str)?)?,
+            "Tf" => lib_builder.transmission_color(Self::parse_color_line(str)?)?,
+            "Ns" => lib_builder.specular_exp(Self::parse_line(str, 1..=1)?[0])?,
+            "d" => lib_builder.transmission(1.0 - Self::parse_line(str, 1..=1)?[0])?,
+            "Tr" => lib_builder.transmission(Self::parse_line(str, 1..=1)?[0])?,
+            "Ni" => lib_builder.optical_density(Self::parse_line(str, 1..=1)?[0])?,
+            "Ke" => lib_builder.emissive_color(Self::parse_color_line(str)?)?,
+            "Pr" => lib_builder.roughness(Self::parse_line(str, 1..=1)?[0])?,
+            "Pm" => lib_builder.metallic(Self::parse_line(str, 1..=1)?[0])?,
+            "illum" => {
+                let value = Self::parse_string_line(str)?;
+                lib_builder.illum(value.parse::<u32>().map_err(|err| ResourceParseError::ParseIntError { err, line: str.to_owned() })?)?
             }
-            if str.starts_with("map_Kd ") {
-                let value = Self::parse_string_line(str).map_err(|e| (e, i))?;
-                lib_builder.diffuse_tex(value).map_err(|e| (e, i))?;
+            "map_Ka" => lib_builder.ambient_tex(Self::parse_texture_line(str)?)?,
+            "map_Kd" => lib_builder.diffuse_tex(Self::parse_texture_line(str)?)?,
+            "map_Ks" => lib_builder.specular_tex(Self::parse_texture_line(str)?)?,
+            "map_Ns" => lib_builder.specular_exp_tex(Self::parse_texture_line(str)?)?,
+            "map_Ke" => lib_builder.emissive_tex(Self::parse_texture_line(str)?)?,
+            "map_d" => lib_builder.alpha_tex(Self::parse_texture_line(str)?)?,
+            "map_bump" | "map_Bump" | "bump" => lib_builder.bump_tex(Self::parse_texture_line(str)?)?,
+            "norm" | "map_Kn" => lib_builder.normal_tex(Self::parse_texture_line(str)?)?,
+            "disp" => lib_builder.displacement_tex(Self::parse_texture_line(str)?)?,
+            "decal" => lib_builder.decal_tex(Self::parse_texture_line(str)?)?,
+            // reflection maps are not supported, reflections are ray traced instead
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // yields (line, line number) pairs with comments stripped and "\" continuations joined,
+    // accepting "\n", "\r\n" and "\r" line endings
+    pub fn logical_lines(data: &str) -> impl Iterator<Item = (Cow<'_, str>, u32)> {
+        let mut physical = data.split('\n')
+            .flat_map(|l| l.strip_suffix('\r').unwrap_or(l).split('\r'))
+            .zip(1..);
+        std::iter::from_fn(move || {
+            let (first, i) = physical.next()?;
+            let mut line = Cow::Borrowed(first);
+            while line.trim_end().ends_with('\\') {
+                let joined = line.trim_end().trim_end_matches('\\').to_owned();
+                match physical.next() {
+                    Some((next, _)) => line = Cow::Owned(joined + " " + next),
+                    None => { line = Cow::Owned(joined); break }
+                }
             }
-            if str.starts_with("map_Ks ") {
-                let value = Self::parse_string_line(str).map_err(|e| (e, i))?;
-                lib_builder.specular_tex(value).map_err(|e| (e, i))?;
+            let line = match line.find('#') {
+                Some(idx) => match line {
+                    Cow::Borrowed(l) => Cow::Borrowed(&l[..idx]),
+                    Cow::Owned(l) => Cow::Owned(l[..idx].to_owned()),
+                },
+                None => line,
+            };
+            Some((line, i))
+        }).filter(|(line, _)| !line.trim().is_empty())
+    }
+
+    pub fn parse_position_line(str: &str) -> Result<(Vector3<f32>, Option<Vector3<f32>>), ResourceParseError> {
+        let values = Self::parse_line(str, 3..=7)?;
+        // non-standard extension: "v x y z r g b" carries a vertex color
+        let color = if values.len() >= 6 {
+            let c = &values[values.len() - 3..];
+            Some(Vector3::new(c[0], c[1], c[2]))
+        } else { None };
+        Ok((Vector3::new(values[0], values[1], values[2]), color))
+    }
+
+    pub fn parse_tex_coord_line(str: &str) -> Result<Vector2<f32>, ResourceParseError> {
+        let values = Self::parse_line(str, 1..=3)?;
+        Ok(Vector2::new(values[0], *values.get(1).unwrap_or(&0.0)))
+    }
+
+    pub fn parse_normal_line(str: &str) -> Result<Vector3<f32>, ResourceParseError> {
+        let values = Self::parse_line(str, 3..=3)?;
+        Ok(Vector3::new(values[0], values[1], values[2]))
+    }
+
+    pub fn split_keyword(str: &str) -> (&str, &str) {
+        let str = str.trim();
+        match str.split_once(char::is_whitespace) {
+            Some((keyword, rest)) => (keyword, rest.trim()),
+            None => (str, ""),
+        }
+    }
+
+    // file names may contain spaces, so tokens are joined until one ends with the ".mtl" extension
+    fn split_file_names(str: &str) -> Vec<String> {
+        let mut names = vec![];
+        let mut current: Vec<&str> = vec![];
+        for token in str.split_whitespace() {
+            current.push(token);
+            if token.to_lowercase().ends_with(".mtl") {
+                names.push(current.join(" "));
+                current.clear();
             }
-            if str.starts_with("map_Ns ") {
-                let value = Self::parse_string_line(str).map_err(|e| (e, i))?;
-                lib_builder.specular_exp_tex(value).map_err(|e| (e, i))?;
+        }
+        if !current.is_empty() { names.push(current.join(" ")) }
+        names
+    }
+
+    fn parse_string_line(str: &str) -> Result<String, ResourceParseError> {
+        let (_, value) = Self::split_keyword(str);
+        if value.is_empty() { Err(ResourceParseError::InvalidLineArgCount { count: 1, line: str.to_owned() }) }
+        else { Ok(value.to_owned()) }
+    }
+
+    // parses "keyword [-option args...] file name", the file name may contain spaces
+    fn parse_texture_line(str: &str) -> Result<TextureMap, ResourceParseError> {
+        let (_, value) = Self::split_keyword(str);
+        let tokens = Self::split_line(value);
+        let invalid = || ResourceParseError::InvalidLineArgCount { count: tokens.len() + 1, line: str.to_owned() };
+        let float = |token: &str| token.parse::<f32>().map_err(|err| ResourceParseError::ParseFloatError { err, line: str.to_owned() });
+        let on_off = |token: &str| match token {
+            "on" => Ok(true),
+            "off" => Ok(false),
+            _ => Err(ResourceParseError::UnsupportedStatement(str.trim().to_owned())),
+        };
+
+        let mut map = TextureMap::new(String::new());
+        let mut i = 0;
+        // returns the argument following the option at i, the file name has to remain after it
+        let arg = |i: usize| tokens.get(i + 1).filter(|_| i + 2 < tokens.len()).copied().ok_or_else(invalid);
+        // reads up to three numbers, the first one is required
+        let vector = |i: usize, default: f32| -> Result<(Vector3<f32>, usize), ResourceParseError> {
+            let mut v = Vector3::from_value(default);
+            v.x = float(arg(i)?)?;
+            let mut count = 1;
+            while count < 3 && i + count + 2 < tokens.len() {
+                match tokens[i + count + 1].parse::<f32>() {
+                    Ok(f) => v[count] = f,
+                    Err(_) => break,
+                }
+                count += 1;
             }
-            Ok(())
-        }).collect::<Result<Vec<_>, _>>().map_err(|(e, i)| ResourceError::parse_err(e, i, name))?;
+            Ok((v, count))
+        };
 
-        Ok(lib_builder.build())
+        while i < tokens.len() && tokens[i].starts_with('-') {
+            let consumed = match tokens[i] {
+                "-blendu" => { map.blend_u = on_off(arg(i)?)?; 1 }
+                "-blendv" => { map.blend_v = on_off(arg(i)?)?; 1 }
+                "-cc" => { map.color_correction = on_off(arg(i)?)?; 1 }
+                "-clamp" => { map.clamp = on_off(arg(i)?)?; 1 }
+                "-bm" => { map.bump_multiplier = float(arg(i)?)?; 1 }
+                "-boost" => { map.boost = float(arg(i)?)?; 1 }
+                "-texres" => {
+                    let res = arg(i)?;
+                    map.resolution = Some(res.parse::<u32>().map_err(|err| ResourceParseError::ParseIntError { err, line: str.to_owned() })?);
+                    1
+                }
+                "-imfchan" => {
+                    map.channel = Some(TextureChannel::from_name(arg(i)?).ok_or_else(|| ResourceParseError::UnsupportedStatement(str.trim().to_owned()))?);
+                    1
+                }
+                "-mm" => {
+                    let gain = tokens.get(i + 2).filter(|_| i + 3 < tokens.len()).copied().ok_or_else(invalid)?;
+                    map.range = (float(arg(i)?)?, float(gain)?);
+                    2
+                }
+                "-o" => { let (v, count) = vector(i, 0.0)?; map.offset = v; count }
+                "-s" => { let (v, count) = vector(i, 1.0)?; map.scale = v; count }
+                "-t" => { let (v, count) = vector(i, 0.0)?; map.turbulence = v; count }
+                "-type" => 1,
+                _ => return Err(ResourceParseError::UnsupportedStatement(str.trim().to_owned())),
+            };
+            i += consumed + 1;
+        }
+
+        if i >= tokens.len() { return Err(invalid()) }
+        map.name = tokens[i..].join(" ");
+        Ok(map)
     }
 
-    fn parse_string_line(str: &str) -> Result<String, ResourceParseError> {
-        let values = Self::split_line(str);
-        let l = values.len();
-        if l != 2 { Err(ResourceParseError::InvalidLineArgCount { count: l, line: str.to_owned() }) }
-        else { Ok(values[1].trim().to_owned()) }
+    fn parse_color_line(str: &str) -> Result<Vector3<f32>, ResourceParseError> {
+        let (_, value) = Self::split_keyword(str);
+        if value.starts_with("spectral") || value.starts_with("xyz") {
+            return Err(ResourceParseError::UnsupportedStatement(str.trim().to_owned()));
+        }
+        // green and blue default to the red value when omitted
+        let values = Self::parse_line(str, 1..=3)?;
+        Ok(Vector3::new(values[0], *values.get(1).unwrap_or(&values[0]), *values.get(2).unwrap_or(&values[0])))
     }
 
     fn parse_line<R: RangeBounds<usize>>(str: &str, len: R) -> Result<Vec<f32>, ResourceParseError> {
         let values = Self::split_line(str);
         let l = values.len();
         if !len.contains(&(l - 1)) { Err(ResourceParseError::InvalidLineArgCount { count: l, line: str.to_owned() }) }
-        else { Ok(values.into_iter().skip(1).map(|str| str.trim().parse::<f32>().unwrap()).collect()) }
-    }
-
-    fn parse_index_line(str: &str) -> Result<Vec<(IndexBundle, IndexBundle, IndexBundle)>, ResourceParseError> {
-        let indices = Self::split_index_line(str);
-        let l = indices.len();
-        if l < 4 { Err(ResourceParseError::InvalidLineArgCount { count: l, line: str.to_owned() }) }
         else {
-            let num_tris = indices.len() - 3;
-            let bundles: Vec<IndexBundle> = indices.iter().skip(1).map(IndexBundle::new).collect::<Result<Vec<_>, _>>()?;
-            Ok((0..num_tris).into_iter().map(|i| (bundles[0].clone(), bundles[i + 1].clone(), bundles[i + 2].clone())).collect())
+            values.into_iter().skip(1)
+                .map(|value| value.parse::<f32>().map_err(|err| ResourceParseError::ParseFloatError { err, line: str.to_owned() }))
+                .collect()
         }
     }
 
-    #[inline]
-    fn split_line_iter(str: &str) -> Filter<Split<char>, fn(&&str) -> bool> {
-        str.split(' ').filter(|s| !s.trim().is_empty())
+    fn parse_index_line(model_builder: &ModelBuilder, str: &str, min_count: usize) -> Result<Vec<IndexBundle>, ResourceParseError> {
+        let counts = [model_builder.position_count(), model_builder.tex_coord_count(), model_builder.normal_count()];
+        Self::parse_index_bundles(str, min_count, counts)
     }
 
-    #[inline]
-    fn split_line(str: &str) -> Vec<&str> {
-        Self::split_line_iter(str).collect()
+    // parses the index bundles of a face or line statement and normalizes them against the
+    // position, tex coord and normal counts at that point in the file
+    pub fn parse_index_bundles(str: &str, min_count: usize, counts: [usize; 3]) -> Result<Vec<IndexBundle>, ResourceParseError> {
+        let [pos_len, tex_len, nor_len] = counts.map(|c| c as i32);
+        let (_, indices) = Self::split_keyword(str);
+        let bundles = indices.split_whitespace().map(|index| {
+            let mut bundle = IndexBundle::new(index)?;
+            bundle.normalize(pos_len, tex_len, nor_len)?;
+            Ok(bundle)
+        }).collect::<Result<Vec<_>, _>>()?;
+
+        let l = bundles.len() + 1;
+        if l < min_count + 1 { Err(ResourceParseError::InvalidLineArgCount { count: l, line: str.to_owned() }) }
+        else { Ok(bundles) }
     }
 
     #[inline]
-    fn split_index_line(str: &str) -> Vec<Vec<&str>> {
-        Self::split_line_iter(str).map(|s| s.split(['/', '\\']).collect()).collect()
+    fn split_line(str: &str) -> Vec<&str> {
+        str.split_whitespace().collect()
     }
 }
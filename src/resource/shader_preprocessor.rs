@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use crate::resource::resource::Resource;
+use crate::util::error::{ResourceError, ShaderError};
+
+// defines injected into a shader, kept sorted so that equal sets compare and hash the same way
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ShaderDefines {
+    defines: BTreeMap<String, String>,
+}
+
+impl ShaderDefines {
+    pub fn new() -> Self {
+        Self { defines: BTreeMap::new() }
+    }
+
+    pub fn with(mut self, name: &str, value: impl ToString) -> Self {
+        self.set(name, value);
+        self
+    }
+
+    // a define without a value, for features toggled with #ifdef
+    pub fn with_flag(self, name: &str) -> Self {
+        self.with(name, "")
+    }
+
+    pub fn set(&mut self, name: &str, value: impl ToString) {
+        self.defines.insert(name.to_owned(), value.to_string());
+    }
+
+    // the defines of both sets, values in other replace those of the same define in self
+    pub fn merged(&self, other: &ShaderDefines) -> ShaderDefines {
+        let mut defines = self.defines.clone();
+        defines.extend(other.defines.clone());
+        Self { defines }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.defines.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.defines.iter()
+    }
+}
+
+impl fmt::Display for ShaderDefines {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let defines: Vec<String> = self.defines.iter()
+            .map(|(name, value)| if value.is_empty() { name.clone() } else { format!("{}={}", name, value) })
+            .collect();
+        write!(f, "[{}]", defines.join(", "))
+    }
+}
+
+// a shader source with its includes resolved and the defines injected after the #version line.
+// every file is compiled as its own source string number, so that the line numbers in compile logs
+// refer to the original files
+pub struct PreprocessedShader {
+    pub source: String,
+    // the file of every source string number, starting with the shader itself
+    pub files: Vec<String>,
+}
+
+struct Preprocessor<'a> {
+    resource: &'a Resource,
+    defines: &'a ShaderDefines,
+    output: String,
+    files: Vec<String>,
+    include_stack: Vec<String>,
+}
+
+impl PreprocessedShader {
+    // include paths are relative to the shader directory. files are included once, later includes
+    // of the same file are skipped, as glsl does not allow redefining structs and functions
+    pub fn new(name: &str, resource: &Resource, defines: &ShaderDefines) -> Result<Self, ResourceError> {
+        let mut preprocessor = Preprocessor { resource, defines, output: String::new(), files: vec![], include_stack: vec![] };
+        preprocessor.process_file(name)?;
+        Ok(Self { source: preprocessor.output, files: preprocessor.files })
+    }
+
+    // replaces the source string numbers at the start of the log lines with file names. drivers differ
+    // in the format, e.g. "0(12) : error" or "ERROR: 0:12: error", so only the number is replaced
+    pub fn map_log(&self, log: &str) -> String {
+        log.lines().map(|line| self.map_log_line(line)).collect::<Vec<_>>().join("\n")
+    }
+
+    fn map_log_line(&self, line: &str) -> String {
+        let prefix_len = ["ERROR: ", "WARNING: "].iter()
+            .find(|prefix| line.starts_with(*prefix))
+            .map_or(0, |prefix| prefix.len());
+        let location = &line[prefix_len..];
+        let digits = location.chars().take_while(|c| c.is_ascii_digit()).count();
+        let is_location = digits > 0 && location[digits..].starts_with(['(', ':'])
+            && location[digits + 1..].starts_with(|c: char| c.is_ascii_digit());
+        let file = location[..digits].parse::<usize>().ok()
+            .filter(|_| is_location)
+            .and_then(|idx| self.files.get(idx));
+        match file {
+            Some(file) => format!("{}{}{}", &line[..prefix_len], file, &location[digits..]),
+            None => line.to_owned(),
+        }
+    }
+}
+
+impl Preprocessor<'_> {
+    fn process_file(&mut self, name: &str) -> Result<(), ResourceError> {
+        let source = self.resource.read_file(name)?;
+        let file_idx = self.files.len();
+        self.files.push(name.to_owned());
+        self.include_stack.push(name.to_owned());
+
+        for (i, line) in source.lines().enumerate() {
+            let directive = line.trim_start();
+            // the line after this one, as #line sets the number of the following line
+            let next_line = i + 2;
+            if let Some(include) = directive.strip_prefix("#include") {
+                let include = Self::parse_include(include)
+                    .ok_or_else(|| ResourceError::shader_err(ShaderError::InvalidInclude { line: i as u32 + 1, directive: directive.to_owned() }, name))?;
+                if self.include_stack.iter().any(|file| file == include) {
+                    let mut cycle = self.include_stack.clone();
+                    cycle.push(include.to_owned());
+                    return Err(ResourceError::shader_err(ShaderError::IncludeCycle(cycle), name));
+                }
+                if !self.files.iter().any(|file| file == include) {
+                    self.output.push_str(&format!("#line 1 {}\n", self.files.len()));
+                    self.process_file(include)?;
+                }
+                self.output.push_str(&format!("#line {} {}\n", next_line, file_idx));
+            } else if file_idx == 0 && directive.starts_with("#version") {
+                self.output.push_str(line);
+                self.output.push('\n');
+                for (define, value) in self.defines.iter() {
+                    self.output.push_str(&format!("#define {} {}\n", define, value));
+                }
+                self.output.push_str(&format!("#line {} 0\n", next_line));
+            } else {
+                self.output.push_str(line);
+                self.output.push('\n');
+            }
+        }
+
+        self.include_stack.pop();
+        Ok(())
+    }
+
+    // the quoted path of an include directive
+    fn parse_include(arguments: &str) -> Option<&str> {
+        let arguments = arguments.split("//").next().unwrap_or_default();
+        arguments.trim().strip_prefix('"')?.strip_suffix('"').filter(|path| !path.is_empty())
+    }
+}
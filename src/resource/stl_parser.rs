@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use cgmath::{InnerSpace, Vector3};
+use crate::raytracing::types::IndexBundle;
+use crate::rendering::mesh_processing::MeshProcessing;
+use crate::rendering::model::{Model, ModelBuilder};
+use crate::util::error::ResourceParseError;
+
+const BINARY_HEADER_SIZE: usize = 84;
+const BINARY_TRIANGLE_SIZE: usize = 50;
+
+struct StlModelBuilder {
+    model_builder: ModelBuilder,
+    position_map: HashMap<[u32; 3], i32>,
+    normal_count: i32,
+}
+
+impl StlModelBuilder {
+    fn new(processing: MeshProcessing) -> Self {
+        let mut model_builder = ModelBuilder::new();
+        model_builder.set_mesh_processing(processing);
+        Self { model_builder, position_map: HashMap::new(), normal_count: 0 }
+    }
+
+    // stl stores every facet separately, so shared corners are merged by their exact position
+    fn position_idx(&mut self, position: Vector3<f32>) -> i32 {
+        let key = [position.x.to_bits(), position.y.to_bits(), position.z.to_bits()];
+        let next_idx = self.position_map.len() as i32;
+        *self.position_map.entry(key).or_insert_with(|| {
+            self.model_builder.add_position(position);
+            next_idx
+        })
+    }
+
+    fn add_facet(&mut self, normal: Vector3<f32>, vertices: [Vector3<f32>; 3]) {
+        let normal = if normal.magnitude2() > 0.0 { normal.normalize() } else {
+            let n = (vertices[1] - vertices[0]).cross(vertices[2] - vertices[0]);
+            if n.magnitude2() > 0.0 { n.normalize() } else { Vector3::unit_y() }
+        };
+        self.model_builder.add_normal(normal);
+        let nor_idx = self.normal_count;
+        self.normal_count += 1;
+
+        let bundles = vertices.map(|v| IndexBundle { pos_idx: self.position_idx(v), tex_idx: IndexBundle::MISSING, nor_idx });
+        let [i0, i1, i2] = bundles;
+        self.model_builder.add_indices(i0, i1, i2, 0);
+    }
+
+    fn build(self) -> Result<Model, (ResourceParseError, u32)> {
+        self.model_builder.build().map_err(|e| (e, 0))
+    }
+}
+
+pub struct StlParser {}
+
+impl StlParser {
+    pub fn parse_model(data: Vec<u8>, processing: MeshProcessing) -> Result<Model, (ResourceParseError, u32)> {
+        if Self::is_binary(&data) { Self::parse_binary(&data, processing) }
+        else {
+            let str = std::str::from_utf8(&data)
+                .map_err(|_| (ResourceParseError::InvalidHeader("ascii stl contains invalid utf-8".to_owned()), 0))?;
+            Self::parse_ascii(str, processing)
+        }
+    }
+
+    // binary files may also start with "solid", so the size implied by the triangle count decides
+    fn is_binary(data: &[u8]) -> bool {
+        if data.len() < BINARY_HEADER_SIZE { return false }
+        let count = u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize;
+        data.len() == BINARY_HEADER_SIZE + count * BINARY_TRIANGLE_SIZE || !data.trim_ascii_start().starts_with(b"solid")
+    }
+
+    fn parse_binary(data: &[u8], processing: MeshProcessing) -> Result<Model, (ResourceParseError, u32)> {
+        let count = u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize;
+        if data.len() < BINARY_HEADER_SIZE + count * BINARY_TRIANGLE_SIZE {
+            return Err((ResourceParseError::UnexpectedEof, 0));
+        }
+
+        let mut stl_builder = StlModelBuilder::new(processing);
+        data[BINARY_HEADER_SIZE..].chunks_exact(BINARY_TRIANGLE_SIZE).take(count).for_each(|tri| {
+            let f = |i: usize| f32::from_le_bytes([tri[i * 4], tri[i * 4 + 1], tri[i * 4 + 2], tri[i * 4 + 3]]);
+            let v = |i: usize| Vector3::new(f(i), f(i + 1), f(i + 2));
+            stl_builder.add_facet(v(0), [v(3), v(6), v(9)]);
+        });
+        stl_builder.build()
+    }
+
+    fn parse_ascii(data: &str, processing: MeshProcessing) -> Result<Model, (ResourceParseError, u32)> {
+        let mut stl_builder = StlModelBuilder::new(processing);
+        let mut normal = Vector3::new(0.0, 0.0, 0.0);
+        let mut vertices = vec![];
+
+        data.lines().zip(1..).map(|(str, i)| {
+            let values: Vec<&str> = str.split_whitespace().collect();
+            match values.first().copied() {
+                Some("facet") => {
+                    normal = Self::parse_vector(str, &values, 2).map_err(|e| (e, i))?;
+                    vertices.clear();
+                }
+                Some("vertex") => vertices.push(Self::parse_vector(str, &values, 1).map_err(|e| (e, i))?),
+                Some("endfacet") => {
+                    if vertices.len() != 3 {
+                        return Err((ResourceParseError::InvalidLineArgCount { count: vertices.len(), line: str.to_owned() }, i));
+                    }
+                    stl_builder.add_facet(normal, [vertices[0], vertices[1], vertices[2]]);
+                }
+                _ => {}
+            }
+            Ok(())
+        }).collect::<Result<Vec<_>, _>>()?;
+
+        stl_builder.build()
+    }
+
+    fn parse_vector(str: &str, values: &[&str], skip: usize) -> Result<Vector3<f32>, ResourceParseError> {
+        if values.len() != skip + 3 {
+            return Err(ResourceParseError::InvalidLineArgCount { count: values.len(), line: str.to_owned() });
+        }
+        let v = values[skip..].iter()
+            .map(|v| v.parse::<f32>().map_err(|err| ResourceParseError::ParseFloatError { err, line: str.to_owned() }))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Vector3::new(v[0], v[1], v[2]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rendering::mesh_processing::MeshProcessing;
+    use crate::rendering::model::Model;
+    use crate::util::error::ResourceParseError;
+    use super::{StlParser, BINARY_HEADER_SIZE, BINARY_TRIANGLE_SIZE};
+
+    // two facets of a unit quad sharing an edge, the second one without a normal
+    const FACETS: [([f32; 3], [[f32; 3]; 3]); 2] = [
+        ([0.0, 0.0, 2.0], [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]]),
+        ([0.0, 0.0, 0.0], [[0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]]),
+    ];
+
+    fn ascii_quad() -> Vec<u8> {
+        let mut data = String::from("solid quad\n");
+        for (normal, vertices) in FACETS {
+            data += &format!("  facet normal {} {} {}\n    outer loop\n", normal[0], normal[1], normal[2]);
+            vertices.iter().for_each(|v| data += &format!("      vertex {} {} {}\n", v[0], v[1], v[2]));
+            data += "    endloop\n  endfacet\n";
+        }
+        data += "endsolid quad\n";
+        data.into_bytes()
+    }
+
+    fn binary_quad(header: &[u8]) -> Vec<u8> {
+        let mut data = header.to_vec();
+        data.resize(80, 0);
+        data.extend((FACETS.len() as u32).to_le_bytes());
+        for (normal, vertices) in FACETS {
+            [normal].iter().chain(&vertices).flatten().for_each(|f| data.extend(f.to_le_bytes()));
+            data.extend([0, 0]);
+        }
+        data
+    }
+
+    fn parse(data: Vec<u8>) -> Result<Model, (ResourceParseError, u32)> {
+        StlParser::parse_model(data, MeshProcessing::default())
+    }
+
+    // positions and normals of the corners of every triangle
+    fn corners(model: &Model) -> Vec<[([f32; 3], [f32; 3]); 3]> {
+        let normals = model.normals().as_ref().expect("normals");
+        let corner = |i: u32| {
+            let (p, n) = (model.positions()[i as usize], normals[i as usize]);
+            ([p.x, p.y, p.z], [n.x, n.y, n.z])
+        };
+        model.triangles().iter().map(|t| [corner(t.p0), corner(t.p1), corner(t.p2)]).collect()
+    }
+
+    #[test]
+    fn ascii_facets_share_their_corners() {
+        let model = parse(ascii_quad()).unwrap();
+        let corners = corners(&model);
+        assert_eq!(corners.iter().map(|t| t.map(|c| c.0)).collect::<Vec<_>>(), FACETS.map(|(_, v)| v).to_vec());
+        // normals are normalized and missing ones are computed from the winding
+        assert!(corners.iter().flatten().all(|c| c.1 == [0.0, 0.0, 1.0]));
+        assert!(!model.has_tex_coords());
+    }
+
+    #[test]
+    fn binary_files_parse_like_ascii_files() {
+        let ascii = corners(&parse(ascii_quad()).unwrap());
+        assert_eq!(corners(&parse(binary_quad(b"binary quad")).unwrap()), ascii);
+        // binary headers may start with "solid" as well, the size tells them apart
+        assert_eq!(corners(&parse(binary_quad(b"solid quad")).unwrap()), ascii);
+    }
+
+    #[test]
+    fn truncated_binary_files_are_an_error() {
+        let data = binary_quad(b"binary quad");
+        for end in BINARY_HEADER_SIZE..data.len() {
+            assert!(matches!(parse(data[..end].to_vec()), Err((ResourceParseError::UnexpectedEof, _))), "truncated to {} bytes", end);
+        }
+        let mut huge = data[..BINARY_HEADER_SIZE + BINARY_TRIANGLE_SIZE].to_vec();
+        huge[80..84].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(parse(huge), Err((ResourceParseError::UnexpectedEof, _))));
+    }
+
+    #[test]
+    fn truncated_ascii_files_never_panic() {
+        let data = ascii_quad();
+        for end in 0..data.len() {
+            if let Err((e, _)) = parse(data[..end].to_vec()) {
+                assert!(matches!(e, ResourceParseError::InvalidLineArgCount { .. } | ResourceParseError::ParseFloatError { .. }), "{:?}", e);
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_ascii_facets_are_rejected() {
+        let data = String::from_utf8(ascii_quad()).unwrap();
+        let missing_vertex = data.replacen("      vertex 1 1 0\n", "", 1);
+        assert!(matches!(parse(missing_vertex.into_bytes()), Err((ResourceParseError::InvalidLineArgCount { count: 2, .. }, 7))));
+        let invalid_number = data.replacen("vertex 1 0 0", "vertex 1 x 0", 1);
+        assert!(matches!(parse(invalid_number.into_bytes()), Err((ResourceParseError::ParseFloatError { .. }, 5))));
+        let short_normal = data.replacen("facet normal 0 0 2", "facet normal 0 0", 1);
+        assert!(matches!(parse(short_normal.into_bytes()), Err((ResourceParseError::InvalidLineArgCount { count: 4, .. }, 2))));
+    }
+}
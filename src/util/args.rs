@@ -0,0 +1,94 @@
+use std::path::PathBuf;
+use crate::rendering::mesh_processing::{MeshProcessing, NormalGeneration};
+use crate::window::window::{DisplayMode, WindowOptions};
+
+pub const USAGE: &str = "\
+usage: raytracer [--scene <name>] [window options] [mesh options] [--benchmark [options]]
+
+  --scene <name>          scene to load, one of the scene presets (default f16)
+  --width <n>             window width, the resolution in fullscreen (default 1000)
+  --height <n>            window height (default 800)
+  --fullscreen            exclusive fullscreen on the primary monitor
+  --borderless            a borderless window covering the primary monitor
+  --vsync                 waits for the vertical blank when presenting frames
+  --resolution-scale <s>  renders at the window size times s and scales the image to the window (default 1)
+  --normals <mode>        keep the normals of the models, generate missing ones or recompute all of them,
+                          one of keep, missing and recompute (default keep)
+  --smoothing-angle <deg> generated normals keep hard edges between faces meeting at a larger angle (default 60)
+  --tangents              generates tangents for models with normals and tex coords but without tangents
+  --weld <epsilon>        merges vertices closer than epsilon with otherwise equal attributes
+  --benchmark             renders a camera path at a fixed timestep and reports frame times, then exits
+  --camera-path <file>    path flown in the benchmark, an orbit around the scene by default
+  --seed <n>              seed of the random numbers used for rendering (default 0)
+  --warmup <frames>       frames rendered before measuring (default 60)
+  --fps <n>               frames per second of the camera path clock (default 60)
+  --output <file>         writes the report to a file instead of printing it";
+
+pub struct BenchmarkArgs {
+    pub camera_path: Option<PathBuf>,
+    pub warmup_frames: u32,
+    pub fps: u32,
+    pub output: Option<PathBuf>,
+}
+
+pub struct Args {
+    pub scene: String,
+    pub window: WindowOptions,
+    pub resolution_scale: f32,
+    pub mesh_processing: MeshProcessing,
+    // random numbers are seeded from the os if not set
+    pub seed: Option<u64>,
+    pub benchmark: Option<BenchmarkArgs>,
+}
+
+impl Args {
+    pub fn from_env() -> Result<Self, String> {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut scene = "f16".to_owned();
+        let mut window = WindowOptions::default();
+        let mut resolution_scale = 1.0;
+        let mut mesh_processing = MeshProcessing::default();
+        let mut seed = None;
+        let mut benchmark = false;
+        let mut benchmark_args = BenchmarkArgs { camera_path: None, warmup_frames: 60, fps: 60, output: None };
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("missing value for {}", arg));
+            match arg.as_str() {
+                "--scene" => scene = value()?,
+                "--width" => window.width = parse_number::<u32>(&arg, value()?)?.max(1),
+                "--height" => window.height = parse_number::<u32>(&arg, value()?)?.max(1),
+                "--fullscreen" => window.display_mode = DisplayMode::Fullscreen,
+                "--borderless" => window.display_mode = DisplayMode::Borderless,
+                "--vsync" => window.vsync = true,
+                "--resolution-scale" => resolution_scale = parse_number::<f32>(&arg, value()?)?.clamp(0.1, 4.0),
+                "--normals" => {
+                    let value = value()?;
+                    mesh_processing.normals = NormalGeneration::from_name(&value)
+                        .ok_or_else(|| format!("invalid value {} for {}", value, arg))?;
+                }
+                "--smoothing-angle" => mesh_processing.smoothing_angle = parse_number(&arg, value()?)?,
+                "--tangents" => mesh_processing.generate_tangents = true,
+                "--weld" => mesh_processing.weld_epsilon = Some(parse_number(&arg, value()?)?),
+                "--benchmark" => benchmark = true,
+                "--camera-path" => benchmark_args.camera_path = Some(PathBuf::from(value()?)),
+                "--seed" => seed = Some(parse_number(&arg, value()?)?),
+                "--warmup" => benchmark_args.warmup_frames = parse_number(&arg, value()?)?,
+                "--fps" => benchmark_args.fps = parse_number::<u32>(&arg, value()?)?.max(1),
+                "--output" => benchmark_args.output = Some(PathBuf::from(value()?)),
+                _ => return Err(format!("unknown argument {}", arg)),
+            }
+        }
+        // benchmarks are always seeded, with 0 unless a seed is given
+        if benchmark && seed.is_none() { seed = Some(0) }
+        Ok(Self { scene, window, resolution_scale, mesh_processing, seed, benchmark: benchmark.then_some(benchmark_args) })
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(arg: &str, value: String) -> Result<T, String> {
+    value.parse().map_err(|_| format!("invalid value {} for {}", value, arg))
+}
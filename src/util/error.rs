@@ -1,10 +1,11 @@
-use std::num::ParseIntError;
+use std::num::{ParseFloatError, ParseIntError};
 use image::ImageError;
 
 #[derive(Debug)]
 pub enum WindowError {
     GlfwInitError,
     CreateWindowError,
+    NoMonitor,
 }
 
 #[derive(Debug)]
@@ -12,12 +13,20 @@ pub enum ResourceLoadError {
     FileContainsNil,
     ImageError { e: ImageError },
     Io { e: std::io::Error },
+    InvalidFileExtension(String),
 }
 
 #[derive(Debug)]
 pub enum ResourceParseError {
     ParseIntError { err: ParseIntError, line: String },
+    ParseFloatError { err: ParseFloatError, line: String },
     InvalidLineArgCount { count: usize, line: String },
+    IndexOutOfRange { idx: i64, len: usize },
+    ZeroIndex { line: String },
+    InvalidHeader(String),
+    UnsupportedStatement(String),
+    UnexpectedEof,
+    InvalidUtf8,
     NoMaterialNamed,
 }
 
@@ -27,6 +36,7 @@ pub enum ResourceError {
     ResourceLoadError { e: ResourceLoadError, file_name: String },
     ResourceParseError { e: ResourceParseError, line: u32, file_name: String },
     ShaderError { e: ShaderError, file_name: String },
+    GltfError { e: GltfError, file_name: String },
     DuplicateMaterial { name: String, file_name: String },
     MaterialNotLoaded { name: String },
     ResourceNotLoaded(String),
@@ -44,6 +54,10 @@ impl ResourceError {
     pub fn shader_err(e: ShaderError, file_name: &str) -> Self {
         Self::ShaderError { e, file_name: file_name.to_owned() }
     }
+
+    pub fn gltf_err(e: GltfError, file_name: &str) -> Self {
+        Self::GltfError { e, file_name: file_name.to_owned() }
+    }
 }
 
 #[derive(Debug)]
@@ -51,9 +65,68 @@ pub enum ShaderError {
     InvalidFileExtension(String),
     CompileError(String),
     LinkError(String),
+    InvalidInclude { line: u32, directive: String },
+    // the chain of includes leading back to an included file
+    IncludeCycle(Vec<String>),
+}
+
+#[derive(Debug)]
+pub struct JsonError {
+    pub msg: String,
+    pub pos: usize,
+}
+
+#[derive(Debug)]
+pub enum GltfError {
+    JsonError(JsonError),
+    InvalidGlb(String),
+    MissingField(String),
+    InvalidIndex { kind: String, idx: usize },
+    InvalidAccessor(usize),
+    InvalidBase64,
+    UnsupportedUri(String),
+    Unsupported(String),
 }
 
 #[derive(Debug)]
 pub enum FramebufferError {
     Error(u32),
 }
+
+#[derive(Debug)]
+pub enum RenderGraphError {
+    DuplicatePass(String),
+    // a texture written by more than one pass
+    DuplicateOutput { texture: String, pass: String },
+    UnknownTexture { texture: String, pass: String },
+    FeedbackLoop { texture: String, pass: String },
+    // passes depending on each other's outputs
+    Cycle(Vec<String>),
+}
+
+#[derive(Debug)]
+pub enum CaptureError {
+    Io { e: std::io::Error },
+    ImageError { e: ImageError },
+    UnknownTexture(String),
+    // the window is minimized or the texture has no pixels
+    EmptyFrame,
+}
+
+#[derive(Debug)]
+pub enum CameraPathError {
+    Io { e: std::io::Error },
+    JsonError(JsonError),
+    InvalidKeyframe(usize),
+    // keyframes have to be sorted by time
+    UnsortedKeyframes,
+}
+
+#[derive(Debug)]
+pub enum ActionMapError {
+    ResourceError(ResourceError),
+    JsonError(JsonError),
+    NotAnObject,
+    UnknownAction(String),
+    InvalidBinding { action: String, binding: String },
+}
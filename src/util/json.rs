@@ -0,0 +1,355 @@
+use std::fmt::{Display, Formatter, Write};
+use crate::util::error::JsonError;
+
+// arrays and objects nested deeper are rejected instead of overflowing the stack
+const MAX_DEPTH: usize = 128;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(str: &str) -> Result<Self, JsonError> {
+        let mut parser = JsonParser { data: str.as_bytes(), pos: 0, depth: 0 };
+        let value = parser.parse_value()?;
+        parser.skip_whitespace();
+        if parser.pos != parser.data.len() { Err(parser.err("trailing characters")) }
+        else { Ok(value) }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn index(&self, idx: usize) -> Option<&Json> {
+        self.as_array().and_then(|a| a.get(idx))
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self { Json::Bool(b) => Some(*b), _ => None }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self { Json::Number(n) => Some(*n), _ => None }
+    }
+
+    pub fn as_f32(&self) -> Option<f32> { self.as_f64().map(|n| n as f32) }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        self.as_f64().filter(|n| *n >= 0.0 && n.fract() == 0.0).map(|n| n as usize)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self { Json::String(s) => Some(s), _ => None }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Json>> {
+        match self { Json::Array(a) => Some(a), _ => None }
+    }
+
+    pub fn as_f32_array(&self) -> Option<Vec<f32>> {
+        self.as_array()?.iter().map(Json::as_f32).collect()
+    }
+
+    pub fn object() -> Self { Json::Object(vec![]) }
+
+    pub fn with(mut self, key: &str, value: impl Into<Json>) -> Self {
+        if let Json::Object(entries) = &mut self { entries.push((key.to_owned(), value.into())) }
+        self
+    }
+}
+
+impl From<bool> for Json { fn from(b: bool) -> Self { Json::Bool(b) } }
+impl From<f64> for Json { fn from(n: f64) -> Self { Json::Number(n) } }
+impl From<f32> for Json { fn from(n: f32) -> Self { Json::Number(n as f64) } }
+impl From<u32> for Json { fn from(n: u32) -> Self { Json::Number(n as f64) } }
+impl From<usize> for Json { fn from(n: usize) -> Self { Json::Number(n as f64) } }
+impl From<&str> for Json { fn from(s: &str) -> Self { Json::String(s.to_owned()) } }
+impl From<String> for Json { fn from(s: String) -> Self { Json::String(s) } }
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(v: Vec<T>) -> Self { Json::Array(v.into_iter().map(Into::into).collect()) }
+}
+
+impl Display for Json {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) if n.is_finite() => write!(f, "{}", n),
+            Json::Number(_) => f.write_str("null"),
+            Json::String(s) => write_escaped(f, s),
+            Json::Array(a) => {
+                f.write_char('[')?;
+                for (i, v) in a.iter().enumerate() {
+                    if i > 0 { f.write_char(',')? }
+                    write!(f, "{}", v)?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(entries) => {
+                f.write_char('{')?;
+                for (i, (k, v)) in entries.iter().enumerate() {
+                    if i > 0 { f.write_char(',')? }
+                    write_escaped(f, k)?;
+                    write!(f, ":{}", v)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_escaped(f: &mut Formatter<'_>, s: &str) -> std::fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+struct JsonParser<'a> {
+    data: &'a [u8],
+    pos: usize,
+    // arrays and objects the parser is inside of
+    depth: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn err(&self, msg: &str) -> JsonError {
+        JsonError { msg: msg.to_owned(), pos: self.pos }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.data.len() && matches!(self.data[self.pos], b' ' | b'\t' | b'\n' | b'\r') {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.data.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), JsonError> {
+        if self.peek() == Some(c) { self.pos += 1; Ok(()) }
+        else { Err(self.err(&format!("expected '{}'", c as char))) }
+    }
+
+    fn parse_literal(&mut self, literal: &str, value: Json) -> Result<Json, JsonError> {
+        if self.data[self.pos..].starts_with(literal.as_bytes()) {
+            self.pos += literal.len();
+            Ok(value)
+        } else { Err(self.err("invalid literal")) }
+    }
+
+    fn parse_value(&mut self) -> Result<Json, JsonError> {
+        match self.peek() {
+            None => Err(self.err("unexpected end of input")),
+            Some(b'{') => self.parse_nested(Self::parse_object),
+            Some(b'[') => self.parse_nested(Self::parse_array),
+            Some(b'"') => Ok(Json::String(self.parse_string()?)),
+            Some(b't') => self.parse_literal("true", Json::Bool(true)),
+            Some(b'f') => self.parse_literal("false", Json::Bool(false)),
+            Some(b'n') => self.parse_literal("null", Json::Null),
+            Some(_) => self.parse_number(),
+        }
+    }
+
+    fn parse_nested(&mut self, parse: fn(&mut Self) -> Result<Json, JsonError>) -> Result<Json, JsonError> {
+        if self.depth == MAX_DEPTH { return Err(self.err("nesting too deep")) }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn parse_object(&mut self) -> Result<Json, JsonError> {
+        self.expect(b'{')?;
+        let mut entries = vec![];
+        if self.peek() == Some(b'}') { self.pos += 1; return Ok(Json::Object(entries)) }
+        loop {
+            if self.peek() != Some(b'"') { return Err(self.err("expected object key")) }
+            let key = self.parse_string()?;
+            self.expect(b':')?;
+            entries.push((key, self.parse_value()?));
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => { self.pos += 1; return Ok(Json::Object(entries)) }
+                _ => return Err(self.err("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Json, JsonError> {
+        self.expect(b'[')?;
+        let mut values = vec![];
+        if self.peek() == Some(b']') { self.pos += 1; return Ok(Json::Array(values)) }
+        loop {
+            values.push(self.parse_value()?);
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => { self.pos += 1; return Ok(Json::Array(values)) }
+                _ => return Err(self.err("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, JsonError> {
+        let hex = self.data.get(self.pos..self.pos + 4).ok_or(self.err("unexpected end of input"))?;
+        let code = std::str::from_utf8(hex).ok()
+            .and_then(|s| u32::from_str_radix(s, 16).ok())
+            .ok_or(self.err("invalid unicode escape"))?;
+        self.pos += 4;
+        Ok(code)
+    }
+
+    fn parse_string(&mut self) -> Result<String, JsonError> {
+        self.expect(b'"')?;
+        let mut bytes = vec![];
+        loop {
+            let c = *self.data.get(self.pos).ok_or(self.err("unterminated string"))?;
+            self.pos += 1;
+            match c {
+                b'"' => break,
+                b'\\' => {
+                    let e = *self.data.get(self.pos).ok_or(self.err("unterminated string"))?;
+                    self.pos += 1;
+                    match e {
+                        b'"' => bytes.push(b'"'),
+                        b'\\' => bytes.push(b'\\'),
+                        b'/' => bytes.push(b'/'),
+                        b'b' => bytes.push(8),
+                        b'f' => bytes.push(12),
+                        b'n' => bytes.push(b'\n'),
+                        b'r' => bytes.push(b'\r'),
+                        b't' => bytes.push(b'\t'),
+                        b'u' => {
+                            let mut code = self.parse_hex4()?;
+                            if (0xD800..0xDC00).contains(&code) && self.data[self.pos..].starts_with(b"\\u") {
+                                self.pos += 2;
+                                let low = self.parse_hex4()?;
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+                            }
+                            let c = char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER);
+                            bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                        }
+                        _ => return Err(self.err("invalid escape sequence")),
+                    }
+                }
+                c => bytes.push(c),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| self.err("invalid utf-8 in string"))
+    }
+
+    fn parse_number(&mut self) -> Result<Json, JsonError> {
+        let start = self.pos;
+        while self.pos < self.data.len() && matches!(self.data[self.pos], b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.data[start..self.pos]).ok()
+            .and_then(|s| s.parse::<f64>().ok())
+            .map(Json::Number)
+            .ok_or(JsonError { msg: "invalid number".to_owned(), pos: start })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Json, MAX_DEPTH};
+
+    fn parse(str: &str) -> Json {
+        Json::parse(str).unwrap_or_else(|e| panic!("failed to parse {:?}: {:?}", str, e))
+    }
+
+    #[test]
+    fn values_parse_into_their_variants() {
+        let json = parse(r#" { "a": [1, -2.5, 3e2, 1E-2], "b": true, "c": false, "d": null, "e": {} } "#);
+        assert_eq!(json.get("a").and_then(Json::as_f32_array), Some(vec![1.0, -2.5, 300.0, 0.01]));
+        assert_eq!(json.get("b").and_then(Json::as_bool), Some(true));
+        assert_eq!(json.get("c").and_then(Json::as_bool), Some(false));
+        assert_eq!(json.get("d"), Some(&Json::Null));
+        assert_eq!(json.get("e"), Some(&Json::object()));
+        assert_eq!(json.get("f"), None);
+        assert_eq!(json.get("a").and_then(|a| a.index(1)).and_then(Json::as_f64), Some(-2.5));
+    }
+
+    #[test]
+    fn only_whole_non_negative_numbers_are_indices() {
+        assert_eq!(parse("3").as_usize(), Some(3));
+        assert_eq!(parse("0.0").as_usize(), Some(0));
+        assert_eq!(parse("1.5").as_usize(), None);
+        assert_eq!(parse("-1").as_usize(), None);
+        assert_eq!(parse("\"1\"").as_usize(), None);
+    }
+
+    #[test]
+    fn escapes_are_decoded() {
+        assert_eq!(parse(r#""\"\\\/\b\f\n\r\t""#).as_str(), Some("\"\\/\u{8}\u{c}\n\r\t"));
+        assert_eq!(parse(r#""\u00e9\u20AC""#).as_str(), Some("é€"));
+        // surrogate pairs are joined into one character
+        assert_eq!(parse(r#""\ud83d\ude00""#).as_str(), Some("😀"));
+        assert_eq!(parse(r#""\ud83d""#).as_str(), Some("\u{fffd}"));
+        assert_eq!(parse("\"é\"").as_str(), Some("é"));
+    }
+
+    #[test]
+    fn strings_are_written_escaped() {
+        let value = Json::from("a\"b\\c\nd\u{1}");
+        assert_eq!(value.to_string(), r#""a\"b\\c\nd\u0001""#);
+        assert_eq!(parse(&value.to_string()), value);
+    }
+
+    #[test]
+    fn written_values_parse_back() {
+        let value = Json::object()
+            .with("name", "x")
+            .with("values", vec![1.5f32, -2.0])
+            .with("flag", true)
+            .with("nested", Json::object().with("count", 3u32));
+        assert_eq!(value.to_string(), r#"{"name":"x","values":[1.5,-2],"flag":true,"nested":{"count":3}}"#);
+        assert_eq!(parse(&value.to_string()), value);
+        assert_eq!(Json::from(f64::NAN).to_string(), "null");
+    }
+
+    #[test]
+    fn invalid_input_is_an_error() {
+        for invalid in [
+            "", "{", "[1,", "[1 2]", "{\"a\" 1}", "{1: 2}", "\"abc", "\"\\x\"", "\"\\u12\"", "tru", "nul",
+            "1.2.3", "-", "[1] 2", "{\"a\": 1,}",
+        ] {
+            assert!(Json::parse(invalid).is_err(), "{:?} parsed", invalid);
+        }
+    }
+
+    #[test]
+    fn errors_report_the_position() {
+        assert_eq!(Json::parse("[1, x]").unwrap_err().pos, 4);
+        assert_eq!(Json::parse("[1] 2").unwrap_err().pos, 4);
+    }
+
+    #[test]
+    fn deep_nesting_is_rejected() {
+        let nested = |depth: usize| "[".repeat(depth) + &"]".repeat(depth);
+        assert!(Json::parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(Json::parse(&nested(MAX_DEPTH + 1)).is_err());
+        assert!(Json::parse(&nested(100_000)).is_err());
+    }
+}
@@ -0,0 +1,40 @@
+// returns the resident set size of the process in bytes, if the platform reports it
+#[cfg(target_os = "linux")]
+pub fn memory_usage() -> Option<usize> {
+    status_kib("VmRSS:").map(|kib| kib * 1024)
+}
+
+// returns the peak resident set size of the process in bytes since the start or the last
+// successful reset_peak_memory_usage, if the platform reports it
+#[cfg(target_os = "linux")]
+pub fn peak_memory_usage() -> Option<usize> {
+    status_kib("VmHWM:").map(|kib| kib * 1024)
+}
+
+// resets the peak resident set size to the current one, returns whether the platform allows it
+#[cfg(target_os = "linux")]
+pub fn reset_peak_memory_usage() -> bool {
+    std::fs::write("/proc/self/clear_refs", "5").is_ok()
+}
+
+#[cfg(target_os = "linux")]
+fn status_kib(field: &str) -> Option<usize> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with(field))?;
+    line.split_whitespace().nth(1)?.parse::<usize>().ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn memory_usage() -> Option<usize> {
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn peak_memory_usage() -> Option<usize> {
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn reset_peak_memory_usage() -> bool {
+    false
+}
@@ -1 +1,4 @@
 pub mod error;
+pub mod json;
+pub mod memory;
+pub mod args;
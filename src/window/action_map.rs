@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::fmt;
+use glfw::{GamepadAxis, GamepadButton, Key, MouseButton};
+use crate::resource::resource::Resource;
+use crate::util::error::ActionMapError;
+use crate::util::json::Json;
+
+// actions and their default bindings, a config file can rebind any of them
+const DEFAULT_BINDINGS: [(&str, &[&str]); 30] = [
+    ("move_forward", &["W", "GamepadAxisLeftY-"]),
+    ("move_back", &["S", "GamepadAxisLeftY+"]),
+    ("move_right", &["D", "GamepadAxisLeftX+"]),
+    ("move_left", &["A", "GamepadAxisLeftX-"]),
+    ("move_up", &["Space", "GamepadButtonRightBumper"]),
+    ("move_down", &["LeftShift", "GamepadButtonLeftBumper"]),
+    ("look_right", &["GamepadAxisRightX+"]),
+    ("look_left", &["GamepadAxisRightX-"]),
+    ("look_up", &["GamepadAxisRightY-"]),
+    ("look_down", &["GamepadAxisRightY+"]),
+    ("capture_cursor", &["MouseButton1"]),
+    ("release_cursor", &["Escape"]),
+    ("orbit_rotate", &["MouseButton1"]),
+    ("orbit_pan", &["MouseButton2"]),
+    ("orbit_pan_modifier", &["LeftShift"]),
+    ("pick_target", &["MouseButton3", "GamepadButtonA"]),
+    ("toggle_gui", &["F1", "GamepadButtonStart"]),
+    ("log_gpu_times", &["F2"]),
+    ("write_gpu_times", &["F3"]),
+    ("screenshot", &["F12"]),
+    ("toggle_recording", &["F10"]),
+    ("add_keyframe", &["K"]),
+    ("toggle_path_playback", &["L"]),
+    ("next_camera_controller", &["C", "GamepadButtonBack"]),
+    ("next_debug_view", &["Tab"]),
+    ("previous_debug_view", &["LeftControl+Tab"]),
+    ("toggle_bvh_overlay", &["B"]),
+    ("bvh_level_up", &["Up"]),
+    ("bvh_level_down", &["Down"]),
+    ("pick_triangle", &["P"]),
+];
+
+// the glfw key names as they are written in configs
+macro_rules! key_names {
+    ($($key:ident),*) => { [$((stringify!($key), Key::$key)),*] };
+}
+
+const KEYS: [(&str, Key); 120] = key_names!(
+    Space, Apostrophe, Comma, Minus, Period, Slash, Num0, Num1, Num2, Num3, Num4, Num5, Num6, Num7,
+    Num8, Num9, Semicolon, Equal, A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V,
+    W, X, Y, Z, LeftBracket, Backslash, RightBracket, GraveAccent, World1, World2, Escape, Enter,
+    Tab, Backspace, Insert, Delete, Right, Left, Down, Up, PageUp, PageDown, Home, End, CapsLock,
+    ScrollLock, NumLock, PrintScreen, Pause, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, F13,
+    F14, F15, F16, F17, F18, F19, F20, F21, F22, F23, F24, F25, Kp0, Kp1, Kp2, Kp3, Kp4, Kp5, Kp6,
+    Kp7, Kp8, Kp9, KpDecimal, KpDivide, KpMultiply, KpSubtract, KpAdd, KpEnter, KpEqual, LeftShift,
+    LeftControl, LeftAlt, LeftSuper, RightShift, RightControl, RightAlt, RightSuper, Menu
+);
+
+fn parse_key(name: &str) -> Option<Key> {
+    KEYS.iter().find(|(key_name, _)| *key_name == name).map(|(_, key)| *key)
+}
+
+fn key_name(key: Key) -> &'static str {
+    KEYS.iter().find(|(_, k)| *k == key).map_or("Unknown", |(name, _)| name)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    // a key, optionally only while a modifier key is held
+    Key { key: Key, modifier: Option<Key> },
+    MouseButton(MouseButton),
+    GamepadButton(GamepadButton),
+    // one direction of a gamepad axis, true for the positive half
+    GamepadAxis(GamepadAxis, bool),
+}
+
+impl Binding {
+    // names are the glfw names, mouse and gamepad names are prefixed with "Mouse" and "Gamepad",
+    // e.g. "W", "LeftControl+Tab", "MouseButton1", "GamepadButtonA" or "GamepadAxisLeftX+"
+    pub fn parse(name: &str) -> Option<Self> {
+        if let Some(axis) = name.strip_prefix("GamepadAxis") {
+            let (axis, positive) = match axis.as_bytes().last()? {
+                b'+' => (&axis[..axis.len() - 1], true),
+                b'-' => (&axis[..axis.len() - 1], false),
+                _ => return None,
+            };
+            return (0..6).filter_map(GamepadAxis::from_i32)
+                .find(|a| format!("{:?}", a) == format!("Axis{}", axis))
+                .map(|a| Binding::GamepadAxis(a, positive));
+        }
+        if let Some(button) = name.strip_prefix("Gamepad") {
+            return (0..15).filter_map(GamepadButton::from_i32)
+                .find(|b| format!("{:?}", b) == button)
+                .map(Binding::GamepadButton);
+        }
+        if let Some(button) = name.strip_prefix("Mouse") {
+            return (0..8).filter_map(MouseButton::from_i32)
+                .find(|b| format!("{:?}", b) == button)
+                .map(Binding::MouseButton);
+        }
+        match name.split_once('+') {
+            Some((modifier, key)) => Some(Binding::Key { key: parse_key(key)?, modifier: Some(parse_key(modifier)?) }),
+            None => Some(Binding::Key { key: parse_key(name)?, modifier: None }),
+        }
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Binding::Key { key, modifier: Some(modifier) } => write!(f, "{}+{}", key_name(*modifier), key_name(*key)),
+            Binding::Key { key, modifier: None } => f.write_str(key_name(*key)),
+            Binding::MouseButton(button) => write!(f, "Mouse{:?}", button),
+            Binding::GamepadButton(button) => write!(f, "Gamepad{:?}", button),
+            Binding::GamepadAxis(axis, positive) => write!(f, "Gamepad{:?}{}", axis, if *positive { "+" } else { "-" }),
+        }
+    }
+}
+
+// named actions bound to keys, mouse buttons and gamepad inputs, input is queried by action
+pub struct ActionMap {
+    bindings: HashMap<String, Vec<Binding>>,
+}
+
+impl Default for ActionMap {
+    fn default() -> Self {
+        let bindings = DEFAULT_BINDINGS.iter()
+            .map(|(action, names)| (action.to_string(), names.iter().map(|name| Binding::parse(name).unwrap()).collect()))
+            .collect();
+        Self { bindings }
+    }
+}
+
+impl ActionMap {
+    // the defaults with the actions in the file rebound, the file is an object of action names
+    // and arrays of binding names
+    pub fn load(res: &Resource, name: &str) -> Result<Self, ActionMapError> {
+        let str = res.read_file(name).map_err(ActionMapError::ResourceError)?;
+        let json = Json::parse(&str).map_err(ActionMapError::JsonError)?;
+        let Json::Object(entries) = json else { return Err(ActionMapError::NotAnObject) };
+
+        let mut map = Self::default();
+        for (action, names) in &entries {
+            if !map.bindings.contains_key(action) { return Err(ActionMapError::UnknownAction(action.clone())) }
+            let names = names.as_array().ok_or_else(|| ActionMapError::InvalidBinding { action: action.clone(), binding: names.to_string() })?;
+            let bindings = names.iter()
+                .map(|name| name.as_str().and_then(Binding::parse)
+                    .ok_or_else(|| ActionMapError::InvalidBinding { action: action.clone(), binding: name.to_string() }))
+                .collect::<Result<_, _>>()?;
+            map.bindings.insert(action.clone(), bindings);
+        }
+        Ok(map)
+    }
+
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        debug_assert!(self.bindings.contains_key(action), "unknown action {}", action);
+        self.bindings.get(action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn rebind(&mut self, action: &str, bindings: Vec<Binding>) {
+        self.bindings.insert(action.to_owned(), bindings);
+    }
+}
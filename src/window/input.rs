@@ -1,22 +1,75 @@
-use glfw::{Key, MouseButton};
-use std::collections::HashMap;
+use glfw::{Action, GamepadAxis, GamepadButton, GamepadState, Key, MouseButton};
+use std::collections::{HashMap, HashSet};
+use crate::window::action_map::{ActionMap, Binding};
 
-fn to_axis(pos: bool, neg: bool) -> f32 {
-    pos as i32 as f32 - neg as i32 as f32
+// stick deflections below this are treated as rest
+const AXIS_DEADZONE: f32 = 0.15;
+// axis value above which a bound axis counts as pressed
+const AXIS_PRESS_THRESHOLD: f32 = 0.5;
+const GAMEPAD_BUTTON_COUNT: usize = 15;
+const GAMEPAD_AXIS_COUNT: usize = 6;
+
+#[derive(Clone, Copy, Default)]
+struct Gamepad {
+    buttons: [bool; GAMEPAD_BUTTON_COUNT],
+    axes: [f32; GAMEPAD_AXIS_COUNT],
+}
+
+impl Gamepad {
+    fn from_state(state: &GamepadState) -> Self {
+        Self {
+            buttons: std::array::from_fn(|i| GamepadButton::from_i32(i as i32)
+                .is_some_and(|button| state.get_button_state(button) != Action::Release)),
+            axes: std::array::from_fn(|i| GamepadAxis::from_i32(i as i32).map_or(0.0, |axis| state.get_axis(axis))),
+        }
+    }
+
+    // the deflection in one direction, rescaled to start at the deadzone
+    fn axis(&self, axis: GamepadAxis, positive: bool) -> f32 {
+        let value = self.axes[axis as usize] * if positive { 1.0 } else { -1.0 };
+        ((value - AXIS_DEADZONE) / (1.0 - AXIS_DEADZONE)).clamp(0.0, 1.0)
+    }
 }
 
 pub struct Input {
     keys: HashMap<Key, bool>,
+    // keys pressed down and released since the last events were handled
+    keys_down: HashSet<Key>,
+    keys_up: HashSet<Key>,
     buttons: HashMap<MouseButton, bool>,
+    buttons_down: HashSet<MouseButton>,
+    buttons_up: HashSet<MouseButton>,
     cursor_pos: (f32, f32),
+    // cursor movement and scrolling since the last events were handled
+    cursor_delta: (f32, f32),
+    scroll: (f32, f32),
+    // the first cursor position after the cursor entered has no delta
+    cursor_known: bool,
+    // characters typed since the last events were handled
+    text: String,
+    // the first connected gamepad in this and the previous frame
+    gamepad: Gamepad,
+    prev_gamepad: Gamepad,
+    actions: ActionMap,
 }
 
 impl Input {
     pub fn new() -> Input {
         Input {
             keys: Default::default(),
+            keys_down: Default::default(),
+            keys_up: Default::default(),
             buttons: Default::default(),
+            buttons_down: Default::default(),
+            buttons_up: Default::default(),
             cursor_pos: (0.0, 0.0),
+            cursor_delta: (0.0, 0.0),
+            scroll: (0.0, 0.0),
+            cursor_known: false,
+            text: String::new(),
+            gamepad: Gamepad::default(),
+            prev_gamepad: Gamepad::default(),
+            actions: ActionMap::default(),
         }
     }
 
@@ -24,34 +77,159 @@ impl Input {
         **self.keys.get(&key).get_or_insert(&false)
     }
 
+    // true only in the frame the key went down, for toggles that should not repeat while held
+    pub fn key_down(&self, key: Key) -> bool {
+        self.keys_down.contains(&key)
+    }
+
+    pub fn key_released(&self, key: Key) -> bool {
+        self.keys_up.contains(&key)
+    }
+
     pub fn button_pressed(&self, button: MouseButton) -> bool {
         **self.buttons.get(&button).get_or_insert(&false)
     }
 
+    pub fn button_down(&self, button: MouseButton) -> bool {
+        self.buttons_down.contains(&button)
+    }
+
+    pub fn button_released(&self, button: MouseButton) -> bool {
+        self.buttons_up.contains(&button)
+    }
+
     pub fn cursor_pos(&self) -> (f32, f32) {
         self.cursor_pos
     }
 
+    pub fn cursor_delta(&self) -> (f32, f32) {
+        self.cursor_delta
+    }
+
+    pub fn scroll(&self) -> (f32, f32) {
+        self.scroll
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    // the raw value of a gamepad axis in [-1, 1], 0 without a gamepad
+    pub fn gamepad_axis(&self, axis: GamepadAxis) -> f32 {
+        self.gamepad.axes[axis as usize]
+    }
+
+    pub fn action_map(&self) -> &ActionMap {
+        &self.actions
+    }
+
+    pub fn set_action_map(&mut self, actions: ActionMap) {
+        self.actions = actions;
+    }
+
+    // whether any binding of the action is held
+    pub fn action_pressed(&self, action: &str) -> bool {
+        self.actions.bindings(action).iter().any(|binding| match *binding {
+            Binding::Key { key, modifier } => self.key_pressed(key) && modifier.is_none_or(|m| self.key_pressed(m)),
+            Binding::MouseButton(button) => self.button_pressed(button),
+            Binding::GamepadButton(button) => self.gamepad.buttons[button as usize],
+            Binding::GamepadAxis(axis, positive) => self.gamepad.axis(axis, positive) > AXIS_PRESS_THRESHOLD,
+        })
+    }
+
+    // true only in the frame a binding of the action went down
+    pub fn action_down(&self, action: &str) -> bool {
+        self.actions.bindings(action).iter().any(|binding| match *binding {
+            Binding::Key { key, modifier } => self.key_down(key) && modifier.is_none_or(|m| self.key_pressed(m)),
+            Binding::MouseButton(button) => self.button_down(button),
+            Binding::GamepadButton(button) => self.gamepad.buttons[button as usize] && !self.prev_gamepad.buttons[button as usize],
+            Binding::GamepadAxis(axis, positive) => self.gamepad.axis(axis, positive) > AXIS_PRESS_THRESHOLD
+                && self.prev_gamepad.axis(axis, positive) <= AXIS_PRESS_THRESHOLD,
+        })
+    }
+
+    pub fn action_released(&self, action: &str) -> bool {
+        self.actions.bindings(action).iter().any(|binding| match *binding {
+            Binding::Key { key, .. } => self.key_released(key),
+            Binding::MouseButton(button) => self.button_released(button),
+            Binding::GamepadButton(button) => !self.gamepad.buttons[button as usize] && self.prev_gamepad.buttons[button as usize],
+            Binding::GamepadAxis(axis, positive) => self.gamepad.axis(axis, positive) <= AXIS_PRESS_THRESHOLD
+                && self.prev_gamepad.axis(axis, positive) > AXIS_PRESS_THRESHOLD,
+        })
+    }
+
+    // how far the action is pressed in [0, 1], keys and buttons are either 0 or 1
+    pub fn action_value(&self, action: &str) -> f32 {
+        self.actions.bindings(action).iter().map(|binding| match *binding {
+            Binding::GamepadAxis(axis, positive) => self.gamepad.axis(axis, positive),
+            Binding::Key { key, modifier } => (self.key_pressed(key) && modifier.is_none_or(|m| self.key_pressed(m))) as i32 as f32,
+            Binding::MouseButton(button) => self.button_pressed(button) as i32 as f32,
+            Binding::GamepadButton(button) => self.gamepad.buttons[button as usize] as i32 as f32,
+        }).fold(0.0, f32::max)
+    }
+
+    // an axis in [-1, 1] from a pair of opposing actions
+    pub fn action_axis(&self, positive: &str, negative: &str) -> f32 {
+        self.action_value(positive) - self.action_value(negative)
+    }
+
     pub fn movement(&self) -> (f32, f32, f32) {
         (
-            to_axis(self.key_pressed(Key::D), self.key_pressed(Key::A)),
-            to_axis(
-                self.key_pressed(Key::Space),
-                self.key_pressed(Key::LeftShift),
-            ),
-            to_axis(self.key_pressed(Key::W), self.key_pressed(Key::S)),
+            self.action_axis("move_right", "move_left"),
+            self.action_axis("move_up", "move_down"),
+            self.action_axis("move_forward", "move_back"),
         )
     }
 
     pub fn set_key_pressed(&mut self, key: Key, pressed: bool) {
+        if pressed && !self.key_pressed(key) { self.keys_down.insert(key); }
+        if !pressed && self.key_pressed(key) { self.keys_up.insert(key); }
         self.keys.insert(key, pressed);
     }
 
+    // resets what only lasts for a frame, before the events of the next one are handled
+    pub fn clear_frame_state(&mut self) {
+        self.keys_down.clear();
+        self.keys_up.clear();
+        self.buttons_down.clear();
+        self.buttons_up.clear();
+        self.cursor_delta = (0.0, 0.0);
+        self.scroll = (0.0, 0.0);
+        self.text.clear();
+    }
+
     pub fn set_button_pressed(&mut self, button: MouseButton, pressed: bool) {
+        if pressed && !self.button_pressed(button) { self.buttons_down.insert(button); }
+        if !pressed && self.button_pressed(button) { self.buttons_up.insert(button); }
         self.buttons.insert(button, pressed);
     }
 
     pub fn set_cursor_pos(&mut self, x: f32, y: f32) {
-        self.cursor_pos = (x, y)
+        if self.cursor_known {
+            self.cursor_delta.0 += x - self.cursor_pos.0;
+            self.cursor_delta.1 += y - self.cursor_pos.1;
+        }
+        self.cursor_pos = (x, y);
+        self.cursor_known = true;
+    }
+
+    pub fn add_scroll(&mut self, x: f32, y: f32) {
+        self.scroll.0 += x;
+        self.scroll.1 += y;
+    }
+
+    pub fn add_text(&mut self, c: char) {
+        self.text.push(c);
+    }
+
+    // polled once per frame, None without a connected gamepad
+    pub fn set_gamepad_state(&mut self, state: Option<GamepadState>) {
+        self.prev_gamepad = self.gamepad;
+        self.gamepad = state.map_or_else(Gamepad::default, |state| Gamepad::from_state(&state));
+    }
+
+    // the cursor jumps when it enters the window or is captured
+    pub fn reset_cursor(&mut self) {
+        self.cursor_known = false;
     }
 }
@@ -1,2 +1,3 @@
 pub mod input;
+pub mod action_map;
 pub mod window;
@@ -1,61 +1,136 @@
 use crate::util::error::WindowError;
 use crate::window::input::Input;
-use glfw::{Action, Context, Glfw, WindowEvent, WindowHint};
+use glfw::{Action, Context, CursorMode, Glfw, JoystickId, SwapInterval, WindowEvent, WindowHint};
+use std::fmt;
 use std::sync::mpsc::Receiver;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayMode {
+    Windowed,
+    // an undecorated window covering the primary monitor at its current video mode
+    Borderless,
+    // exclusive fullscreen on the primary monitor at the size of the window options
+    Fullscreen,
+}
+
+impl DisplayMode {
+    pub const ALL: [DisplayMode; 3] = [DisplayMode::Windowed, DisplayMode::Borderless, DisplayMode::Fullscreen];
+}
+
+impl fmt::Display for DisplayMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DisplayMode::Windowed => "windowed",
+            DisplayMode::Borderless => "borderless",
+            DisplayMode::Fullscreen => "fullscreen",
+        })
+    }
+}
+
+pub struct WindowOptions {
+    // size of the window in screen coordinates, the resolution in fullscreen
+    pub width: u32,
+    pub height: u32,
+    pub display_mode: DisplayMode,
+    pub vsync: bool,
+}
+
+impl Default for WindowOptions {
+    fn default() -> Self {
+        Self { width: 1000, height: 800, display_mode: DisplayMode::Windowed, vsync: false }
+    }
+}
+
 pub struct Window {
     glfw: Glfw,
     window_handle: Option<glfw::Window>,
     events: Receiver<(f64, WindowEvent)>,
+    // the events handled in the current frame, for consumers needing more than the input state
+    frame_events: Vec<WindowEvent>,
     input: Input,
+    // size of the framebuffer in pixels, larger than the window size on hidpi displays
     width: u32,
     height: u32,
+    // size of the window in screen coordinates, which cursor positions are given in
+    window_size: (u32, u32),
     resized: bool,
+    display_mode: DisplayMode,
+    // position and size the window returns to when leaving fullscreen
+    windowed_rect: (i32, i32, u32, u32),
+    // resolution of exclusive fullscreen
+    fullscreen_size: (u32, u32),
+    vsync: bool,
     prev_time: f64,
     delta_time: f64,
+    // replaces the measured frame time, e.g. while recording frames
+    fixed_timestep: Option<f64>,
 }
 
 impl Window {
-    pub fn new(width: u32, height: u32, title: &str) -> Result<Self, WindowError> {
+    pub fn new(title: &str, options: &WindowOptions) -> Result<Self, WindowError> {
         use glfw::fail_on_errors;
         let mut glfw: Glfw = match glfw::init(fail_on_errors!()) {
             Ok(glfw) => glfw,
             Err(_) => return Err(WindowError::GlfwInitError),
         };
 
+        // hints only apply to windows created after them
+        glfw.default_window_hints();
+        glfw.window_hint(WindowHint::ContextVersion(4, 6));
+        glfw.window_hint(WindowHint::OpenGlProfile(glfw::OpenGlProfileHint::Core));
+        glfw.window_hint(WindowHint::OpenGlForwardCompat(true));
+        glfw.window_hint(WindowHint::Resizable(true));
+        // the window is scaled with the content scale of the monitor and gets a framebuffer at the
+        // full resolution of hidpi displays
+        glfw.window_hint(WindowHint::ScaleToMonitor(true));
+        glfw.window_hint(WindowHint::CocoaRetinaFramebuffer(true));
+
         let (mut window, events) =
-            match glfw.create_window(width, height, title, glfw::WindowMode::Windowed) {
+            match glfw.create_window(options.width, options.height, title, glfw::WindowMode::Windowed) {
                 Some(we) => we,
                 None => return Err(WindowError::CreateWindowError),
             };
 
         window.make_current();
 
-        glfw.default_window_hints();
-        glfw.window_hint(WindowHint::ContextVersion(3, 3));
-        glfw.window_hint(WindowHint::OpenGlProfile(glfw::OpenGlProfileHint::Core));
-        glfw.window_hint(WindowHint::OpenGlForwardCompat(true));
-        glfw.window_hint(WindowHint::Resizable(true));
-
         window.set_key_polling(true);
         window.set_mouse_button_polling(true);
         window.set_cursor_pos_polling(true);
         window.set_size_polling(true);
+        window.set_framebuffer_size_polling(true);
+        window.set_scroll_polling(true);
+        window.set_char_polling(true);
+        window.set_cursor_enter_polling(true);
 
         gl::load_with(|s| window.get_proc_address(s) as *const _);
-        glfw.set_swap_interval(glfw::SwapInterval::None);
 
-        Ok(Window {
+        let (x, y) = window.get_pos();
+        let (window_width, window_height) = window.get_size();
+        let (width, height) = window.get_framebuffer_size();
+        let mut window = Window {
             window_handle: Some(window),
             events,
+            frame_events: vec![],
             input: Input::new(),
-            width,
-            height,
+            width: width as u32,
+            height: height as u32,
+            window_size: (window_width as u32, window_height as u32),
             resized: false,
+            display_mode: DisplayMode::Windowed,
+            windowed_rect: (x, y, window_width as u32, window_height as u32),
+            fullscreen_size: (options.width, options.height),
+            vsync: options.vsync,
             prev_time: glfw.get_time(),
             delta_time: 0.01,
+            fixed_timestep: None,
             glfw,
-        })
+        };
+        window.set_vsync(options.vsync);
+        // the window still opens, windowed, when the display mode cannot be set
+        if let Err(e) = window.set_display_mode(options.display_mode) {
+            println!("Failed to set display mode {:?}, staying windowed: {:?}", options.display_mode, e);
+        }
+        Ok(window)
     }
 
     pub fn should_close(&self) -> bool {
@@ -69,7 +144,10 @@ impl Window {
     pub fn handle_events(&mut self) {
         self.glfw.poll_events();
         self.resized = false;
+        self.input.clear_frame_state();
+        self.frame_events.clear();
         for (_, event) in glfw::flush_messages(&self.events) {
+            self.frame_events.push(event.clone());
             match event {
                 WindowEvent::Key(key, _, action, _) => {
                     self.input.set_key_pressed(key, action != Action::Release)
@@ -78,7 +156,11 @@ impl Window {
                     .input
                     .set_button_pressed(button, action != Action::Release),
                 WindowEvent::CursorPos(x, y) => self.input.set_cursor_pos(x as f32, y as f32),
-                WindowEvent::Size(w, h) => {
+                WindowEvent::CursorEnter(_) => self.input.reset_cursor(),
+                WindowEvent::Scroll(x, y) => self.input.add_scroll(x as f32, y as f32),
+                WindowEvent::Char(c) => self.input.add_text(c),
+                WindowEvent::Size(w, h) => self.window_size = (w as u32, h as u32),
+                WindowEvent::FramebufferSize(w, h) => {
                     let width = w as u32;
                     let height = h as u32;
                     if self.width != width || self.height != height {
@@ -90,8 +172,9 @@ impl Window {
                 _ => {}
             }
         }
+        self.input.set_gamepad_state(self.glfw.get_joystick(JoystickId::Joystick1).get_gamepad_state());
         let curr_time = self.glfw.get_time();
-        self.delta_time = curr_time - self.prev_time;
+        self.delta_time = self.fixed_timestep.unwrap_or(curr_time - self.prev_time);
         self.prev_time = curr_time;
     }
 
@@ -103,6 +186,19 @@ impl Window {
         &self.input
     }
 
+    pub fn input_mut(&mut self) -> &mut Input {
+        &mut self.input
+    }
+
+    pub fn frame_events(&self) -> &[WindowEvent] {
+        &self.frame_events
+    }
+
+    pub fn time(&self) -> f64 {
+        self.prev_time
+    }
+
+    // framebuffer size in pixels
     pub fn width(&self) -> u32 {
         self.width
     }
@@ -114,6 +210,102 @@ impl Window {
     }
     pub fn dt(&self) -> f32 { self.delta_time as f32 }
 
+    // size in screen coordinates
+    pub fn window_size(&self) -> (u32, u32) {
+        self.window_size
+    }
+
+    // framebuffer pixels per screen coordinate, 2 on retina displays and 1 where the window itself
+    // is scaled to the monitor
+    pub fn framebuffer_scale(&self) -> (f32, f32) {
+        (
+            self.width as f32 / self.window_size.0.max(1) as f32,
+            self.height as f32 / self.window_size.1.max(1) as f32,
+        )
+    }
+
+    // a cursor position in framebuffer pixels, with the origin in the upper left corner
+    pub fn framebuffer_pos(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        let (scale_x, scale_y) = self.framebuffer_scale();
+        (x * scale_x, y * scale_y)
+    }
+
+    // the ui scale the monitor asks for, e.g. 1.5 at 150% scaling
+    pub fn content_scale(&self) -> f32 {
+        self.window_handle.as_ref().unwrap().get_content_scale().0
+    }
+
+    pub fn display_mode(&self) -> DisplayMode {
+        self.display_mode
+    }
+
+    // without a primary monitor the window stays windowed
+    pub fn set_display_mode(&mut self, mode: DisplayMode) -> Result<(), WindowError> {
+        if mode == self.display_mode { return Ok(()) }
+        let window = self.window_handle.as_mut().unwrap();
+        if self.display_mode == DisplayMode::Windowed {
+            let (x, y) = window.get_pos();
+            let (width, height) = window.get_size();
+            self.windowed_rect = (x, y, width as u32, height as u32);
+        }
+        let fullscreen_size = self.fullscreen_size;
+        let windowed_rect = self.windowed_rect;
+        self.glfw.with_primary_monitor(|_, monitor| {
+            match mode {
+                DisplayMode::Windowed => {
+                    let (x, y, width, height) = windowed_rect;
+                    window.set_decorated(true);
+                    window.set_monitor(glfw::WindowMode::Windowed, x, y, width, height, None);
+                }
+                DisplayMode::Borderless => {
+                    let monitor = monitor.ok_or(WindowError::NoMonitor)?;
+                    let video_mode = monitor.get_video_mode().ok_or(WindowError::NoMonitor)?;
+                    let (x, y) = monitor.get_pos();
+                    window.set_decorated(false);
+                    window.set_monitor(glfw::WindowMode::Windowed, x, y, video_mode.width, video_mode.height, None);
+                }
+                DisplayMode::Fullscreen => {
+                    let monitor = monitor.ok_or(WindowError::NoMonitor)?;
+                    let (width, height) = fullscreen_size;
+                    window.set_monitor(glfw::WindowMode::FullScreen(monitor), 0, 0, width, height, None);
+                }
+            }
+            Ok(())
+        })?;
+        self.display_mode = mode;
+        // the swap interval can be reset with the video mode
+        self.set_vsync(self.vsync);
+        Ok(())
+    }
+
+    pub fn vsync(&self) -> bool {
+        self.vsync
+    }
+
+    pub fn set_vsync(&mut self, vsync: bool) {
+        self.vsync = vsync;
+        self.glfw.set_swap_interval(if vsync { SwapInterval::Sync(1) } else { SwapInterval::None });
+    }
+
+    // a captured cursor is hidden and not limited by the window, for relative mouse movement
+    pub fn set_cursor_captured(&mut self, captured: bool) {
+        let mode = if captured { CursorMode::Disabled } else { CursorMode::Normal };
+        self.window_handle.as_mut().unwrap().set_cursor_mode(mode);
+        self.input.reset_cursor();
+    }
+
+    pub fn cursor_captured(&self) -> bool {
+        self.window_handle.as_ref().unwrap().get_cursor_mode() == CursorMode::Disabled
+    }
+
+    pub fn set_fixed_timestep(&mut self, timestep: Option<f32>) {
+        self.fixed_timestep = timestep.map(|timestep| timestep as f64);
+    }
+
+    pub fn fixed_timestep(&self) -> Option<f32> {
+        self.fixed_timestep.map(|timestep| timestep as f32)
+    }
+
     pub fn resized(&self) -> bool {
         self.resized
     }